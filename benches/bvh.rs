@@ -0,0 +1,88 @@
+//! Compares intersecting rays with the BVH to testing every shape one by one.
+//! Run with `cargo bench --bench bvh`.
+
+use ascii_engine::core::bvh::{Aabb, Bvh};
+use ascii_engine::core::camera::Camera;
+use ascii_engine::core::common::{HitData, Ray};
+use ascii_engine::core::material::{Material, MaterialId};
+use ascii_engine::core::scene::Scene;
+use ascii_engine::core::shapes::{Hittable, Shape, Sphere};
+use cgmath::{Vector3, Zero};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+const RAY_COUNT: usize = 2_000;
+
+fn main() {
+    println!("{:>10} {:>12} {:>14} {:>14} {:>10}", "shapes", "build", "linear", "bvh", "speedup");
+    for shape_count in [100, 1_000, 10_000, 100_000] {
+        let mut rng = StdRng::seed_from_u64(shape_count as u64);
+        let shapes = random_shapes(shape_count, &mut rng);
+        let rays = random_rays(RAY_COUNT, &mut rng);
+
+        let build_start = Instant::now();
+        let bounds: Vec<Aabb> = shapes.iter().filter_map(|shape| shape.bounds()).collect();
+        let bvh = Bvh::build(&bounds);
+        let build_time = build_start.elapsed();
+
+        let linear_time = time_rays(&rays, |ray| {
+            shapes.iter()
+                .filter_map(|shape| shape.intersect(ray))
+                .min_by(|hit1, hit2| hit1.t.total_cmp(&hit2.t))
+        });
+        let bvh_time = time_rays(&rays, |ray| bvh.closest_hit(ray, |i| shapes[i].intersect(ray)));
+
+        println!(
+            "{:>10} {:>10.2}ms {:>10.0}ns/ray {:>10.0}ns/ray {:>9.1}x",
+            shape_count,
+            build_time.as_secs_f64() * 1000.0,
+            nanos_per_ray(linear_time),
+            nanos_per_ray(bvh_time),
+            linear_time.as_secs_f64() / bvh_time.as_secs_f64(),
+        );
+    }
+}
+
+fn time_rays(rays: &[Ray], intersect: impl Fn(&Ray) -> Option<HitData>) -> Duration {
+    let start = Instant::now();
+    for ray in rays {
+        black_box(intersect(ray));
+    }
+    start.elapsed()
+}
+
+fn nanos_per_ray(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1e9 / RAY_COUNT as f64
+}
+
+// small spheres filling a cube, so the density stays the same as the count grows
+fn random_shapes(count: usize, rng: &mut StdRng) -> Vec<Shape> {
+    let material = any_material();
+    let half_size = (count as f32).cbrt() * 2.0;
+    (0..count)
+        .map(|_| {
+            let center = random_vector(rng) * half_size;
+            let radius = 0.2 + rng.random::<f32>() * 0.6;
+            Sphere::new(center, radius, material).into()
+        })
+        .collect()
+}
+
+// the shapes are only intersected, not shaded, so their material doesn't matter
+fn any_material() -> MaterialId {
+    let mut scene = Scene::new(Camera::from_position_and_target(Vector3::zero(), -Vector3::unit_z()));
+    scene.add_material(Material::default())
+}
+
+// rays from outside of the cube of spheres towards its middle
+fn random_rays(count: usize, rng: &mut StdRng) -> Vec<Ray> {
+    (0..count)
+        .map(|_| Ray::new(Vector3::new(0.0, 0.0, 200.0), random_vector(rng) * 0.2 - Vector3::unit_z()))
+        .collect()
+}
+
+fn random_vector(rng: &mut StdRng) -> Vector3<f32> {
+    Vector3::new(rng.random::<f32>(), rng.random::<f32>(), rng.random::<f32>()) * 2.0 - Vector3::new(1.0, 1.0, 1.0)
+}
@@ -0,0 +1,303 @@
+use ascii_engine::core::scene_generator::Palette;
+use ascii_engine::render::file_renderer::ToneMapping;
+use ascii_engine::render::color::ColorDepth;
+use ascii_engine::render::dithering::Dithering;
+use ascii_engine::render::terminal_renderer::TerminalRenderType;
+use cgmath::Vector3;
+use clap::{Parser, ValueEnum};
+use std::path::PathBuf;
+use std::str::FromStr;
+
+/// Renders 3D scenes in the terminal with a ray tracer.
+///
+/// Without a scene file a scene of random spheres is generated. With --output a single frame is rendered
+/// into an image file, with --benchmark frames are rendered without showing them, so neither needs a terminal.
+#[derive(Parser)]
+#[command(version, about)]
+pub struct Cli {
+    /// Scene file in TOML to show
+    pub scene: Option<PathBuf>,
+
+    /// Wavefront OBJ model to add to the scene
+    #[arg(long, value_name = "OBJ")]
+    pub model: Option<PathBuf>,
+
+    /// Resolution of the image in pixels. In the terminal it is limited by the size of the terminal,
+    /// which is used by default
+    #[arg(long, value_name = "WIDTHxHEIGHT")]
+    pub size: Option<Resolution>,
+
+    /// How the image is drawn in the terminal at the start, tab switches between the render types
+    #[arg(long, value_enum, default_value_t = RenderTypeArg::Colored)]
+    pub render_type: RenderTypeArg,
+
+    /// The colors the terminal can show, by default they are detected from COLORTERM and TERM
+    #[arg(long, value_enum, default_value_t = ColorsArg::Auto)]
+    pub colors: ColorsArg,
+
+    /// Dithers the colors with Floyd–Steinberg when the terminal can't show true colors
+    #[arg(long)]
+    pub color_dithering: bool,
+
+    /// How the brightness is turned into the dots of the braille render type
+    #[arg(long, value_enum, default_value_t = DitheringArg::Ordered)]
+    pub dithering: DitheringArg,
+
+    /// Maximum number of frames per second in the terminal, 0 for no limit
+    #[arg(long, value_name = "FPS", default_value_t = 144)]
+    pub fps_cap: u32,
+
+    /// Seed of the random numbers, e.g. of the generated scene and the path tracer. Random by default,
+    /// the seed of a generated scene is printed so it can be shown again
+    #[arg(long)]
+    pub seed: Option<u64>,
+
+    /// Number of spheres in the generated scene
+    #[arg(long, value_name = "COUNT", default_value_t = 10)]
+    pub spheres: usize,
+
+    /// Colors of the spheres in the generated scene
+    #[arg(long, value_enum, default_value_t = PaletteArg::Random)]
+    pub palette: PaletteArg,
+
+    /// Lets the spheres in the generated scene intersect each other
+    #[arg(long)]
+    pub allow_overlap: bool,
+
+    /// Position of the camera at the start, overriding the one of the scene
+    #[arg(long, value_name = "X,Y,Z", value_parser = parse_vector, allow_hyphen_values = true)]
+    pub camera_position: Option<Vector3<f32>>,
+
+    /// Point the camera looks at at the start, overriding the direction of the scene
+    #[arg(long, value_name = "X,Y,Z", value_parser = parse_vector, allow_hyphen_values = true)]
+    pub camera_target: Option<Vector3<f32>>,
+
+    /// Renders a single frame into a PNG or PPM file instead of showing the scene in the terminal
+    #[arg(short, long, value_name = "FILE")]
+    pub output: Option<PathBuf>,
+
+    /// Path traces frames rendered into a file with this many samples per pixel instead of ray tracing them
+    #[arg(long)]
+    pub samples: Option<u32>,
+
+    /// How colors brighter than white are mapped when rendering into a file
+    #[arg(long, value_enum, default_value_t = ToneMappingArg::Clamp)]
+    pub tone_mapping: ToneMappingArg,
+
+    /// Gamma of the colors when rendering into a file, 1 leaves them unchanged
+    #[arg(long, default_value_t = 1.0, value_parser = parse_gamma)]
+    pub gamma: f32,
+
+    /// Renders this many frames without a terminal and prints how long it took
+    #[arg(long, value_name = "FRAMES")]
+    pub benchmark: Option<u32>,
+
+    /// Writes the scene into a scene file before showing it, and again when it is saved with Ctrl+S
+    #[arg(long, value_name = "FILE")]
+    pub save_scene: Option<PathBuf>,
+}
+
+// where Ctrl+S saves a generated scene when no file is given with --save-scene
+const DEFAULT_SAVE_PATH: &str = "scene.toml";
+
+impl Cli {
+    /// Where Ctrl+S saves the edited scene. The loaded scene file is only written over when --save-scene names it,
+    /// otherwise the scene is saved next to it as `<name>.edited.toml`, so its comments and layout are kept.
+    pub fn save_path(&self) -> PathBuf {
+        match (&self.save_scene, &self.scene) {
+            (Some(save_scene), _) => save_scene.clone(),
+            (None, Some(scene)) => {
+                let stem = scene.file_stem().unwrap_or_default().to_string_lossy();
+                scene.with_file_name(format!("{}.edited.toml", stem))
+            }
+            (None, None) => PathBuf::from(DEFAULT_SAVE_PATH),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Resolution {
+    pub width: usize,
+    pub height: usize,
+}
+
+impl FromStr for Resolution {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Resolution, String> {
+        let invalid = || format!("invalid resolution {}, expected WIDTHxHEIGHT, e.g. 320x180", s);
+        let (width, height) = s.split_once('x').ok_or_else(invalid)?;
+        let width = width.trim().parse().map_err(|_| invalid())?;
+        let height = height.trim().parse().map_err(|_| invalid())?;
+        if width == 0 || height == 0 {
+            return Err(invalid());
+        }
+        Ok(Resolution { width, height })
+    }
+}
+
+fn parse_gamma(s: &str) -> Result<f32, String> {
+    match s.trim().parse::<f32>() {
+        Ok(gamma) if gamma.is_finite() && gamma > 0.0 => Ok(gamma),
+        _ => Err(format!("invalid gamma {}, expected a number greater than 0", s)),
+    }
+}
+
+fn parse_vector(s: &str) -> Result<Vector3<f32>, String> {
+    let components: Vec<f32> = s.split(',')
+        .map(|component| component.trim().parse())
+        .collect::<Result<_, _>>()
+        .map_err(|_| format!("invalid vector {}, expected X,Y,Z", s))?;
+    match components[..] {
+        [x, y, z] => Ok(Vector3::new(x, y, z)),
+        _ => Err(format!("invalid vector {}, expected three components X,Y,Z", s)),
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum RenderTypeArg {
+    /// Every pixel is a terminal cell with that background color
+    Colored,
+    /// Every terminal cell shows two pixels with a half block, which doubles the resolution
+    HalfBlock,
+    /// Every pixel is an ascii character that is denser for brighter pixels
+    Ascii,
+    /// Every terminal cell shows 2x4 pixels as the dots of a braille pattern, without colors
+    Braille,
+}
+
+impl From<RenderTypeArg> for TerminalRenderType {
+    fn from(render_type: RenderTypeArg) -> TerminalRenderType {
+        match render_type {
+            RenderTypeArg::Colored => TerminalRenderType::Colored,
+            RenderTypeArg::HalfBlock => TerminalRenderType::HalfBlock,
+            RenderTypeArg::Ascii => TerminalRenderType::BlackAndWhite,
+            RenderTypeArg::Braille => TerminalRenderType::Braille,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum ColorsArg {
+    /// Detected from the COLORTERM and TERM environment variables
+    Auto,
+    /// Any 24 bit color
+    Truecolor,
+    /// The 256 colors of xterm
+    Ansi256,
+    /// The 16 colors of the Linux console
+    Ansi16,
+}
+
+impl From<ColorsArg> for ColorDepth {
+    fn from(colors: ColorsArg) -> ColorDepth {
+        match colors {
+            ColorsArg::Auto => ColorDepth::detect(),
+            ColorsArg::Truecolor => ColorDepth::TrueColor,
+            ColorsArg::Ansi256 => ColorDepth::Ansi256,
+            ColorsArg::Ansi16 => ColorDepth::Ansi16,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum DitheringArg {
+    /// A regular pattern that stays in place in animations
+    Ordered,
+    /// Floyd–Steinberg error diffusion, which keeps more detail
+    ErrorDiffusion,
+}
+
+impl From<DitheringArg> for Dithering {
+    fn from(dithering: DitheringArg) -> Dithering {
+        match dithering {
+            DitheringArg::Ordered => Dithering::Ordered,
+            DitheringArg::ErrorDiffusion => Dithering::ErrorDiffusion,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum PaletteArg {
+    Random,
+    Pastel,
+    Grayscale,
+}
+
+impl From<PaletteArg> for Palette {
+    fn from(palette: PaletteArg) -> Palette {
+        match palette {
+            PaletteArg::Random => Palette::Random,
+            PaletteArg::Pastel => Palette::Pastel,
+            PaletteArg::Grayscale => Palette::Grayscale,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum ToneMappingArg {
+    Clamp,
+    Reinhard,
+    Aces,
+}
+
+impl From<ToneMappingArg> for ToneMapping {
+    fn from(tone_mapping: ToneMappingArg) -> ToneMapping {
+        match tone_mapping {
+            ToneMappingArg::Clamp => ToneMapping::Clamp,
+            ToneMappingArg::Reinhard => ToneMapping::Reinhard,
+            ToneMappingArg::Aces => ToneMapping::Aces,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    #[test]
+    fn cli_is_consistent() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn parses_options() {
+        let cli = Cli::try_parse_from([
+            "ascii_engine", "scene.toml", "--size", "64x32", "--render-type", "ascii",
+            "--camera-position", "-1,2.5,3", "--seed", "7", "-o", "frame.png", "--spheres", "20",
+            "--palette", "pastel", "--colors", "ansi256",
+        ]).unwrap();
+        assert_eq!(cli.scene, Some(PathBuf::from("scene.toml")));
+        assert_eq!(cli.size, Some(Resolution { width: 64, height: 32 }));
+        assert!(matches!(cli.render_type, RenderTypeArg::Ascii));
+        assert_eq!(cli.camera_position, Some(Vector3::new(-1.0, 2.5, 3.0)));
+        assert_eq!(cli.seed, Some(7));
+        assert_eq!(cli.output, Some(PathBuf::from("frame.png")));
+        assert_eq!(cli.spheres, 20);
+        assert!(matches!(cli.palette, PaletteArg::Pastel));
+        assert!(matches!(cli.colors, ColorsArg::Ansi256) && !cli.color_dithering);
+        assert!(!cli.allow_overlap);
+        assert_eq!(cli.fps_cap, 144);
+    }
+
+    #[test]
+    fn edits_are_not_saved_over_the_loaded_scene() {
+        let save_path = |args: &[&str]| Cli::try_parse_from(["ascii_engine"].iter().chain(args)).unwrap().save_path();
+        assert_eq!(save_path(&["scenes/room.toml"]), PathBuf::from("scenes/room.edited.toml"));
+        assert_eq!(save_path(&["scenes/room.toml", "--save-scene", "scenes/room.toml"]), PathBuf::from("scenes/room.toml"));
+        assert_eq!(save_path(&[]), PathBuf::from("scene.toml"));
+    }
+
+    #[test]
+    fn rejects_invalid_values() {
+        assert!("64".parse::<Resolution>().is_err());
+        assert!("0x10".parse::<Resolution>().is_err());
+        assert!(parse_vector("1,2").is_err());
+        for gamma in ["0", "-2.2", "inf", "NaN"] {
+            assert!(Cli::try_parse_from(["ascii_engine", "--gamma", gamma]).is_err(), "gamma {}", gamma);
+        }
+        assert_eq!(Cli::try_parse_from(["ascii_engine", "--gamma", "2.2"]).unwrap().gamma, 2.2);
+        assert!(Cli::try_parse_from(["ascii_engine", "--render-type", "sepia"]).is_err());
+    }
+}
@@ -6,6 +6,7 @@ pub struct Clock {
     elapsed_time: Duration,
     last_dt: Duration,
     tick_count: u64,
+    paused: bool,
 }
 
 impl TimeProvider for Clock {
@@ -17,6 +18,11 @@ impl TimeProvider for Clock {
         &self.last_dt
     }
 }
+impl Default for Clock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 impl Clock {
     pub fn new() -> Clock {
         Clock {
@@ -24,22 +30,34 @@ impl Clock {
             last_dt: Duration::from_millis(0),
             elapsed_time: Duration::from_millis(0),
             tick_count: 0,
+            paused: false,
         }
     }
-    pub(crate) fn tick_count(&self) -> u64 {
+    pub fn tick_count(&self) -> u64 {
         self.tick_count
     }
     pub fn tick(&mut self) {
         if let Some(last_tick_time) = self.last_tick_time {
             let now = Instant::now();
             self.last_dt = now - last_tick_time;
-            self.elapsed_time += self.last_dt;
+            if !self.paused {
+                self.elapsed_time += self.last_dt;
+            }
         }
 
         self.last_tick_time = Some(Instant::now());
         self.tick_count += 1;
     }
 
+    /// While the clock is paused the total time stands still, but `dt` still measures the time between ticks
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
     pub fn reset(&mut self) {
         self.last_tick_time = None;
         self.last_dt = Duration::from_millis(0);
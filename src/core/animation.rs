@@ -0,0 +1,330 @@
+//! Animations of the shapes, lights and the camera of a scene. An animation only depends on the time since
+//! the scene started, so `Scene::tick` can apply it at any time without knowing about earlier frames.
+
+use crate::core::light::LightId;
+use crate::core::scene_graph::NodeId;
+use crate::core::shapes::ShapeId;
+use cgmath::{InnerSpace, Quaternion, Vector3, VectorSpace};
+
+/// The part of a scene an animation changes
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Target {
+    /// The transform of the node of the shape, and its color
+    Shape(ShapeId),
+    /// The transform of a node of the scene graph, e.g. of a group
+    Node(NodeId),
+    Light(LightId),
+    Camera,
+}
+
+/// Something in the scene that changes over time, animations are applied by `Scene::tick`.
+/// Shapes and nodes are moved, turned and scaled by changing their transform, relative to their parent.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Animation {
+    Position { target: Target, track: Track<Vector3<f32>> },
+    /// Without rotation the camera and lights face along -z.
+    /// Point and area lights shine in every direction and aren't turned.
+    Rotation { target: Target, track: Track<Quaternion<f32>> },
+    /// Scales nodes uniformly. Lights and the camera have no size.
+    Scale { target: Target, track: Track<f32> },
+    /// The color of a light, or the albedo of a shape. The albedo of the shape replaces the one of its material,
+    /// which stays the same for the other shapes with the material.
+    Color { target: Target, track: Track<Vector3<f32>> },
+    /// Moves the target on a horizontal circle around `center`
+    Orbit {
+        target: Target,
+        center: Vector3<f32>,
+        radius: f32,
+        /// In radians per second
+        speed: f32,
+    },
+    /// Moves the target along the straight lines between the points with a constant speed, taking `duration`
+    /// seconds for the whole path. A repeating path also leads from the last point back to the first one,
+    /// and starts over from there.
+    Path { target: Target, points: Vec<Vector3<f32>>, duration: f32, repeat: bool },
+    /// Keeps the origin of the node of a shape at the position of a light, e.g. a glowing sphere around the origin
+    /// showing where a point light is. It is applied after the other animations, so the shape follows a moving light.
+    Attach { shape: ShapeId, light: LightId },
+}
+
+/// What an animation changes at a point in time
+pub(crate) enum Change {
+    Position(Target, Vector3<f32>),
+    Rotation(Target, Quaternion<f32>),
+    Scale(Target, f32),
+    Color(Target, Vector3<f32>),
+}
+
+impl Animation {
+    /// The change `time` seconds after the start, `None` for attachments, which depend on the scene
+    pub(crate) fn change_at(&self, time: f32) -> Option<Change> {
+        let change = match self {
+            Animation::Position { target, track } => Change::Position(*target, track.sample(time)?),
+            Animation::Rotation { target, track } => Change::Rotation(*target, track.sample(time)?),
+            Animation::Scale { target, track } => Change::Scale(*target, track.sample(time)?),
+            Animation::Color { target, track } => Change::Color(*target, track.sample(time)?),
+            Animation::Orbit { target, center, radius, speed } => {
+                let angle = time * speed;
+                Change::Position(*target, center + Vector3::new(angle.cos(), 0.0, angle.sin()) * *radius)
+            }
+            Animation::Path { target, points, duration, repeat } => {
+                Change::Position(*target, position_on_path(points, *duration, *repeat, time)?)
+            }
+            Animation::Attach { .. } => return None,
+        };
+        Some(change)
+    }
+
+    /// Whether the animation changes the target, or attaches it to something. Animations of shapes and lights
+    /// are removed together with them.
+    pub fn refers_to(&self, target: Target) -> bool {
+        match *self {
+            Animation::Position { target: animated, .. }
+            | Animation::Rotation { target: animated, .. }
+            | Animation::Scale { target: animated, .. }
+            | Animation::Color { target: animated, .. }
+            | Animation::Orbit { target: animated, .. }
+            | Animation::Path { target: animated, .. } => animated == target,
+            Animation::Attach { shape, light } => target == Target::Shape(shape) || target == Target::Light(light),
+        }
+    }
+}
+
+fn position_on_path(points: &[Vector3<f32>], duration: f32, repeat: bool, time: f32) -> Option<Vector3<f32>> {
+    let first = *points.first()?;
+    let mut segments: Vec<_> = points.windows(2).map(|segment| (segment[0], segment[1])).collect();
+    if repeat {
+        segments.push((*points.last()?, first));
+    }
+    let length: f32 = segments.iter().map(|(start, end)| (end - start).magnitude()).sum();
+    if length == 0.0 || duration <= 0.0 {
+        return Some(first);
+    }
+
+    let progress = if repeat { (time / duration).rem_euclid(1.0) } else { (time / duration).clamp(0.0, 1.0) };
+    let mut distance = progress * length;
+    for &(start, end) in &segments {
+        let segment_length = (end - start).magnitude();
+        if distance <= segment_length && segment_length > 0.0 {
+            return Some(start.lerp(end, distance / segment_length));
+        }
+        distance -= segment_length;
+    }
+    segments.last().map(|&(_, end)| end)
+}
+
+/// How the value of a track changes from one keyframe to the next
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Easing {
+    /// Keeps the value of the keyframe until the next keyframe
+    Step,
+    Linear,
+    /// Starts slowly, like `ease-in` in CSS
+    EaseIn,
+    /// Ends slowly, like `ease-out` in CSS
+    EaseOut,
+    /// Starts and ends slowly, like `ease-in-out` in CSS
+    EaseInOut,
+    /// A Bezier curve from (0, 0) to (1, 1) with the control points (x1, y1) and (x2, y2),
+    /// like `cubic-bezier` in CSS. The x axis is the time, x1 and x2 are clamped between 0 and 1.
+    CubicBezier(f32, f32, f32, f32),
+}
+
+impl Easing {
+    /// The fraction of the change between two keyframes after the fraction `t` of the time between them
+    pub fn apply(&self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+        match *self {
+            Easing::Step => if t < 1.0 { 0.0 } else { 1.0 },
+            Easing::Linear => t,
+            Easing::EaseIn => cubic_bezier(0.42, 0.0, 1.0, 1.0, t),
+            Easing::EaseOut => cubic_bezier(0.0, 0.0, 0.58, 1.0, t),
+            Easing::EaseInOut => cubic_bezier(0.42, 0.0, 0.58, 1.0, t),
+            Easing::CubicBezier(x1, y1, x2, y2) => cubic_bezier(x1.clamp(0.0, 1.0), y1, x2.clamp(0.0, 1.0), y2, t),
+        }
+    }
+}
+
+/// The y coordinate of the point of the curve at the x coordinate `x`
+fn cubic_bezier(x1: f32, y1: f32, x2: f32, y2: f32, x: f32) -> f32 {
+    let bezier = |p1: f32, p2: f32, s: f32| {
+        3.0 * p1 * s * (1.0 - s) * (1.0 - s) + 3.0 * p2 * s * s * (1.0 - s) + s * s * s
+    };
+    // with the control points between 0 and 1 x grows with the curve parameter, so it can be found by bisection
+    let (mut low, mut high) = (0.0, 1.0);
+    for _ in 0..24 {
+        let middle = (low + high) / 2.0;
+        if bezier(x1, x2, middle) < x {
+            low = middle;
+        } else {
+            high = middle;
+        }
+    }
+    bezier(y1, y2, (low + high) / 2.0)
+}
+
+/// Values that can be blended between keyframes
+pub trait Interpolate: Copy {
+    /// `self` for `t` = 0, `other` for `t` = 1
+    fn interpolate(self, other: Self, t: f32) -> Self;
+}
+
+impl Interpolate for f32 {
+    fn interpolate(self, other: f32, t: f32) -> f32 {
+        self + (other - self) * t
+    }
+}
+
+impl Interpolate for Vector3<f32> {
+    fn interpolate(self, other: Vector3<f32>, t: f32) -> Vector3<f32> {
+        self.lerp(other, t)
+    }
+}
+
+impl Interpolate for Quaternion<f32> {
+    fn interpolate(self, other: Quaternion<f32>, t: f32) -> Quaternion<f32> {
+        self.normalize().slerp(other.normalize(), t)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Keyframe<T> {
+    /// In seconds since the start of the scene
+    pub time: f32,
+    pub value: T,
+    /// How the value changes from this keyframe to the next one
+    pub easing: Easing,
+}
+
+impl<T> Keyframe<T> {
+    pub fn new(time: f32, value: T) -> Keyframe<T> {
+        Keyframe { time, value, easing: Easing::Linear }
+    }
+
+    pub fn with_easing(self, easing: Easing) -> Keyframe<T> {
+        Keyframe { easing, ..self }
+    }
+}
+
+/// Values at points in time, with the values in between interpolated
+#[derive(Clone, Debug, PartialEq)]
+pub struct Track<T> {
+    keyframes: Vec<Keyframe<T>>,
+    repeat: bool,
+}
+
+impl<T: Interpolate> Track<T> {
+    /// The keyframes are sorted by their time. A repeating track starts over at the time of the first
+    /// keyframe when the last keyframe is reached.
+    pub fn new(mut keyframes: Vec<Keyframe<T>>, repeat: bool) -> Track<T> {
+        keyframes.sort_by(|a, b| a.time.total_cmp(&b.time));
+        Track { keyframes, repeat }
+    }
+
+    pub fn keyframes(&self) -> &[Keyframe<T>] {
+        &self.keyframes
+    }
+
+    pub fn repeats(&self) -> bool {
+        self.repeat
+    }
+
+    /// The value at `time`, `None` if the track has no keyframes.
+    /// Before the first keyframe and after the last one of a track that doesn't repeat the value doesn't change.
+    pub fn sample(&self, time: f32) -> Option<T> {
+        let first = self.keyframes.first()?;
+        let last = self.keyframes.last()?;
+        let duration = last.time - first.time;
+        let time = if self.repeat && duration > 0.0 {
+            first.time + (time - first.time).rem_euclid(duration)
+        } else {
+            time
+        };
+
+        // the keyframe the current interpolation starts from
+        let index = self.keyframes.partition_point(|keyframe| keyframe.time <= time);
+        if index == 0 {
+            return Some(first.value);
+        }
+        let (from, to) = match self.keyframes.get(index) {
+            Some(to) => (&self.keyframes[index - 1], to),
+            None => return Some(last.value),
+        };
+        let t = (time - from.time) / (to.time - from.time);
+        Some(from.value.interpolate(to.value, from.easing.apply(t)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::{Deg, Rotation, Rotation3};
+
+    fn assert_close(actual: f32, expected: f32) {
+        assert!((actual - expected).abs() < 1e-4, "expected {}, got {}", expected, actual);
+    }
+
+    #[test]
+    fn easings_go_from_zero_to_one() {
+        let easings = [
+            Easing::Step, Easing::Linear, Easing::EaseIn, Easing::EaseOut, Easing::EaseInOut,
+            Easing::CubicBezier(0.3, -0.5, 0.7, 1.5),
+        ];
+        for easing in easings {
+            assert_close(easing.apply(0.0), 0.0);
+            assert_close(easing.apply(1.0), 1.0);
+        }
+        assert!(Easing::EaseIn.apply(0.25) < 0.25);
+        assert!(Easing::EaseOut.apply(0.25) > 0.25);
+        assert_close(Easing::EaseInOut.apply(0.5), 0.5);
+        // a Bezier curve with the control points on the diagonal is linear
+        assert_close(Easing::CubicBezier(0.25, 0.25, 0.75, 0.75).apply(0.3), 0.3);
+        assert_eq!(Easing::Step.apply(0.99), 0.0);
+    }
+
+    #[test]
+    fn tracks_interpolate_between_keyframes() {
+        let track = Track::new(vec![
+            Keyframe::new(2.0, 10.0),
+            Keyframe::new(0.0, 0.0),
+            Keyframe::new(3.0, 0.0).with_easing(Easing::Step),
+        ], false);
+        assert_close(track.sample(-1.0).unwrap(), 0.0);
+        assert_close(track.sample(1.0).unwrap(), 5.0);
+        assert_close(track.sample(2.5).unwrap(), 5.0);
+        assert_close(track.sample(10.0).unwrap(), 0.0);
+        assert!(Track::<f32>::new(Vec::new(), false).sample(1.0).is_none());
+    }
+
+    #[test]
+    fn repeating_tracks_start_over() {
+        let track = Track::new(vec![Keyframe::new(1.0, 0.0), Keyframe::new(3.0, 4.0)], true);
+        assert_close(track.sample(2.0).unwrap(), 2.0);
+        assert_close(track.sample(4.0).unwrap(), 2.0);
+        assert_close(track.sample(5.5).unwrap(), 1.0);
+    }
+
+    #[test]
+    fn rotations_are_interpolated_on_the_shortest_arc() {
+        let track = Track::new(vec![
+            Keyframe::new(0.0, Quaternion::from_angle_y(Deg(0.0))),
+            Keyframe::new(1.0, Quaternion::from_angle_y(Deg(90.0))),
+        ], false);
+        let halfway = track.sample(0.5).unwrap().rotate_vector(Vector3::unit_x());
+        let expected = Quaternion::from_angle_y(Deg(45.0)).rotate_vector(Vector3::unit_x());
+        assert!((halfway - expected).magnitude() < 1e-4, "{:?}", halfway);
+    }
+
+    #[test]
+    fn paths_are_followed_with_constant_speed() {
+        let points = vec![Vector3::new(0.0, 0.0, 0.0), Vector3::new(3.0, 0.0, 0.0), Vector3::new(3.0, 1.0, 0.0)];
+        let position = |time, repeat| position_on_path(&points, 4.0, repeat, time).unwrap();
+
+        assert_eq!(position(2.0, false), Vector3::new(2.0, 0.0, 0.0));
+        assert_eq!(position(8.0, false), Vector3::new(3.0, 1.0, 0.0));
+        // the way back closes the path, making it 3 + 1 + sqrt(10) long
+        let length = 4.0 + 10.0f32.sqrt();
+        let x = position(3.0 / length * 4.0 + 4.0, true).x;
+        assert_close(x, 3.0);
+    }
+}
@@ -0,0 +1,388 @@
+use crate::core::common::{HitData, Ray};
+use cgmath::{ElementWise, Vector3};
+
+/// Axis aligned bounding box, used as the bounding volume of the BVH
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Aabb {
+    pub min: Vector3<f32>,
+    pub max: Vector3<f32>,
+}
+
+impl Aabb {
+    pub fn new(min: Vector3<f32>, max: Vector3<f32>) -> Aabb {
+        Aabb { min, max }
+    }
+
+    /// The smallest box containing all the points
+    pub fn from_points(points: impl IntoIterator<Item=Vector3<f32>>) -> Aabb {
+        points.into_iter().fold(Aabb::empty(), |bounds, point| bounds.union(&Aabb::new(point, point)))
+    }
+
+    /// A box containing nothing, the union of it with any other box is the other box
+    pub fn empty() -> Aabb {
+        Aabb {
+            min: Vector3::new(f32::INFINITY, f32::INFINITY, f32::INFINITY),
+            max: Vector3::new(f32::NEG_INFINITY, f32::NEG_INFINITY, f32::NEG_INFINITY),
+        }
+    }
+
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        Aabb {
+            min: Vector3::new(self.min.x.min(other.min.x), self.min.y.min(other.min.y), self.min.z.min(other.min.z)),
+            max: Vector3::new(self.max.x.max(other.max.x), self.max.y.max(other.max.y), self.max.z.max(other.max.z)),
+        }
+    }
+
+    pub fn centroid(&self) -> Vector3<f32> {
+        (self.min + self.max) / 2.0
+    }
+
+    pub fn surface_area(&self) -> f32 {
+        let size = self.max - self.min;
+        if size.x < 0.0 || size.y < 0.0 || size.z < 0.0 {
+            return 0.0;
+        }
+        2.0 * (size.x * size.y + size.y * size.z + size.z * size.x)
+    }
+
+    /// The distance along the ray where it enters the box, if it does so before `max_t`.
+    /// `inverse_direction` is the reciprocal of each component of the direction of the ray.
+    pub fn intersect(&self, origin: Vector3<f32>, inverse_direction: Vector3<f32>, max_t: f32) -> Option<f32> {
+        let t1 = (self.min - origin).mul_element_wise(inverse_direction);
+        let t2 = (self.max - origin).mul_element_wise(inverse_direction);
+        let t_near = t1.x.min(t2.x).max(t1.y.min(t2.y)).max(t1.z.min(t2.z)).max(0.0);
+        let t_far = t1.x.max(t2.x).min(t1.y.max(t2.y)).min(t1.z.max(t2.z)).min(max_t);
+        if t_near <= t_far {
+            Some(t_near)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Clone)]
+struct BvhNode {
+    bounds: Aabb,
+    // leaves own `item_count` items starting at `first_item` in `item_indices`,
+    // interior nodes have no items, their left child is the next node and their right child is at `right_child`
+    first_item: usize,
+    item_count: usize,
+    right_child: usize,
+}
+
+impl BvhNode {
+    fn is_leaf(&self) -> bool {
+        self.item_count > 0
+    }
+}
+
+const BIN_COUNT: usize = 12;
+const MAX_LEAF_SIZE: usize = 4;
+// the cost of visiting a node relative to intersecting an item, in the surface area heuristic
+const TRAVERSAL_COST: f32 = 1.0;
+
+/// Bounding volume hierarchy over a list of items given by their bounding boxes,
+/// built with the surface area heuristic.
+/// The BVH only stores the indices of the items, the intersection of the items is done by the caller.
+#[derive(Clone)]
+pub struct Bvh {
+    nodes: Vec<BvhNode>,
+    item_indices: Vec<usize>,
+}
+
+impl Bvh {
+    pub fn build(item_bounds: &[Aabb]) -> Bvh {
+        let mut bvh = Bvh {
+            nodes: Vec::with_capacity(2 * item_bounds.len()),
+            item_indices: (0..item_bounds.len()).collect(),
+        };
+        if !item_bounds.is_empty() {
+            let centroids: Vec<_> = item_bounds.iter().map(Aabb::centroid).collect();
+            bvh.build_node(0, item_bounds.len(), item_bounds, &centroids);
+        }
+        bvh
+    }
+
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn bounds(&self) -> Aabb {
+        self.nodes.first().map_or(Aabb::empty(), |root| root.bounds)
+    }
+
+    fn build_node(&mut self, first_item: usize, item_count: usize, item_bounds: &[Aabb], centroids: &[Vector3<f32>]) {
+        let items = first_item..first_item + item_count;
+        let bounds = self.item_indices[items.clone()].iter()
+            .fold(Aabb::empty(), |bounds, &i| bounds.union(&item_bounds[i]));
+
+        let node_index = self.nodes.len();
+        self.nodes.push(BvhNode { bounds, first_item, item_count, right_child: 0 });
+        if item_count <= 1 {
+            return;
+        }
+
+        let split = self.find_split(first_item, item_count, item_bounds, centroids);
+        let leaf_cost = item_count as f32;
+        let (axis, split_position) = match split {
+            Some((axis, split_position, cost)) if cost < leaf_cost || item_count > MAX_LEAF_SIZE => (axis, split_position),
+            _ => return,
+        };
+
+        // partition the items of the node, so the ones left of the split come first
+        let mut left_count = 0;
+        for i in items.clone() {
+            if centroids[self.item_indices[i]][axis] < split_position {
+                self.item_indices.swap(i, first_item + left_count);
+                left_count += 1;
+            }
+        }
+        if left_count == 0 || left_count == item_count {
+            return;
+        }
+
+        self.nodes[node_index].item_count = 0;
+        self.build_node(first_item, left_count, item_bounds, centroids);
+        self.nodes[node_index].right_child = self.nodes.len();
+        self.build_node(first_item + left_count, item_count - left_count, item_bounds, centroids);
+    }
+
+    /// The axis, position and cost of the cheapest split of the items according to the surface area heuristic.
+    /// The candidates are the borders of bins placed evenly along the extent of the centroids of the items.
+    fn find_split(
+        &self,
+        first_item: usize,
+        item_count: usize,
+        item_bounds: &[Aabb],
+        centroids: &[Vector3<f32>],
+    ) -> Option<(usize, f32, f32)> {
+        let items = &self.item_indices[first_item..first_item + item_count];
+        let centroid_bounds = Aabb::from_points(items.iter().map(|&i| centroids[i]));
+        let node_area = items.iter()
+            .fold(Aabb::empty(), |bounds, &i| bounds.union(&item_bounds[i]))
+            .surface_area();
+
+        let mut best: Option<(usize, f32, f32)> = None;
+        for axis in 0..3 {
+            let min = centroid_bounds.min[axis];
+            let extent = centroid_bounds.max[axis] - min;
+            if extent <= f32::EPSILON {
+                continue;
+            }
+
+            let mut bins = [(Aabb::empty(), 0usize); BIN_COUNT];
+            let bin_of = |centroid: f32| (((centroid - min) / extent * BIN_COUNT as f32) as usize).min(BIN_COUNT - 1);
+            for &i in items {
+                let bin = &mut bins[bin_of(centroids[i][axis])];
+                bin.0 = bin.0.union(&item_bounds[i]);
+                bin.1 += 1;
+            }
+
+            // the area and item count on the right side of every bin border, accumulated from the right
+            let mut right_side = [(0.0, 0usize); BIN_COUNT];
+            let (mut right_bounds, mut right_count) = (Aabb::empty(), 0);
+            for border in (1..BIN_COUNT).rev() {
+                right_bounds = right_bounds.union(&bins[border].0);
+                right_count += bins[border].1;
+                right_side[border] = (right_bounds.surface_area(), right_count);
+            }
+
+            let (mut left_bounds, mut left_count) = (Aabb::empty(), 0);
+            for border in 1..BIN_COUNT {
+                left_bounds = left_bounds.union(&bins[border - 1].0);
+                left_count += bins[border - 1].1;
+                let (right_area, right_count) = right_side[border];
+                if left_count == 0 || right_count == 0 {
+                    continue;
+                }
+
+                let cost = TRAVERSAL_COST
+                    + (left_bounds.surface_area() * left_count as f32 + right_area * right_count as f32) / node_area;
+                if best.is_none_or(|(_, _, best_cost)| cost < best_cost) {
+                    let position = min + extent * border as f32 / BIN_COUNT as f32;
+                    best = Some((axis, position, cost));
+                }
+            }
+        }
+        best
+    }
+
+    /// Updates the bounds of the nodes after the items moved, without changing the structure of the tree.
+    /// This is much cheaper than building the tree again, but the tree gets less efficient
+    /// the further the items move from their position at the time of building.
+    pub fn refit(&mut self, item_bounds: &[Aabb]) {
+        // children always come after their parents
+        for node_index in (0..self.nodes.len()).rev() {
+            let node = &self.nodes[node_index];
+            let bounds = if node.is_leaf() {
+                self.item_indices[node.first_item..node.first_item + node.item_count].iter()
+                    .fold(Aabb::empty(), |bounds, &i| bounds.union(&item_bounds[i]))
+            } else {
+                self.nodes[node_index + 1].bounds.union(&self.nodes[node.right_child].bounds)
+            };
+            self.nodes[node_index].bounds = bounds;
+        }
+    }
+
+    /// Visits the leaves the ray passes through, from the closest one.
+    /// `visit_item` is called with the index of each item in these leaves and the current maximum distance,
+    /// and returns the new maximum distance beyond which nodes are skipped.
+    fn traverse(&self, ray: &Ray, mut max_t: f32, mut visit_item: impl FnMut(usize, f32) -> f32) {
+        if self.nodes.is_empty() {
+            return;
+        }
+        let origin = *ray.origin();
+        let direction = ray.direction().get();
+        let inverse_direction = Vector3::new(1.0 / direction.x, 1.0 / direction.y, 1.0 / direction.z);
+
+        let mut stack = Vec::with_capacity(64);
+        stack.push(0);
+        while let Some(node_index) = stack.pop() {
+            let node = &self.nodes[node_index];
+            if node.bounds.intersect(origin, inverse_direction, max_t).is_none() {
+                continue;
+            }
+
+            if node.is_leaf() {
+                for &item in &self.item_indices[node.first_item..node.first_item + node.item_count] {
+                    max_t = visit_item(item, max_t);
+                }
+                continue;
+            }
+
+            let left = node_index + 1;
+            let right = node.right_child;
+            let left_t = self.nodes[left].bounds.intersect(origin, inverse_direction, max_t);
+            let right_t = self.nodes[right].bounds.intersect(origin, inverse_direction, max_t);
+            // the closer child is pushed last, so it is visited first
+            match (left_t, right_t) {
+                (Some(left_t), Some(right_t)) if left_t < right_t => stack.extend([right, left]),
+                (Some(_), Some(_)) => stack.extend([left, right]),
+                (Some(_), None) => stack.push(left),
+                (None, Some(_)) => stack.push(right),
+                (None, None) => {}
+            }
+        }
+    }
+
+    /// The closest hit of the ray with the items, `intersect_item` intersects the ray with the item of an index
+    pub fn closest_hit(&self, ray: &Ray, intersect_item: impl FnMut(usize) -> Option<HitData>) -> Option<HitData> {
+        self.closest_item_hit(ray, intersect_item).map(|(_, hit)| hit)
+    }
+
+    /// Like `closest_hit`, with the index of the item that was hit
+    pub fn closest_item_hit(&self, ray: &Ray, mut intersect_item: impl FnMut(usize) -> Option<HitData>) -> Option<(usize, HitData)> {
+        let mut closest_hit: Option<(usize, HitData)> = None;
+        self.traverse(ray, f32::INFINITY, |item, max_t| {
+            match intersect_item(item) {
+                Some(hit) if hit.t < max_t => {
+                    let t = hit.t;
+                    closest_hit = Some((item, hit));
+                    t
+                }
+                _ => max_t,
+            }
+        });
+        closest_hit
+    }
+
+    /// Whether any of the items is hit by the ray closer than `max_t`
+    pub fn any_hit(&self, ray: &Ray, max_t: f32, mut intersect_item: impl FnMut(usize) -> Option<HitData>) -> bool {
+        let mut found = false;
+        self.traverse(ray, max_t, |item, max_t| {
+            if found {
+                return 0.0;
+            }
+            if intersect_item(item).is_some_and(|hit| hit.t < max_t) {
+                found = true;
+                // nothing is closer than 0, so the traversal stops
+                return 0.0;
+            }
+            max_t
+        });
+        found
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::material::MaterialId;
+    use crate::core::shapes::{Hittable, Sphere};
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    fn random_spheres(count: usize, rng: &mut StdRng) -> Vec<Sphere> {
+        (0..count)
+            .map(|_| {
+                let center = Vector3::new(rng.random::<f32>(), rng.random::<f32>(), rng.random::<f32>()) * 20.0
+                    - Vector3::new(10.0, 10.0, 10.0);
+                Sphere::new(center, 0.1 + rng.random::<f32>() * 0.5, MaterialId(0))
+            })
+            .collect()
+    }
+
+    fn random_rays(count: usize, rng: &mut StdRng) -> Vec<Ray> {
+        (0..count)
+            .map(|_| {
+                let direction = Vector3::new(rng.random::<f32>(), rng.random::<f32>(), rng.random::<f32>())
+                    - Vector3::new(0.5, 0.5, 0.5);
+                Ray::new(Vector3::new(0.0, 0.0, 15.0) - direction * 5.0, direction)
+            })
+            .collect()
+    }
+
+    fn linear_closest_hit(spheres: &[Sphere], ray: &Ray) -> Option<HitData> {
+        spheres.iter()
+            .filter_map(|sphere| sphere.intersect(ray))
+            .min_by(|hit1, hit2| hit1.t.total_cmp(&hit2.t))
+    }
+
+    fn bounds_of(spheres: &[Sphere]) -> Vec<Aabb> {
+        spheres.iter().map(|sphere| sphere.bounds().unwrap()).collect()
+    }
+
+    fn assert_same_hits(bvh: &Bvh, spheres: &[Sphere], rays: &[Ray]) {
+        for ray in rays {
+            let expected = linear_closest_hit(spheres, ray).map(|hit| hit.t);
+            let actual = bvh.closest_hit(ray, |i| spheres[i].intersect(ray)).map(|hit| hit.t);
+            assert_eq!(actual, expected);
+            assert_eq!(bvh.any_hit(ray, f32::INFINITY, |i| spheres[i].intersect(ray)), expected.is_some());
+        }
+    }
+
+    #[test]
+    fn finds_the_same_hits_as_linear_search() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let spheres = random_spheres(500, &mut rng);
+        let rays = random_rays(500, &mut rng);
+        let bvh = Bvh::build(&bounds_of(&spheres));
+
+        assert_same_hits(&bvh, &spheres, &rays);
+    }
+
+    #[test]
+    fn refit_follows_moved_items() {
+        let mut rng = StdRng::seed_from_u64(11);
+        let mut spheres = random_spheres(200, &mut rng);
+        let rays = random_rays(200, &mut rng);
+        let mut bvh = Bvh::build(&bounds_of(&spheres));
+
+        for sphere in spheres.iter_mut() {
+            sphere.set_center(sphere.center() + Vector3::new(3.0, -2.0, 1.0));
+        }
+        bvh.refit(&bounds_of(&spheres));
+
+        assert_same_hits(&bvh, &spheres, &rays);
+    }
+
+    #[test]
+    fn any_hit_ignores_hits_beyond_max_t() {
+        let spheres = vec![Sphere::new(Vector3::new(0.0, 0.0, -10.0), 1.0, MaterialId(0))];
+        let bvh = Bvh::build(&bounds_of(&spheres));
+        let ray = Ray::new(Vector3::new(0.0, 0.0, 0.0), -Vector3::unit_z());
+
+        assert!(!bvh.any_hit(&ray, 5.0, |i| spheres[i].intersect(&ray)));
+        assert!(bvh.any_hit(&ray, 50.0, |i| spheres[i].intersect(&ray)));
+    }
+}
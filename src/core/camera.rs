@@ -7,14 +7,15 @@ use std::time::Duration;
 use crate::core::movement::MovementComponent;
 
 #[derive(Builder)]
-pub(crate) struct Camera {
+pub struct Camera {
     movement: MovementComponent,
     pitch: Rad<f32>,
     yaw: Rad<f32>,
     fov: Rad<f32>,
     aspect_ratio: f32,
-    near_plane: f32,
-    far_plane: f32,
+    // incremented whenever the view of the camera changes
+    #[builder(default)]
+    revision: u64,
 }
 
 impl Camera {
@@ -45,6 +46,52 @@ impl Camera {
         Ray::new(self.movement.position, dir_world_space)
     }
 
+    pub fn set_aspect_ratio(&mut self, aspect_ratio: f32) {
+        self.aspect_ratio = aspect_ratio;
+        self.revision += 1;
+    }
+
+    pub fn position(&self) -> Vector3<f32> {
+        self.movement.position
+    }
+
+    /// The direction the center of the view looks at
+    pub fn forward(&self) -> Vector3<f32> {
+        self.rotation().rotate_vector(-Vector3::unit_z())
+    }
+
+    pub fn set_position(&mut self, position: Vector3<f32>) {
+        if position != self.movement.position {
+            self.movement.position = position;
+            self.revision += 1;
+        }
+    }
+
+    /// Turns the camera to look in the direction the rotation turns -z into, any roll is dropped
+    pub fn set_rotation(&mut self, rotation: Quaternion<f32>) {
+        let angles = angles_towards(rotation.rotate_vector(-Vector3::unit_z()));
+        if angles != (self.yaw, self.pitch) {
+            (self.yaw, self.pitch) = angles;
+            self.revision += 1;
+        }
+    }
+
+    /// The vertical field of view
+    pub fn fov(&self) -> Rad<f32> {
+        self.fov
+    }
+
+    pub fn set_fov(&mut self, fov: impl Into<Rad<f32>>) {
+        self.fov = fov.into();
+        self.revision += 1;
+    }
+
+    /// Changes every time the camera moves, turns or its projection changes,
+    /// so anything computed from the view of the camera can tell when it is outdated
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
     pub fn scale(&self) -> f32 {
         (self.fov.0 / 2.0).tan()
     }
@@ -63,6 +110,7 @@ impl Camera {
         rotation.rotate_vector(vec)
     }
     pub fn tick(&mut self, actions: impl Iterator<Item=SceneAction>, dt: &Duration) {
+        let view_before = (self.movement.position, self.yaw, self.pitch);
         let mut vel = Vector3::zero();
         for action in actions {
             match action {
@@ -73,25 +121,28 @@ impl Camera {
                     let pitch_change = -delta_in_screen_space.x * sensitivity;
                     self.rotate(yaw_change, pitch_change);
                 }
-                SceneAction::Move(dir) => vel += self.direction_to_movement(dir)
+                SceneAction::Move(dir) => vel += self.direction_to_movement(dir),
+                // shapes are selected and edited by the scene
+                SceneAction::Select { .. } | SceneAction::DragSelected { .. } | SceneAction::ScaleSelected { .. }
+                | SceneAction::CycleMaterial { .. } | SceneAction::DeleteSelected | SceneAction::DuplicateSelected
+                | SceneAction::Undo | SceneAction::Redo => {}
             };
         }
 
         self.movement.set_velocity(vel * self.movement.max_speed);
-        self.movement.tick(&dt)
+        self.movement.tick(dt);
+
+        if (self.movement.position, self.yaw, self.pitch) != view_before {
+            self.revision += 1;
+        }
     }
 
     pub fn from_position_and_target(position: Vector3<f32>, look_at: Vector3<f32>) -> Self {
         // Default parameters
         let fov = Deg(45.0).into();
         let aspect_ratio = 16.0 / 9.0;
-        let near_plane = 0.1;
-        let far_plane = 100.0;
-
-        let forward = (look_at - position).normalize();
 
-        let yaw = Rad(forward.x.atan2(forward.z) + PI);
-        let pitch = Rad(-forward.y.asin());
+        let (yaw, pitch) = angles_towards(look_at - position);
 
         Camera {
             movement: MovementComponent::new(position),
@@ -99,14 +150,58 @@ impl Camera {
             pitch,
             fov,
             aspect_ratio,
-            near_plane,
-            far_plane,
+            revision: 0,
         }
     }
 
-    fn rotation(&self) -> Quaternion<f32> {
+    /// Moves the camera to `position` and turns it towards `look_at`
+    pub fn set_position_and_target(&mut self, position: Vector3<f32>, look_at: Vector3<f32>) {
+        let (yaw, pitch) = angles_towards(look_at - position);
+        self.movement.position = position;
+        self.yaw = yaw;
+        self.pitch = pitch;
+        self.revision += 1;
+    }
+
+    /// The orientation of the camera, without rotation it looks along -z
+    pub fn rotation(&self) -> Quaternion<f32> {
         let yaw_quat = Quaternion::from_angle_y(self.yaw);
         let pitch_quat = Quaternion::from_angle_x(self.pitch);
         yaw_quat * pitch_quat
     }
-}
\ No newline at end of file
+}
+
+/// The yaw and the pitch of a camera looking in the direction
+fn angles_towards(direction: Vector3<f32>) -> (Rad<f32>, Rad<f32>) {
+    let forward = direction.normalize();
+    let yaw = Rad(forward.x.atan2(forward.z) + PI);
+    let pitch = Rad(forward.y.asin());
+    (yaw, pitch)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn looks_at_the_target() {
+        let position = Vector3::new(1.0, 2.0, 3.0);
+        for target in [Vector3::new(0.0, 0.0, 0.0), Vector3::new(4.0, 5.0, -1.0), Vector3::new(1.0, -1.0, 4.0)] {
+            let camera = Camera::from_position_and_target(position, target);
+            let direction = camera.get_ray(Vector2::zero()).direction().get();
+            let expected = (target - position).normalize();
+            assert!((direction - expected).magnitude() < 1e-5, "{:?} instead of {:?}", direction, expected);
+        }
+    }
+
+    #[test]
+    fn moves_and_turns_to_the_target() {
+        let mut camera = Camera::from_position_and_target(Vector3::zero(), -Vector3::unit_z());
+        let revision = camera.revision();
+        camera.set_position_and_target(Vector3::new(0.0, 5.0, 0.0), Vector3::new(3.0, 1.0, 0.0));
+
+        assert_eq!(camera.position(), Vector3::new(0.0, 5.0, 0.0));
+        assert!((camera.forward() - Vector3::new(0.6, -0.8, 0.0)).magnitude() < 1e-5);
+        assert!(camera.revision() > revision);
+    }
+}
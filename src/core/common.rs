@@ -1,10 +1,11 @@
 use cgmath::num_traits::Float;
 use cgmath::{InnerSpace, Vector3, VectorSpace};
+use crate::core::material::MaterialId;
 pub struct HitData {
     pub intersection: Vector3<f32>,
     pub t: f32,
     pub normal: NormalizedVector3<f32>,
-    pub color: Vector3<f32>,
+    pub material: MaterialId,
 }
 
 pub struct NormalizedVector3<T>(Vector3<T>);
@@ -29,7 +30,7 @@ impl Ray {
         self.origin + self.direction.0 * t
     }
 
-    pub(super) fn new(origin: Vector3<f32>, direction: Vector3<f32>) -> Ray {
+    pub fn new(origin: Vector3<f32>, direction: Vector3<f32>) -> Ray {
         Ray {
             origin, direction: NormalizedVector3::from_vector3(direction)
         }
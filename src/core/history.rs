@@ -0,0 +1,359 @@
+use crate::core::animation::Animation;
+use crate::core::light::{Light, LightId};
+use crate::core::material::{Material, MaterialId};
+use crate::core::scene::Scene;
+use crate::core::scene_graph::NodeId;
+use crate::core::shapes::{Hittable, Shape, ShapeId};
+use crate::core::transform::Transform;
+use std::collections::VecDeque;
+
+/// How many edits a scene can undo unless it is changed with `History::set_capacity`
+pub const DEFAULT_HISTORY_SIZE: usize = 100;
+
+/// A reversible change of a scene, made with `Scene::apply`. Applying an edit gives the edit that undoes it.
+#[derive(Clone)]
+pub enum Edit {
+    /// Adds a shape with its own node, which isn't transformed and has no parent
+    AddShape(Box<Shape>),
+    RemoveShape(ShapeId),
+    /// Puts a removed shape back with the same handles
+    RestoreShape(Box<RemovedShape>),
+    AddLight(Light),
+    RemoveLight(LightId),
+    RestoreLight(Box<RemovedLight>),
+    AddGroup(Transform),
+    RemoveGroup(NodeId),
+    RestoreGroup(Box<RemovedNode>),
+    SetTransform(NodeId, Transform),
+    /// Gives a shape another material
+    SetMaterial(ShapeId, MaterialId),
+    /// Changes the properties of a material, for all the shapes using it
+    ChangeMaterial(MaterialId, Material),
+    ChangeLight(LightId, Light),
+}
+
+impl Edit {
+    // changes the scene and returns the edit that undoes the change, None without changing anything
+    // if the edit refers to removed objects or changes nothing
+    pub(crate) fn apply(self, scene: &mut Scene) -> Option<Edit> {
+        match self {
+            Edit::AddShape(shape) => Some(Edit::RemoveShape(scene.add_shape(*shape))),
+            Edit::RemoveShape(id) => scene.take_shape(id).map(|removed| Edit::RestoreShape(Box::new(removed))),
+            Edit::RestoreShape(removed) => {
+                let id = removed.id;
+                scene.restore_shape(*removed).then_some(Edit::RemoveShape(id))
+            }
+            Edit::AddLight(light) => Some(Edit::RemoveLight(scene.add_light(light))),
+            Edit::RemoveLight(id) => scene.take_light(id).map(|removed| Edit::RestoreLight(Box::new(removed))),
+            Edit::RestoreLight(removed) => {
+                let id = removed.id;
+                scene.restore_light(*removed).then_some(Edit::RemoveLight(id))
+            }
+            Edit::AddGroup(transform) => Some(Edit::RemoveGroup(scene.add_group(transform))),
+            Edit::RemoveGroup(node) => scene.take_group(node).map(|removed| Edit::RestoreGroup(Box::new(removed))),
+            Edit::RestoreGroup(removed) => {
+                let node = removed.node;
+                scene.restore_group(*removed).then_some(Edit::RemoveGroup(node))
+            }
+            Edit::SetTransform(node, transform) => {
+                let before = scene.transform(node).filter(|&before| before != transform)?;
+                scene.set_transform(node, transform);
+                Some(Edit::SetTransform(node, before))
+            }
+            Edit::SetMaterial(id, material) => {
+                let shape = scene.shape_mut(id)?;
+                let before = shape.material();
+                shape.set_material(material);
+                Some(Edit::SetMaterial(id, before))
+            }
+            Edit::ChangeMaterial(id, material) => {
+                let before = std::mem::replace(scene.material_mut(id), material);
+                Some(Edit::ChangeMaterial(id, before))
+            }
+            Edit::ChangeLight(id, light) => {
+                let before = std::mem::replace(scene.light_mut(id)?, light);
+                Some(Edit::ChangeLight(id, before))
+            }
+        }
+    }
+}
+
+/// Everything that is removed with a shape, so it can be put back
+#[derive(Clone)]
+pub struct RemovedShape {
+    pub(crate) id: ShapeId,
+    pub(crate) shape: Shape,
+    pub(crate) name: Option<String>,
+    pub(crate) node: RemovedNode,
+    pub(crate) animations: Vec<Animation>,
+}
+
+/// Everything that is removed with a light, so it can be put back
+#[derive(Clone)]
+pub struct RemovedLight {
+    pub(crate) id: LightId,
+    pub(crate) light: Light,
+    pub(crate) name: Option<String>,
+    pub(crate) animations: Vec<Animation>,
+}
+
+/// A node removed from the scene graph, with the children that were moved under its parent
+#[derive(Clone)]
+pub struct RemovedNode {
+    pub(crate) node: NodeId,
+    pub(crate) transform: Transform,
+    pub(crate) parent: Option<NodeId>,
+    pub(crate) children: Vec<NodeId>,
+    pub(crate) animations: Vec<Animation>,
+}
+
+/// The edits that undo the last changes of a scene, and the ones that redo the undone changes.
+/// The oldest edits are forgotten when there are more of them than the capacity.
+#[derive(Clone)]
+pub struct History {
+    undo: VecDeque<Edit>,
+    redo: Vec<Edit>,
+    capacity: usize,
+    // whether the next change of the transform of the node of the last edit is merged into it
+    merging: bool,
+}
+
+impl Default for History {
+    fn default() -> History {
+        History::new(DEFAULT_HISTORY_SIZE)
+    }
+}
+
+impl History {
+    pub fn new(capacity: usize) -> History {
+        History { undo: VecDeque::new(), redo: Vec::new(), capacity, merging: false }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Changes how many edits can be undone, forgetting the oldest ones if there are more
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        while self.undo.len() > capacity {
+            self.undo.pop_front();
+        }
+    }
+
+    /// How many edits can be undone
+    pub fn undo_count(&self) -> usize {
+        self.undo.len()
+    }
+
+    /// How many undone edits can be redone
+    pub fn redo_count(&self) -> usize {
+        self.redo.len()
+    }
+
+    /// Forgets all the edits
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+        self.merging = false;
+    }
+
+    // records the edit undoing a new change, the undone changes can't be redone after it
+    pub(crate) fn record(&mut self, undo: Edit) {
+        self.redo.clear();
+        self.push_undo(undo);
+    }
+
+    // like `record`, but a change of the transform of the same node as the last recorded one is merged into it,
+    // so a whole drag is undone at once
+    pub(crate) fn record_merged(&mut self, undo: Edit) {
+        let same_node = match (self.undo.back(), &undo) {
+            (Some(Edit::SetTransform(last, _)), Edit::SetTransform(node, _)) => last == node,
+            _ => false,
+        };
+        if self.merging && same_node {
+            // the last edit already brings the node back to where it was before the drag
+            self.redo.clear();
+        } else {
+            self.record(undo);
+        }
+        self.merging = true;
+    }
+
+    /// The next change starts a new edit instead of being merged into the last one
+    pub fn end_merging(&mut self) {
+        self.merging = false;
+    }
+
+    pub(crate) fn last_undo(&self) -> Option<&Edit> {
+        self.undo.back()
+    }
+
+    pub(crate) fn take_undo(&mut self) -> Option<Edit> {
+        self.merging = false;
+        self.undo.pop_back()
+    }
+
+    pub(crate) fn take_redo(&mut self) -> Option<Edit> {
+        self.merging = false;
+        self.redo.pop()
+    }
+
+    pub(crate) fn push_undo(&mut self, undo: Edit) {
+        self.merging = false;
+        if self.capacity == 0 {
+            return;
+        }
+        if self.undo.len() == self.capacity {
+            self.undo.pop_front();
+        }
+        self.undo.push_back(undo);
+    }
+
+    pub(crate) fn push_redo(&mut self, redo: Edit) {
+        self.redo.push(redo);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::animation::Target;
+    use crate::core::camera::Camera;
+    use crate::core::shapes::Sphere;
+    use crate::input::SceneAction;
+    use cgmath::{Vector2, Vector3, Zero};
+
+    fn create_scene() -> (Scene, MaterialId) {
+        let mut scene = Scene::new(Camera::from_position_and_target(Vector3::zero(), -Vector3::unit_z()));
+        let material = scene.add_material(Material::default());
+        (scene, material)
+    }
+
+    fn orbit(target: Target) -> Animation {
+        Animation::Orbit { target, center: Vector3::zero(), radius: 1.0, speed: 1.0 }
+    }
+
+    #[test]
+    fn removed_objects_come_back_with_their_handles() {
+        let (mut scene, material) = create_scene();
+        let group = scene.add_group(Transform::from_translation(Vector3::new(0.0, 0.0, -10.0)));
+        let sphere = scene.add_named_shape("ball", Sphere::new(Vector3::zero(), 1.0, material));
+        let sphere_node = scene.shape_node(sphere).unwrap();
+        scene.set_parent(sphere_node, Some(group));
+        let light = scene.add_named_light("lamp", Light::from_position(Vector3::unit_y()));
+        scene.add_animation(orbit(Target::Shape(sphere)));
+        scene.add_animation(orbit(Target::Light(light)));
+
+        assert!(scene.apply(Edit::RemoveGroup(group)));
+        assert!(scene.apply(Edit::RemoveShape(sphere)));
+        assert!(scene.apply(Edit::RemoveLight(light)));
+        assert!(!scene.apply(Edit::RemoveShape(sphere)));
+        assert_eq!((scene.shapes().count(), scene.lights().count(), scene.animations().len()), (0, 0, 0));
+
+        while scene.undo() {}
+        assert_eq!(scene.shape_name(sphere), Some("ball"));
+        assert_eq!(scene.light_name(light), Some("lamp"));
+        assert_eq!(scene.graph().parent(sphere_node), Some(group));
+        assert_eq!(scene.animations().len(), 2);
+        // the shape is in the group again
+        assert!(scene.pick(Vector2::new(0.0, 0.0)).is_some_and(|pick| pick.shape == sphere));
+
+        assert_eq!(scene.history().redo_count(), 3);
+        assert!(scene.redo());
+        assert_eq!(scene.groups().count(), 0);
+        assert_eq!(scene.graph().parent(sphere_node), None);
+    }
+
+    #[test]
+    fn new_edits_cannot_be_redone_over() {
+        let (mut scene, material) = create_scene();
+        let sphere = scene.add_shape(Sphere::new(Vector3::zero(), 1.0, material));
+        let other_material = scene.add_material(Material::default());
+        let light = scene.add_light(Light::from_position(Vector3::unit_y()));
+
+        scene.apply(Edit::SetMaterial(sphere, other_material));
+        scene.apply(Edit::ChangeLight(light, Light::from_position(Vector3::unit_x())));
+        assert!(scene.undo());
+        assert_eq!(scene.light(light).unwrap().position(), Some(Vector3::unit_y()));
+
+        scene.apply(Edit::AddLight(Light::from_position(Vector3::unit_z())));
+        assert!(!scene.redo());
+        assert!(scene.undo() && scene.undo());
+        assert_eq!((scene.shape(sphere).unwrap().material(), scene.lights().count()), (material, 1));
+        assert!(!scene.undo());
+    }
+
+    #[test]
+    fn only_the_latest_edits_are_kept() {
+        let (mut scene, material) = create_scene();
+        let sphere = scene.add_shape(Sphere::new(Vector3::zero(), 1.0, material));
+        let node = scene.shape_node(sphere).unwrap();
+        scene.history_mut().set_capacity(2);
+        for x in 1..=3 {
+            scene.apply(Edit::SetTransform(node, Transform::from_translation(Vector3::new(x as f32, 0.0, 0.0))));
+        }
+
+        assert!(scene.undo() && scene.undo());
+        assert!(!scene.undo());
+        assert_eq!(scene.transform(node).unwrap().translation, Vector3::unit_x());
+    }
+
+    #[test]
+    fn drags_are_undone_at_once() {
+        let (mut scene, material) = create_scene();
+        let sphere = scene.add_shape(Sphere::new(Vector3::new(0.0, 0.0, -10.0), 1.0, material));
+        let node = scene.shape_node(sphere).unwrap();
+        let time = crate::clock::Clock::new();
+        let drag = SceneAction::DragSelected { delta: Vector2::new(50, 0) };
+        let select = SceneAction::Select { position: Vector2::new(0, 0) };
+
+        for action in [select, drag, drag, select, drag, SceneAction::ScaleSelected { steps: 1 }] {
+            scene.tick([action].into_iter(), &time);
+        }
+        // the second click started another drag, the scroll is merged into it
+        assert_eq!(scene.history().undo_count(), 2);
+        assert!(scene.undo() && scene.undo());
+        assert!(scene.transform(node).unwrap().is_identity());
+
+        scene.tick([SceneAction::Redo].into_iter(), &time);
+        assert!(scene.transform(node).unwrap().translation.x > 0.0);
+        scene.tick([SceneAction::Undo].into_iter(), &time);
+        assert!(scene.transform(node).unwrap().is_identity());
+    }
+
+    #[test]
+    fn edits_changing_nothing_are_not_recorded() {
+        let (mut scene, material) = create_scene();
+        let sphere = scene.add_shape(Sphere::new(Vector3::new(0.0, 0.0, -10.0), 1.0, material));
+        let node = scene.shape_node(sphere).unwrap();
+        let revision = scene.revision();
+        assert!(!scene.apply(Edit::SetTransform(node, Transform::identity())));
+        assert_eq!((scene.history().undo_count(), scene.revision()), (0, revision));
+
+        // a drag back to where it started leaves nothing to undo
+        let time = crate::clock::Clock::new();
+        let drag = |x: i16| SceneAction::DragSelected { delta: Vector2::new(x, 0) };
+        for action in [SceneAction::Select { position: Vector2::new(0, 0) }, drag(0), drag(50), drag(-50)] {
+            scene.tick([action].into_iter(), &time);
+        }
+        assert!(scene.transform(node).unwrap().translation.x.abs() < 1e-4);
+        assert_eq!(scene.history().undo_count(), 0);
+    }
+
+    #[test]
+    fn edits_that_cannot_be_undone_are_kept() {
+        let (mut scene, material) = create_scene();
+        let sphere = scene.add_shape(Sphere::new(Vector3::zero(), 1.0, material));
+        let other_material = scene.add_material(Material::default());
+        assert!(scene.apply(Edit::SetMaterial(sphere, other_material)));
+
+        // the shape is removed without the history, so its material can't be set back
+        let removed = scene.take_shape(sphere).unwrap();
+        assert!(!scene.undo());
+        assert_eq!(scene.history().undo_count(), 1);
+        assert!(scene.restore_shape(removed));
+        assert!(scene.undo());
+        assert_eq!(scene.shape(sphere).unwrap().material(), material);
+    }
+}
@@ -1,24 +1,33 @@
 use crate::core::scene::Scene;
-use cgmath::Vector3;
+use cgmath::{Vector3, Zero};
 use rayon::iter::IndexedParallelIterator;
 use rayon::iter::IntoParallelRefMutIterator;
 use rayon::iter::ParallelIterator;
 use crate::input::terminal_input_handler::pixel_to_screen_space;
 
-const WIDTH_TO_HEIGHT_RATIO: f32 = 16. / 9.;
-const SCREEN_HEIGHT: usize = 120;
-const SCREEN_WIDTH: usize = (SCREEN_HEIGHT as f32 * WIDTH_TO_HEIGHT_RATIO) as usize;
-pub const PIXEL_COUNT: usize = SCREEN_WIDTH * SCREEN_HEIGHT;
-
-pub struct Pixel { color: Vector3<f32> }
+#[derive(Clone, Copy)]
+pub struct Pixel {
+    color: Vector3<f32>,
+    // whether the pixel shows the selected shape of the scene
+    selected: bool,
+}
 impl Pixel {
     pub fn new(color: Vector3<f32>) -> Pixel {
-        Pixel {color}
+        Pixel { color, selected: false }
+    }
+
+    pub fn is_selected(&self) -> bool {
+        self.selected
     }
     pub fn intensity(&self) -> f32 {
         (self.color.x + self.color.y + self.color.z) / 3.0
     }
 
+    /// The color as it was computed, channels can be above 1 for very bright pixels
+    pub fn linear_color(&self) -> Vector3<f32> {
+        self.color
+    }
+
     pub fn color(&self) -> (u8,u8,u8) {
         (
             (self.color.x * 255.).round() as u8,
@@ -27,39 +36,68 @@ impl Pixel {
         )
     }
 }
-pub(crate) struct Image {
-    pixels: [Pixel; PIXEL_COUNT],
+pub struct Image {
+    width: usize,
+    height: usize,
+    pixels: Vec<Pixel>,
 }
 
 impl Image {
+    /// Ray traces the scene, the pixels showing the selected shape of the scene are marked as selected
     pub fn write(&mut self, scene: &Scene) {
+        let (width, height) = (self.width, self.height);
+        let selected = scene.selected();
+        self.fill_pixels(|col, row| {
+            let pixel_in_screen_space = pixel_to_screen_space(col, row, width, height);
+            let ray = scene.camera().get_ray(pixel_in_screen_space);
+            let (color, shape) = scene.trace_with_shape(&ray);
+            Pixel { color, selected: selected.is_some() && shape == selected }
+        });
+    }
+
+    /// Sets the color of every pixel in parallel, `color_at` gets the column and the row of the pixel
+    pub fn fill(&mut self, color_at: impl Fn(usize, usize) -> Vector3<f32> + Sync) {
+        self.fill_pixels(|col, row| Pixel::new(color_at(col, row)));
+    }
+
+    fn fill_pixels(&mut self, pixel_at: impl Fn(usize, usize) -> Pixel + Sync) {
+        let width = self.width;
         self.pixels
             .par_iter_mut()
             .enumerate()
-            .map(|(i, p)| {
-                let col = i % Self::width();
-                let row = i / Self::width();
-                let pixel_in_screen_space = pixel_to_screen_space(col as u16, row as u16);
-                let ray = scene.camera().get_ray(pixel_in_screen_space);
-                (p, scene.trace(&ray))
-            })
-            .for_each(|(p, color)| *p = Pixel::new(color));
+            .for_each(|(i, p)| *p = pixel_at(i % width, i / width));
     }
 
-    pub fn new(pixels: [f32; PIXEL_COUNT]) -> Image {
+    pub fn new(width: usize, height: usize) -> Image {
         Image {
-            pixels: pixels.map(|p| Pixel::new(Vector3::new(p, 0., 0.))),
+            width,
+            height,
+            pixels: vec![Pixel::new(Vector3::zero()); width * height],
         }
     }
-    pub fn pixels(&self) -> &[Pixel; PIXEL_COUNT] {
+
+    /// Changes the resolution of the image. The pixel data is cleared,
+    /// the next call to `write` fills it again.
+    pub fn resize(&mut self, width: usize, height: usize) {
+        self.width = width;
+        self.height = height;
+        self.pixels.clear();
+        self.pixels.resize(width * height, Pixel::new(Vector3::zero()));
+    }
+
+    pub fn pixels(&self) -> &[Pixel] {
         &self.pixels
     }
 
-    pub fn width() -> usize {
-        SCREEN_WIDTH
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
     }
 
-    pub(crate) fn height() -> usize {
-        SCREEN_HEIGHT
+    pub fn aspect_ratio(&self) -> f32 {
+        self.width as f32 / self.height.max(1) as f32
     }
 }
@@ -1,11 +1,417 @@
-use cgmath::Vector3;
+use cgmath::{InnerSpace, Rad, Vector3};
+use std::f32::consts::PI;
 
-pub struct Light {
-    pub position: Vector3<f32>
+/// How the light of a light source weakens with the distance from it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Attenuation {
+    None,
+    Linear,
+    InverseSquare,
+}
+
+impl Attenuation {
+    pub fn factor(&self, distance: f32) -> f32 {
+        match self {
+            Attenuation::None => 1.0,
+            Attenuation::Linear => 1.0 / distance,
+            Attenuation::InverseSquare => 1.0 / (distance * distance),
+        }
+    }
+}
+
+/// A handle of a light added to a scene
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct LightId(pub(crate) usize);
+
+/// The light arriving at a point from one sample of a light source.
+pub struct LightSample {
+    /// Normalized direction from the lit point towards the sample
+    pub direction: Vector3<f32>,
+    /// Distance between the lit point and the sample, infinite for directional lights
+    pub distance: f32,
+    pub radiance: Vector3<f32>,
+}
+
+#[derive(Clone)]
+pub enum Light {
+    Point(PointLight),
+    Directional(DirectionalLight),
+    Spot(SpotLight),
+    Area(AreaLight),
 }
 
 impl Light {
     pub fn from_position(position: Vector3<f32>) -> Light {
-        Light { position }
+        Light::Point(PointLight::new(position, Vector3::new(1.0, 1.0, 1.0), 2.0, Attenuation::Linear))
+    }
+
+    /// How many shadow rays are needed to shade a point lit by this light.
+    /// Only area lights need more than one, that is what makes their shadows soft.
+    pub fn sample_count(&self) -> usize {
+        match self {
+            Light::Area(light) => light.sample_count(),
+            _ => 1,
+        }
+    }
+
+    /// The `index`th sample of the light arriving at `point`, `index` is less than `sample_count`.
+    /// The radiance of the samples is not divided by the sample count.
+    /// Returns `None` if no light arrives from this sample, e.g. the point is outside the cone of a spot light.
+    pub fn sample(&self, point: Vector3<f32>, index: usize) -> Option<LightSample> {
+        match self {
+            Light::Point(light) => Some(light.sample(point)),
+            Light::Directional(light) => Some(light.sample()),
+            Light::Spot(light) => light.sample(point),
+            Light::Area(light) => Some(light.sample(point, index)),
+        }
+    }
+
+    pub fn position(&self) -> Option<Vector3<f32>> {
+        match self {
+            Light::Point(light) => Some(light.position),
+            Light::Directional(_) => None,
+            Light::Spot(light) => Some(light.position),
+            Light::Area(light) => Some(light.shape.center()),
+        }
+    }
+
+    /// Moves the light, directional lights have no position so they are left unchanged
+    pub fn set_position(&mut self, position: Vector3<f32>) {
+        match self {
+            Light::Point(light) => light.position = position,
+            Light::Directional(_) => {}
+            Light::Spot(light) => light.position = position,
+            Light::Area(light) => light.shape.set_center(position),
+        }
+    }
+
+    /// The direction the light shines in, `None` for lights that shine in every direction
+    pub fn direction(&self) -> Option<Vector3<f32>> {
+        match self {
+            Light::Directional(light) => Some(light.direction),
+            Light::Spot(light) => Some(light.direction),
+            Light::Point(_) | Light::Area(_) => None,
+        }
+    }
+
+    /// Turns directional and spot lights, the other lights are left unchanged
+    pub fn set_direction(&mut self, direction: Vector3<f32>) {
+        match self {
+            Light::Directional(light) => light.direction = direction.normalize(),
+            Light::Spot(light) => light.direction = direction.normalize(),
+            Light::Point(_) | Light::Area(_) => {}
+        }
+    }
+
+    pub fn color(&self) -> Vector3<f32> {
+        match self {
+            Light::Point(light) => light.color,
+            Light::Directional(light) => light.color,
+            Light::Spot(light) => light.color,
+            Light::Area(light) => light.color,
+        }
+    }
+
+    pub fn set_color(&mut self, color: Vector3<f32>) {
+        match self {
+            Light::Point(light) => light.color = color,
+            Light::Directional(light) => light.color = color,
+            Light::Spot(light) => light.color = color,
+            Light::Area(light) => light.color = color,
+        }
+    }
+}
+
+fn sample_towards(
+    point: Vector3<f32>,
+    sample_position: Vector3<f32>,
+    radiance: impl FnOnce(f32) -> Vector3<f32>,
+) -> LightSample {
+    let point_to_light = sample_position - point;
+    let distance = point_to_light.magnitude();
+    LightSample {
+        direction: point_to_light / distance,
+        distance,
+        radiance: radiance(distance),
+    }
+}
+
+#[derive(Clone)]
+pub struct PointLight {
+    pub position: Vector3<f32>,
+    pub color: Vector3<f32>,
+    pub intensity: f32,
+    pub attenuation: Attenuation,
+}
+
+impl PointLight {
+    pub fn new(position: Vector3<f32>, color: Vector3<f32>, intensity: f32, attenuation: Attenuation) -> PointLight {
+        PointLight { position, color, intensity, attenuation }
+    }
+
+    /// The light arriving at a point that is `distance` away from the light source
+    pub fn radiance(&self, distance: f32) -> Vector3<f32> {
+        self.color * self.intensity * self.attenuation.factor(distance)
+    }
+
+    fn sample(&self, point: Vector3<f32>) -> LightSample {
+        sample_towards(point, self.position, |distance| self.radiance(distance))
+    }
+}
+
+impl From<PointLight> for Light {
+    fn from(light: PointLight) -> Light { Light::Point(light) }
+}
+
+/// Light coming from infinitely far away in parallel rays, like sunlight
+#[derive(Clone)]
+pub struct DirectionalLight {
+    /// The direction the light travels in
+    direction: Vector3<f32>,
+    pub color: Vector3<f32>,
+    pub intensity: f32,
+}
+
+impl DirectionalLight {
+    pub fn new(direction: Vector3<f32>, color: Vector3<f32>, intensity: f32) -> DirectionalLight {
+        DirectionalLight { direction: direction.normalize(), color, intensity }
+    }
+
+    pub fn direction(&self) -> Vector3<f32> {
+        self.direction
+    }
+
+    fn sample(&self) -> LightSample {
+        LightSample {
+            direction: -self.direction,
+            distance: f32::INFINITY,
+            radiance: self.color * self.intensity,
+        }
+    }
+}
+
+impl From<DirectionalLight> for Light {
+    fn from(light: DirectionalLight) -> Light { Light::Directional(light) }
+}
+
+/// A point light that only shines inside a cone. Inside the inner angle the light has full intensity,
+/// between the inner and the outer angle it fades out smoothly.
+#[derive(Clone)]
+pub struct SpotLight {
+    pub position: Vector3<f32>,
+    direction: Vector3<f32>,
+    inner_angle: Rad<f32>,
+    outer_angle: Rad<f32>,
+    pub color: Vector3<f32>,
+    pub intensity: f32,
+    pub attenuation: Attenuation,
+}
+
+impl SpotLight {
+    pub fn new(
+        position: Vector3<f32>,
+        direction: Vector3<f32>,
+        inner_angle: impl Into<Rad<f32>>,
+        outer_angle: impl Into<Rad<f32>>,
+        color: Vector3<f32>,
+        intensity: f32,
+        attenuation: Attenuation,
+    ) -> SpotLight {
+        let inner_angle = inner_angle.into();
+        let outer_angle = outer_angle.into();
+        SpotLight {
+            position,
+            direction: direction.normalize(),
+            inner_angle: Rad(inner_angle.0.min(outer_angle.0)),
+            outer_angle,
+            color,
+            intensity,
+            attenuation,
+        }
+    }
+
+    pub fn direction(&self) -> Vector3<f32> {
+        self.direction
+    }
+
+    pub fn inner_angle(&self) -> Rad<f32> {
+        self.inner_angle
+    }
+
+    pub fn outer_angle(&self) -> Rad<f32> {
+        self.outer_angle
+    }
+
+    /// 1 inside the inner cone, 0 outside the outer cone and smoothly interpolated in between
+    fn cone_factor(&self, light_to_point: Vector3<f32>) -> f32 {
+        let cos_angle = light_to_point.dot(self.direction);
+        let cos_inner = self.inner_angle.0.cos();
+        let cos_outer = self.outer_angle.0.cos();
+        if cos_angle >= cos_inner {
+            return 1.0;
+        }
+        if cos_angle <= cos_outer {
+            return 0.0;
+        }
+        let x = (cos_angle - cos_outer) / (cos_inner - cos_outer);
+        x * x * (3.0 - 2.0 * x)
     }
-}
\ No newline at end of file
+
+    fn sample(&self, point: Vector3<f32>) -> Option<LightSample> {
+        let sample = sample_towards(point, self.position, |distance| {
+            self.color * self.intensity * self.attenuation.factor(distance)
+        });
+        let cone_factor = self.cone_factor(-sample.direction);
+        if cone_factor <= 0.0 {
+            return None;
+        }
+        Some(LightSample { radiance: sample.radiance * cone_factor, ..sample })
+    }
+}
+
+impl From<SpotLight> for Light {
+    fn from(light: SpotLight) -> Light { Light::Spot(light) }
+}
+
+#[derive(Clone)]
+pub enum AreaLightShape {
+    /// A parallelogram, `edge1` and `edge2` are the vectors from its center to the middle of two neighbouring edges
+    Rectangle { center: Vector3<f32>, edge1: Vector3<f32>, edge2: Vector3<f32> },
+    Sphere { center: Vector3<f32>, radius: f32 },
+}
+
+impl AreaLightShape {
+    pub fn center(&self) -> Vector3<f32> {
+        match self {
+            AreaLightShape::Rectangle { center, .. } => *center,
+            AreaLightShape::Sphere { center, .. } => *center,
+        }
+    }
+
+    pub fn set_center(&mut self, new_center: Vector3<f32>) {
+        match self {
+            AreaLightShape::Rectangle { center, .. } => *center = new_center,
+            AreaLightShape::Sphere { center, .. } => *center = new_center,
+        }
+    }
+}
+
+/// A light with a surface, which is approximated by a fixed set of point lights on it.
+/// Points that only see a part of these are in the penumbra of a soft shadow.
+#[derive(Clone)]
+pub struct AreaLight {
+    pub shape: AreaLightShape,
+    pub color: Vector3<f32>,
+    pub intensity: f32,
+    pub attenuation: Attenuation,
+    samples_per_axis: usize,
+}
+
+impl AreaLight {
+    pub fn new(
+        shape: AreaLightShape,
+        color: Vector3<f32>,
+        intensity: f32,
+        attenuation: Attenuation,
+        samples_per_axis: usize,
+    ) -> AreaLight {
+        AreaLight { shape, color, intensity, attenuation, samples_per_axis: samples_per_axis.max(1) }
+    }
+
+    pub fn samples_per_axis(&self) -> usize {
+        self.samples_per_axis
+    }
+
+    fn sample_count(&self) -> usize {
+        self.samples_per_axis * self.samples_per_axis
+    }
+
+    fn sample_position(&self, point: Vector3<f32>, index: usize) -> Vector3<f32> {
+        let n = self.samples_per_axis;
+        match self.shape {
+            AreaLightShape::Rectangle { center, edge1, edge2 } => {
+                // the middle of a cell of an n x n grid over the rectangle
+                let u = 2.0 * ((index % n) as f32 + 0.5) / n as f32 - 1.0;
+                let v = 2.0 * ((index / n) as f32 + 0.5) / n as f32 - 1.0;
+                center + edge1 * u + edge2 * v
+            }
+            AreaLightShape::Sphere { center, radius } => {
+                // the sphere looks like a disc from the point, the samples are spread evenly on that disc
+                // along a golden angle spiral
+                let normal = (point - center).normalize();
+                let helper = if normal.x.abs() < 0.9 { Vector3::unit_x() } else { Vector3::unit_y() };
+                let tangent = normal.cross(helper).normalize();
+                let bitangent = normal.cross(tangent);
+
+                const GOLDEN_ANGLE: f32 = PI * 0.763_932;
+                let r = ((index as f32 + 0.5) / self.sample_count() as f32).sqrt();
+                let phi = index as f32 * GOLDEN_ANGLE;
+                center + (tangent * phi.cos() + bitangent * phi.sin()) * r * radius
+            }
+        }
+    }
+
+    fn sample(&self, point: Vector3<f32>, index: usize) -> LightSample {
+        let sample_position = self.sample_position(point, index);
+        sample_towards(point, sample_position, |distance| {
+            self.color * self.intensity * self.attenuation.factor(distance)
+        })
+    }
+}
+
+impl From<AreaLight> for Light {
+    fn from(light: AreaLight) -> Light { Light::Area(light) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::Deg;
+
+    #[test]
+    fn spot_light_fades_between_inner_and_outer_cone() {
+        let light = SpotLight::new(
+            Vector3::new(0.0, 0.0, 0.0),
+            -Vector3::unit_y(),
+            Deg(20.0),
+            Deg(40.0),
+            Vector3::new(1.0, 1.0, 1.0),
+            1.0,
+            Attenuation::None,
+        );
+        let radiance_at = |angle: f32| {
+            let angle = angle.to_radians();
+            light.sample(Vector3::new(angle.sin(), -angle.cos(), 0.0)).map(|s| s.radiance.x)
+        };
+
+        assert_eq!(radiance_at(10.0), Some(1.0));
+        let faded = radiance_at(30.0).unwrap();
+        assert!(0.0 < faded && faded < 1.0);
+        assert!(radiance_at(50.0).is_none());
+    }
+
+    #[test]
+    fn rectangle_area_light_samples_cover_the_rectangle() {
+        let light = AreaLight::new(
+            AreaLightShape::Rectangle {
+                center: Vector3::new(0.0, 2.0, 0.0),
+                edge1: Vector3::unit_x(),
+                edge2: Vector3::unit_z(),
+            },
+            Vector3::new(1.0, 1.0, 1.0),
+            1.0,
+            Attenuation::None,
+            2,
+        );
+        let positions: Vec<_> = (0..light.sample_count())
+            .map(|i| light.sample_position(Vector3::new(0.0, 0.0, 0.0), i))
+            .collect();
+
+        assert_eq!(positions, vec![
+            Vector3::new(-0.5, 2.0, -0.5),
+            Vector3::new(0.5, 2.0, -0.5),
+            Vector3::new(-0.5, 2.0, 0.5),
+            Vector3::new(0.5, 2.0, 0.5),
+        ]);
+    }
+}
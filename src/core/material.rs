@@ -0,0 +1,56 @@
+use cgmath::{Vector3, Zero};
+
+/// Describes how the surface of an object interacts with light.
+/// Materials are stored in the scene and objects refer to them by `MaterialId`, so they can be shared.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Material {
+    /// The fraction of the light reflected diffusely for each color channel
+    pub albedo: Vector3<f32>,
+    /// The color of the Blinn-Phong highlights, black for matte surfaces
+    pub specular: Vector3<f32>,
+    /// The exponent of the Blinn-Phong highlights, higher is a smaller, sharper highlight
+    pub shininess: f32,
+    /// Light emitted by the surface itself, independently of the lights of the scene
+    pub emissive: Vector3<f32>,
+    /// The fraction of the light reflected like a mirror, between 0 and 1
+    pub reflectivity: f32,
+    /// The fraction of the light passing through the surface, between 0 and 1
+    pub transparency: f32,
+    /// The index of refraction of the inside of the object, used for transparent objects
+    pub refractive_index: f32,
+}
+
+impl Material {
+    /// A matte material
+    pub fn diffuse(albedo: Vector3<f32>) -> Material {
+        Material {
+            albedo,
+            specular: Vector3::zero(),
+            shininess: 1.0,
+            emissive: Vector3::zero(),
+            reflectivity: 0.0,
+            transparency: 0.0,
+            refractive_index: 1.0,
+        }
+    }
+
+    /// A material with Blinn-Phong highlights
+    pub fn glossy(albedo: Vector3<f32>, specular: Vector3<f32>, shininess: f32) -> Material {
+        Material { specular, shininess, ..Material::diffuse(albedo) }
+    }
+
+    /// A material that glows with the given color and isn't lit by anything else
+    pub fn emissive(color: Vector3<f32>) -> Material {
+        Material { emissive: color, ..Material::diffuse(Vector3::zero()) }
+    }
+}
+
+impl Default for Material {
+    fn default() -> Material {
+        Material::diffuse(Vector3::new(0.8, 0.8, 0.8))
+    }
+}
+
+/// A handle of a material added to a scene
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct MaterialId(pub(crate) usize);
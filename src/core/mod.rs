@@ -1,7 +1,18 @@
+pub mod animation;
+pub mod bvh;
 pub mod camera;
 pub mod common;
+pub mod history;
 pub mod light;
+pub mod material;
 pub mod movement;
+pub mod obj;
+pub mod optics;
+pub mod path_tracer;
 pub mod scene;
+pub mod scene_file;
+pub mod scene_generator;
+pub mod scene_graph;
 pub mod image;
-pub mod shapes;
\ No newline at end of file
+pub mod shapes;
+pub mod transform;
\ No newline at end of file
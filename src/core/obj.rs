@@ -0,0 +1,270 @@
+//! Loading of triangle meshes from Wavefront OBJ files.
+//! Only the geometry is read (`v`, `vt`, `vn` and `f`), the other statements, e.g. materials, groups
+//! and free-form curves, are ignored.
+//! Polygons with more than three vertices are split into triangles.
+
+use crate::core::material::MaterialId;
+use crate::core::shapes::{Mesh, MeshFace};
+use cgmath::{InnerSpace, Vector2, Vector3};
+use std::fmt::{Display, Formatter};
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+use std::str::SplitWhitespace;
+
+#[derive(Debug)]
+pub enum ObjError {
+    Io(std::io::Error),
+    Parse { line: usize, message: String },
+}
+
+impl Display for ObjError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ObjError::Io(error) => write!(f, "couldn't read obj file: {}", error),
+            ObjError::Parse { line, message } => write!(f, "invalid obj file at line {}: {}", line, message),
+        }
+    }
+}
+
+impl std::error::Error for ObjError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ObjError::Io(error) => Some(error),
+            ObjError::Parse { .. } => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for ObjError {
+    fn from(error: std::io::Error) -> ObjError {
+        ObjError::Io(error)
+    }
+}
+
+pub fn load_obj(path: impl AsRef<Path>, material: MaterialId) -> Result<Mesh, ObjError> {
+    let file = File::open(path.as_ref())?;
+    let mut mesh = parse_obj(BufReader::new(file), material)?;
+    mesh.set_source(path.as_ref());
+    Ok(mesh)
+}
+
+pub fn parse_obj(reader: impl BufRead, material: MaterialId) -> Result<Mesh, ObjError> {
+    let mut positions = Vec::new();
+    let mut normals = Vec::new();
+    let mut texture_coordinates = Vec::new();
+    let mut faces = Vec::new();
+
+    for (line_index, line) in reader.lines().enumerate() {
+        let line = line?;
+        let line_number = line_index + 1;
+        let error = |message: String| ObjError::Parse { line: line_number, message };
+
+        let content = line.split('#').next().unwrap_or("");
+        let mut tokens = content.split_whitespace();
+        let keyword = if let Some(keyword) = tokens.next() {
+            keyword
+        } else {
+            continue;
+        };
+
+        match keyword {
+            "v" => {
+                let [x, y, z, w] = parse_floats(&mut tokens, 3, 4, 1.0).map_err(error)?;
+                // positions with a weight are in homogeneous coordinates
+                if w == 0.0 {
+                    return Err(error("a vertex with a w of 0 is at infinity".to_string()));
+                }
+                positions.push(Vector3::new(x, y, z) / w);
+            }
+            "vn" => {
+                let [x, y, z, _] = parse_floats(&mut tokens, 3, 3, 0.0).map_err(error)?;
+                let normal = Vector3::new(x, y, z);
+                // a normal without a direction can't be normalized
+                if normal.magnitude2() == 0.0 {
+                    return Err(error("a normal has a length of 0".to_string()));
+                }
+                normals.push(normal);
+            }
+            "vt" => {
+                let [u, v, _, _] = parse_floats(&mut tokens, 1, 3, 0.0).map_err(error)?;
+                texture_coordinates.push(Vector2::new(u, v));
+            }
+            "f" => {
+                let vertices = tokens
+                    .map(|token| parse_face_vertex(token, positions.len(), texture_coordinates.len(), normals.len()))
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(error)?;
+                if vertices.len() < 3 {
+                    return Err(error(format!("a face needs at least 3 vertices, found {}", vertices.len())));
+                }
+                for i in 1..vertices.len() - 1 {
+                    faces.push(triangle_face([vertices[0], vertices[i], vertices[i + 1]]).map_err(error)?);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Ok(Mesh::new(positions, normals, texture_coordinates, faces, material))
+}
+
+/// Parses between `required` and `max` floats of a statement, the missing ones up to 4 are `default`
+fn parse_floats(tokens: &mut SplitWhitespace, required: usize, max: usize, default: f32) -> Result<[f32; 4], String> {
+    let numbers = tokens
+        .map(|token| token.parse::<f32>().map_err(|_| format!("`{}` is not a number", token)))
+        .collect::<Result<Vec<_>, _>>()?;
+    if numbers.len() < required || numbers.len() > max {
+        return Err(format!("expected {} to {} numbers, found {}", required, max, numbers.len()));
+    }
+
+    let mut values = [default; 4];
+    for (value, number) in values.iter_mut().zip(numbers) {
+        *value = number;
+    }
+    Ok(values)
+}
+
+type FaceVertex = (usize, Option<usize>, Option<usize>);
+
+/// Parses `v`, `v/vt`, `v//vn` or `v/vt/vn` into zero based indices
+fn parse_face_vertex(
+    token: &str,
+    position_count: usize,
+    texture_coordinate_count: usize,
+    normal_count: usize,
+) -> Result<FaceVertex, String> {
+    let mut parts = token.split('/');
+    let position = match parts.next() {
+        Some(index) if !index.is_empty() => resolve_index(index, position_count, "vertex")?,
+        _ => return Err(format!("`{}` has no vertex index", token)),
+    };
+    let texture_coordinate = match parts.next() {
+        Some(index) if !index.is_empty() => Some(resolve_index(index, texture_coordinate_count, "texture coordinate")?),
+        _ => None,
+    };
+    let normal = match parts.next() {
+        Some(index) if !index.is_empty() => Some(resolve_index(index, normal_count, "normal")?),
+        _ => None,
+    };
+    if parts.next().is_some() {
+        return Err(format!("`{}` has too many indices", token));
+    }
+    Ok((position, texture_coordinate, normal))
+}
+
+/// OBJ indices start at 1, negative indices count backwards from the last element defined so far
+fn resolve_index(index: &str, count: usize, name: &str) -> Result<usize, String> {
+    let value: i64 = index.parse().map_err(|_| format!("`{}` is not a valid {} index", index, name))?;
+    let resolved = if value > 0 {
+        value - 1
+    } else {
+        count as i64 + value
+    };
+    if value == 0 || resolved < 0 || resolved >= count as i64 {
+        return Err(format!("{} index {} is out of range, there are {} defined so far", name, value, count));
+    }
+    Ok(resolved as usize)
+}
+
+fn triangle_face(vertices: [FaceVertex; 3]) -> Result<MeshFace, String> {
+    let all_or_none = |indices: [Option<usize>; 3], name: &str| match indices {
+        [Some(a), Some(b), Some(c)] => Ok(Some([a, b, c])),
+        [None, None, None] => Ok(None),
+        _ => Err(format!("some vertices of a face have {}, others don't", name)),
+    };
+    Ok(MeshFace {
+        positions: vertices.map(|(position, _, _)| position),
+        texture_coordinates: all_or_none(vertices.map(|(_, texture_coordinate, _)| texture_coordinate), "texture coordinates")?,
+        normals: all_or_none(vertices.map(|(_, _, normal)| normal), "normals")?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(source: &str) -> Result<Mesh, ObjError> {
+        parse_obj(source.as_bytes(), MaterialId(0))
+    }
+
+    fn parse_error_line(source: &str) -> usize {
+        match parse(source) {
+            Err(ObjError::Parse { line, .. }) => line,
+            Err(error) => panic!("expected a parse error, got {}", error),
+            Ok(_) => panic!("expected a parse error"),
+        }
+    }
+
+    #[test]
+    fn quad_with_normals_and_texture_coordinates() {
+        let mesh = parse("\
+            # a unit square
+            o square
+            v 0 0 0
+            v 1 0 0
+            v 1 1 0
+            v 0 1 0
+            vt 0 0
+            vt 1 0
+            vt 1 1
+            vt 0 1
+            vn 0 0 1
+            f 1/1/1 2/2/1 3/3/1 -1/-1/-1
+        ").unwrap();
+
+        assert_eq!(mesh.positions().len(), 4);
+        assert_eq!(mesh.texture_coordinates().len(), 4);
+        assert_eq!(mesh.faces(), &[
+            MeshFace { positions: [0, 1, 2], normals: Some([0, 0, 0]), texture_coordinates: Some([0, 1, 2]) },
+            MeshFace { positions: [0, 2, 3], normals: Some([0, 0, 0]), texture_coordinates: Some([0, 2, 3]) },
+        ]);
+    }
+
+    #[test]
+    fn face_with_only_positions_and_normals() {
+        let mesh = parse("v 0 0 0\nv 1 0 0\nv 0 1 0\nvn 0 0 1\nf 1//1 2//1 3//1\n").unwrap();
+        assert_eq!(mesh.faces()[0].normals, Some([0, 0, 0]));
+        assert_eq!(mesh.faces()[0].texture_coordinates, None);
+    }
+
+    #[test]
+    fn loaded_mesh_interpolates_vertex_normals() {
+        use crate::core::common::Ray;
+        use crate::core::shapes::Hittable;
+        use cgmath::InnerSpace;
+
+        let mesh = parse("v -1 -1 0\nv 1 -1 0\nv 0 1 0\nvn -1 0 1\nvn 1 0 1\nvn 0 0 1\nf 1//1 2//2 3//3\n").unwrap();
+        let hit = mesh.intersect(&Ray::new(Vector3::new(0.5, -0.5, 1.0), -Vector3::unit_z())).unwrap();
+        // the barycentric weights of the hit are 1/8, 5/8 and 1/4
+        let expected = Vector3::new(0.5, 0.0, 1.0).normalize();
+        assert!((hit.normal.get() - expected).magnitude() < 1e-4, "got {:?}", hit.normal.get());
+    }
+
+    #[test]
+    fn malformed_files_report_the_line() {
+        assert_eq!(parse_error_line("v 0 0 0\nv 1 x 0\n"), 2);
+        assert_eq!(parse_error_line("v 0 0\n"), 1);
+        assert_eq!(parse_error_line("v 0 0 0\nv 1 0 0\nf 1 2\n"), 3);
+        assert_eq!(parse_error_line("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 4\n"), 4);
+        assert_eq!(parse_error_line("v 0 0 0\nv 1 0 0\nv 0 1 0\nvn 0 0 1\nf 1//1 2 3\n"), 5);
+        assert_eq!(parse_error_line("v 0 0 0\nvn 0 0 0\n"), 2);
+        assert_eq!(parse_error_line("v 0 0 0\nvn 0 1 0 7\n"), 2);
+        assert_eq!(parse_error_line("vt 0.5 0.5 0 9\n"), 1);
+        assert_eq!(parse_error_line("v 0 0 0 1 1\n"), 1);
+        assert_eq!(parse_error_line("v 1 2 3 0\n"), 1);
+    }
+
+    #[test]
+    fn unsupported_statements_are_skipped() {
+        let mesh = parse("v 0 0 0\nv 1 0 0\nv 0 1 0\nvp 0.5\ncstype bspline\ndeg 3\nlod 1\nusemap map\nf 1 2 3\n").unwrap();
+        assert_eq!(mesh.faces().len(), 1);
+    }
+
+    #[test]
+    fn weighted_positions_are_divided_by_w() {
+        let mesh = parse("v 2 4 6 2\nv 1 0 0\nv 0 1 0\nf 1 2 3\n").unwrap();
+        assert_eq!(mesh.positions()[0], Vector3::new(1.0, 2.0, 3.0));
+        assert_eq!(mesh.positions()[1], Vector3::new(1.0, 0.0, 0.0));
+    }
+}
@@ -0,0 +1,50 @@
+//! Directions and reflectance of light at the boundary of two materials
+
+use cgmath::{InnerSpace, Vector3};
+
+/// Mirrors the direction on the surface with the given normal
+pub fn reflect(direction: Vector3<f32>, normal: Vector3<f32>) -> Vector3<f32> {
+    direction - normal * (2.0 * direction.dot(normal))
+}
+
+/// The direction of a ray after passing through a surface according to Snell's law,
+/// `eta` is the ratio of the refractive indices of the two sides. `normal` is on the side the ray comes from.
+/// None in case of total internal reflection.
+pub fn refract(direction: Vector3<f32>, normal: Vector3<f32>, eta: f32) -> Option<Vector3<f32>> {
+    let cos_incident = -direction.dot(normal);
+    let k = 1.0 - eta * eta * (1.0 - cos_incident * cos_incident);
+    if k < 0.0 {
+        return None;
+    }
+    Some(direction * eta + normal * (eta * cos_incident - k.sqrt()))
+}
+
+/// Schlick's approximation of the fraction of light reflected at the boundary of two materials,
+/// `cos_theta` is the cosine of the angle on the side with the lower refractive index
+pub fn schlick(cos_theta: f32, n1: f32, n2: f32) -> f32 {
+    let r0 = ((n1 - n2) / (n1 + n2)).powi(2);
+    r0 + (1.0 - r0) * (1.0 - cos_theta).powi(5)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn refraction_follows_snells_law() {
+        let normal = Vector3::unit_y();
+        let incident = Vector3::new(1.0, -1.0, 0.0).normalize();
+
+        let refracted = refract(incident, normal, 1.0 / 1.5).unwrap();
+        assert!((refracted.x - 45f32.to_radians().sin() / 1.5).abs() < 1e-5);
+        assert!((refracted.magnitude() - 1.0).abs() < 1e-5);
+        // from glass to air above the critical angle of ~41.8 degrees
+        assert!(refract(incident, normal, 1.5).is_none());
+    }
+
+    #[test]
+    fn schlick_reflectance() {
+        assert!((schlick(1.0, 1.0, 1.5) - 0.04).abs() < 1e-6);
+        assert!((schlick(0.0, 1.0, 1.5) - 1.0).abs() < 1e-6);
+    }
+}
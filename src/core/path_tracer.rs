@@ -0,0 +1,296 @@
+use crate::core::common::{HitData, Ray};
+use crate::core::image::Image;
+use crate::core::material::Material;
+use crate::core::optics::{reflect, refract, schlick};
+use crate::core::scene::{Scene, SHADOW_BIAS};
+use crate::input::terminal_input_handler::pixel_to_screen_space;
+use cgmath::{ElementWise, InnerSpace, Vector2, Vector3, Zero};
+use rand::rngs::SmallRng;
+use rand::{Rng, SeedableRng};
+use rayon::iter::{IndexedParallelIterator, IntoParallelRefMutIterator, ParallelIterator};
+use std::f32::consts::PI;
+
+// after this many bounces paths are terminated randomly, with a chance depending on how much light they still carry
+const RUSSIAN_ROULETTE_START: u32 = 3;
+
+/// Monte Carlo path tracer that renders the scene progressively.
+/// Each call to `render` adds one sample to every pixel, and the image shows the average of all samples so far.
+/// The samples are thrown away when the camera moves, the scene is edited or the resolution changes.
+///
+/// Light sources are sampled directly at every diffuse bounce (next event estimation),
+/// emissive surfaces only contribute when a path hits them.
+/// To look the same as `Scene::trace`, the diffuse reflection of a light is `albedo * radiance * cos_theta`.
+pub struct PathTracer {
+    max_bounces: u32,
+    seed: u64,
+    accumulated: Vec<Vector3<f32>>,
+    sample_count: u32,
+    // the revisions of the camera and the scene the samples were taken with
+    revisions: Option<(u64, u64)>,
+}
+
+impl PathTracer {
+    pub fn new(max_bounces: u32, seed: u64) -> PathTracer {
+        PathTracer {
+            max_bounces,
+            seed,
+            accumulated: Vec::new(),
+            sample_count: 0,
+            revisions: None,
+        }
+    }
+
+    /// The number of samples per pixel accumulated so far
+    pub fn sample_count(&self) -> u32 {
+        self.sample_count
+    }
+
+    pub fn reset(&mut self) {
+        self.accumulated.clear();
+        self.sample_count = 0;
+        self.revisions = None;
+    }
+
+    /// Adds a sample to every pixel and writes the average of the samples into the image
+    pub fn render(&mut self, scene: &Scene, image: &mut Image) {
+        let (width, height) = (image.width(), image.height());
+        let revisions = (scene.camera().revision(), scene.revision());
+        if self.revisions != Some(revisions) || self.accumulated.len() != width * height {
+            self.reset();
+            self.accumulated.resize(width * height, Vector3::zero());
+            self.revisions = Some(revisions);
+        }
+
+        let frame_seed = self.seed ^ (self.sample_count as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+        let max_bounces = self.max_bounces;
+        self.accumulated
+            .par_iter_mut()
+            .enumerate()
+            .for_each(|(i, sum)| {
+                let mut rng = SmallRng::seed_from_u64(frame_seed.wrapping_add(i as u64));
+                let (col, row) = (i % width, i / width);
+                // a random point inside the pixel, so the edges are antialiased as the samples add up
+                let jitter = Vector2::new(
+                    (rng.random::<f32>() - 0.5) * 2.0 / width as f32,
+                    (0.5 - rng.random::<f32>()) * 2.0 / height as f32,
+                );
+                let pixel_in_screen_space = pixel_to_screen_space(col, row, width, height) + jitter;
+                let ray = scene.camera().get_ray(pixel_in_screen_space);
+                *sum += trace_path(scene, ray, max_bounces, &mut rng);
+            });
+        self.sample_count += 1;
+
+        let sample_count = self.sample_count as f32;
+        let accumulated = &self.accumulated;
+        image.fill(|col, row| accumulated[row * width + col] / sample_count);
+    }
+}
+
+/// One random estimate of the light arriving along the ray
+pub fn trace_path(scene: &Scene, mut ray: Ray, max_bounces: u32, rng: &mut impl Rng) -> Vector3<f32> {
+    let mut color = Vector3::zero();
+    // the fraction of the light at the current vertex of the path that reaches the camera
+    let mut throughput = Vector3::new(1.0, 1.0, 1.0);
+
+    for bounce in 0..=max_bounces {
+        let (hit, material) = if let Some(surface) = scene.intersect_surface(&ray) {
+            surface
+        } else {
+            color += throughput.mul_element_wise(scene.ambient_light_color());
+            break;
+        };
+        color += throughput.mul_element_wise(material.emissive);
+
+        let direction = ray.direction().get();
+        let normal = hit.normal.get();
+        let entering = direction.dot(normal) < 0.0;
+        let facing_normal = if entering { normal } else { -normal };
+
+        // one of the lobes of the material is chosen with a chance equal to its weight, so the weights cancel out
+        let reflectivity = material.reflectivity.clamp(0.0, 1.0);
+        let transparency = material.transparency.clamp(0.0, 1.0 - reflectivity);
+        let lobe = rng.random::<f32>();
+        let next_ray = if lobe < reflectivity {
+            Ray::new(hit.intersection + SHADOW_BIAS * facing_normal, reflect(direction, facing_normal))
+        } else if lobe < reflectivity + transparency {
+            transmit(&hit, direction, facing_normal, entering, &material, rng)
+        } else {
+            color += throughput.mul_element_wise(sample_lights(scene, &hit, facing_normal, -direction, &material, rng));
+            throughput = throughput.mul_element_wise(material.albedo);
+            let bounce_direction = cosine_weighted_direction(facing_normal, rng);
+            Ray::new(hit.intersection + SHADOW_BIAS * facing_normal, bounce_direction)
+        };
+
+        if bounce >= RUSSIAN_ROULETTE_START {
+            let survival_chance = throughput.x.max(throughput.y).max(throughput.z).clamp(0.05, 1.0);
+            if rng.random::<f32>() >= survival_chance {
+                break;
+            }
+            throughput /= survival_chance;
+        }
+        ray = next_ray;
+    }
+    color
+}
+
+/// Refracts or reflects the ray at the surface of a transparent material, with the chance of reflection given by Fresnel
+fn transmit(
+    hit: &HitData,
+    direction: Vector3<f32>,
+    facing_normal: Vector3<f32>,
+    entering: bool,
+    material: &Material,
+    rng: &mut impl Rng,
+) -> Ray {
+    let reflected_ray = Ray::new(hit.intersection + SHADOW_BIAS * facing_normal, reflect(direction, facing_normal));
+    let (n1, n2) = if entering {
+        (1.0, material.refractive_index)
+    } else {
+        (material.refractive_index, 1.0)
+    };
+    let refracted_direction = if let Some(refracted_direction) = refract(direction, facing_normal, n1 / n2) {
+        refracted_direction
+    } else {
+        return reflected_ray; // total internal reflection
+    };
+
+    let cos_incident = -direction.dot(facing_normal);
+    let cos_transmitted = refracted_direction.dot(-facing_normal);
+    let fresnel = schlick(cos_incident.min(cos_transmitted).max(0.0), n1, n2);
+    if rng.random::<f32>() < fresnel {
+        reflected_ray
+    } else {
+        Ray::new(hit.intersection - SHADOW_BIAS * facing_normal, refracted_direction)
+    }
+}
+
+/// Next event estimation: the light reaching the hit directly from one random sample of every light
+fn sample_lights(
+    scene: &Scene,
+    hit: &HitData,
+    normal: Vector3<f32>,
+    to_viewer: Vector3<f32>,
+    material: &Material,
+    rng: &mut impl Rng,
+) -> Vector3<f32> {
+    let origin = hit.intersection + SHADOW_BIAS * normal;
+    let mut out_color = Vector3::zero();
+    for (_, light) in scene.lights() {
+        let sample_index = rng.gen_range(0..light.sample_count());
+        let sample = if let Some(sample) = light.sample(hit.intersection, sample_index) {
+            sample
+        } else {
+            continue;
+        };
+        let cos_theta = sample.direction.dot(normal);
+        if cos_theta <= 0.0 || scene.is_occluded(&Ray::new(origin, sample.direction), sample.distance) {
+            continue;
+        }
+
+        let halfway = (sample.direction + to_viewer).normalize();
+        let specular_strength = halfway.dot(normal).max(0.0).powf(material.shininess);
+        let reflected = material.albedo * cos_theta + material.specular * specular_strength;
+        out_color += reflected.mul_element_wise(sample.radiance);
+    }
+    out_color
+}
+
+/// A random direction in the hemisphere around the normal, directions close to the normal are more likely,
+/// proportionally to the cosine of their angle with it
+fn cosine_weighted_direction(normal: Vector3<f32>, rng: &mut impl Rng) -> Vector3<f32> {
+    let phi = 2.0 * PI * rng.random::<f32>();
+    let r2 = rng.random::<f32>();
+    let r = r2.sqrt();
+
+    let helper = if normal.x.abs() < 0.9 { Vector3::unit_x() } else { Vector3::unit_y() };
+    let tangent = normal.cross(helper).normalize();
+    let bitangent = normal.cross(tangent);
+    tangent * (r * phi.cos()) + bitangent * (r * phi.sin()) + normal * (1.0 - r2).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::camera::Camera;
+    use crate::core::history::Edit;
+    use crate::core::shapes::Sphere;
+
+    #[test]
+    fn cosine_weighted_directions_are_in_the_hemisphere() {
+        let mut rng = SmallRng::seed_from_u64(1);
+        let normal = Vector3::new(1.0, 2.0, -1.0).normalize();
+        let mut average_cos = 0.0;
+        for _ in 0..10_000 {
+            let direction = cosine_weighted_direction(normal, &mut rng);
+            assert!((direction.magnitude() - 1.0).abs() < 1e-4);
+            assert!(direction.dot(normal) >= 0.0);
+            average_cos += direction.dot(normal) / 10_000.0;
+        }
+        // the expected value of the cosine is 2/3 for a cosine weighted distribution
+        assert!((average_cos - 2.0 / 3.0).abs() < 0.01, "average cosine was {}", average_cos);
+    }
+
+    #[test]
+    fn white_furnace() {
+        // inside a closed sphere that emits 1 and reflects half of the light reaching it,
+        // the light converges to 1 + 0.5 + 0.25 + ... = 2 as the number of bounces grows
+        let camera = Camera::from_position_and_target(Vector3::zero(), -Vector3::unit_z());
+        let mut scene = Scene::new(camera);
+        let material = scene.add_material(Material {
+            albedo: Vector3::new(0.5, 0.5, 0.5),
+            ..Material::emissive(Vector3::new(1.0, 1.0, 1.0))
+        });
+        scene.add_shape(Sphere::new(Vector3::zero(), 5.0, material));
+        let mut rng = SmallRng::seed_from_u64(2);
+
+        let sample_count = 20_000;
+        let average = (0..sample_count)
+            .map(|_| trace_path(&scene, Ray::new(Vector3::zero(), -Vector3::unit_z()), 64, &mut rng))
+            .fold(Vector3::zero(), |sum, sample| sum + sample) / sample_count as f32;
+        assert!((average.x - 2.0).abs() < 0.05, "average was {:?}", average);
+    }
+
+    #[test]
+    fn samples_accumulate_until_the_camera_changes() {
+        let camera = Camera::from_position_and_target(Vector3::zero(), -Vector3::unit_z());
+        let mut scene = Scene::new(camera);
+        let mut image = Image::new(4, 3);
+        let mut path_tracer = PathTracer::new(4, 0);
+
+        path_tracer.render(&scene, &mut image);
+        path_tracer.render(&scene, &mut image);
+        assert_eq!(path_tracer.sample_count(), 2);
+
+        scene.camera_mut().set_aspect_ratio(2.0);
+        path_tracer.render(&scene, &mut image);
+        assert_eq!(path_tracer.sample_count(), 1);
+
+        image.resize(2, 2);
+        path_tracer.render(&scene, &mut image);
+        assert_eq!(path_tracer.sample_count(), 1);
+    }
+
+    #[test]
+    fn samples_accumulate_until_the_scene_is_edited() {
+        let camera = Camera::from_position_and_target(Vector3::zero(), -Vector3::unit_z());
+        let mut scene = Scene::new(camera);
+        let material = scene.add_material(Material::default());
+        let sphere = scene.add_shape(Sphere::new(Vector3::new(0.0, 0.0, -5.0), 1.0, material));
+        let mut image = Image::new(4, 3);
+        let mut path_tracer = PathTracer::new(4, 0);
+
+        path_tracer.render(&scene, &mut image);
+        path_tracer.render(&scene, &mut image);
+        assert_eq!(path_tracer.sample_count(), 2);
+
+        assert!(scene.apply(Edit::RemoveShape(sphere)));
+        path_tracer.render(&scene, &mut image);
+        assert_eq!(path_tracer.sample_count(), 1);
+        path_tracer.render(&scene, &mut image);
+        assert_eq!(path_tracer.sample_count(), 2);
+
+        assert!(scene.undo());
+        path_tracer.render(&scene, &mut image);
+        assert_eq!(path_tracer.sample_count(), 1);
+    }
+}
@@ -1,98 +1,1379 @@
 use crate::input::SceneAction;
-use cgmath::{Array, ElementWise, InnerSpace, Vector3, Zero};
-use std::cmp::Ordering;
+use cgmath::{ElementWise, EuclideanSpace, InnerSpace, Matrix4, Point3, Rotation, SquareMatrix, Transform as _, Vector2, Vector3, Zero};
+use std::collections::BTreeMap;
 use std::time::Duration;
+use crate::core::animation::{Animation, Change, Target};
+use crate::core::bvh::{Aabb, Bvh};
 use crate::core::camera::Camera;
-use crate::core::common::{HitData, NormalizedVector3, Ray};
-use crate::core::light::Light;
-use crate::core::shapes::Sphere;
+use crate::core::common::{HitData, Ray};
+use crate::core::history::{Edit, History, RemovedLight, RemovedNode, RemovedShape};
+use crate::core::light::{Light, LightId};
+use crate::core::material::{Material, MaterialId};
+use crate::core::optics::{reflect, refract, schlick};
+use crate::core::scene_graph::{NodeId, SceneGraph};
+use crate::core::shapes::{Hittable, Shape, ShapeId};
+use crate::core::transform::{Transform, WorldTransform};
 
+// offset of the shadow ray origins along the surface normal, so they don't hit the surface they start from
+pub(crate) const SHADOW_BIAS: f32 = 1e-3;
+// how much larger a step of the scroll wheel makes the selected shape
+const SCALE_STEP: f32 = 1.1;
+
+/// The handles of shapes, lights and nodes stay valid while other ones are added and removed.
+/// The handle of a removed object isn't given to a new one, the getters return `None` for it.
 pub struct Scene {
     camera: Camera,
-    spheres: Vec<Sphere>,
-    lights: Vec<Light>,
+    // shapes and lights are None where they were removed
+    shapes: Vec<Option<Shape>>,
+    lights: Vec<Option<Light>>,
+    // the names of shapes and lights, by their index
+    shape_names: Vec<Option<String>>,
+    light_names: Vec<Option<String>>,
+    materials: Vec<Material>,
+    // the names of the materials, by the index of the material
+    material_names: Vec<Option<String>>,
+    animations: Vec<Animation>,
+    // the albedo of each shape with an animated color, which replaces the one of its material, by the index of the shape
+    shape_albedos: Vec<Option<Vector3<f32>>>,
+    graph: SceneGraph,
+    // the node of each shape in the scene graph, and the shape of each node if it has one
+    shape_nodes: Vec<NodeId>,
+    node_shapes: Vec<Option<ShapeId>>,
+    // the transform of each shape into the world, None if the shape isn't transformed
+    world_transforms: Vec<Option<WorldTransform>>,
     ambient_light_color: Vector3<f32>,
+    // None if shapes were added, removed or changed since the last update, then the shapes are intersected one by one
+    acceleration: Option<Acceleration>,
+    shapes_moved: bool,
+    max_depth: u32,
+    selected: Option<ShapeId>,
+    history: History,
+    // counts the edits, see `revision`
+    revision: u64,
+}
+
+/// The shape seen at a point of the screen
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Pick {
+    pub shape: ShapeId,
+    /// From the camera to the point
+    pub distance: f32,
+    /// Where the shape is hit, in world space
+    pub point: Vector3<f32>,
+}
+
+/// A BVH over the bounded shapes of the scene, the unbounded ones are tested one by one
+struct Acceleration {
+    bvh: Bvh,
+    // the index in `Scene::shapes` of each item of the BVH
+    bounded_shapes: Vec<usize>,
+    unbounded_shapes: Vec<usize>,
+}
+
+impl Acceleration {
+    /// `shape_bounds` are the index and the bounds in world space of each shape
+    fn build(shape_bounds: &[(usize, Option<Aabb>)]) -> Acceleration {
+        let mut bounded_shapes = Vec::new();
+        let mut unbounded_shapes = Vec::new();
+        let mut bounds = Vec::new();
+        for &(index, shape_bounds) in shape_bounds {
+            match shape_bounds {
+                Some(shape_bounds) => {
+                    bounded_shapes.push(index);
+                    bounds.push(shape_bounds);
+                }
+                None => unbounded_shapes.push(index),
+            }
+        }
+        Acceleration { bvh: Bvh::build(&bounds), bounded_shapes, unbounded_shapes }
+    }
+
+    /// The shapes must be the same as when the acceleration was built, only their bounds changed
+    fn refit(&mut self, shape_bounds: &[(usize, Option<Aabb>)]) {
+        let bounds: Vec<_> = shape_bounds.iter()
+            .filter_map(|&(_, bounds)| bounds)
+            .collect();
+        self.bvh.refit(&bounds);
+    }
 }
 
 impl Scene {
     pub fn trace(&self, ray: &Ray) -> Vector3<f32> {
-        let first_hit = if let Some(hit) = self.intersect(&ray) {
-            hit
-        } else {
-            return self.ambient_light_color;
-        };
-        let mut out_color = self.ambient_light_color.mul_element_wise(first_hit.color);
+        self.trace_recursive(ray, 0)
+    }
 
-        for light in &self.lights {
-            let intersection_to_light = light.position - first_hit.intersection;
-            let origin = first_hit.intersection + f32::EPSILON * first_hit.normal.get();
-            let shadow_ray = Ray::new(origin, intersection_to_light);
-            let first_shadow_hit = self.intersect(&shadow_ray);
-            if first_shadow_hit.is_none() {
-                continue;
-            }
+    /// Like `trace`, with the shape the ray hits first, e.g. to highlight the selected shape
+    /// without intersecting the ray again
+    pub fn trace_with_shape(&self, ray: &Ray) -> (Vector3<f32>, Option<ShapeId>) {
+        match self.closest_shape_hit(ray) {
+            Some((index, hit)) => (self.shade(ray, &hit, &self.hit_material(index, &hit), 0), Some(ShapeId(index))),
+            None => (self.ambient_light_color, None),
+        }
+    }
+
+    /// Whitted style ray tracing, reflective and transparent materials continue the ray
+    /// until it reaches `max_depth` bounces
+    fn trace_recursive(&self, ray: &Ray, depth: u32) -> Vector3<f32> {
+        match self.closest_shape_hit(ray) {
+            Some((index, hit)) => self.shade(ray, &hit, &self.hit_material(index, &hit), depth),
+            None => self.ambient_light_color,
+        }
+    }
+
+    // the light leaving the first hit of the ray towards its origin
+    fn shade(&self, ray: &Ray, first_hit: &HitData, material: &Material, depth: u32) -> Vector3<f32> {
+        let local_color = self.direct_light(ray, first_hit, material);
+
+        let reflectivity = material.reflectivity.clamp(0.0, 1.0);
+        let transparency = material.transparency.clamp(0.0, 1.0 - reflectivity);
+        if reflectivity + transparency <= 0.0 || depth >= self.max_depth {
+            return local_color;
+        }
+
+        let direction = ray.direction().get();
+        let normal = first_hit.normal.get();
+        // the normal on the side of the surface the ray comes from
+        let entering = direction.dot(normal) < 0.0;
+        let facing_normal = if entering { normal } else { -normal };
+        let reflected_ray = Ray::new(first_hit.intersection + SHADOW_BIAS * facing_normal, reflect(direction, facing_normal));
+        let reflected_color = self.trace_recursive(&reflected_ray, depth + 1);
+
+        let mut out_color = local_color * (1.0 - reflectivity - transparency) + reflected_color * reflectivity;
+        if transparency > 0.0 {
+            let (n1, n2) = if entering {
+                (1.0, material.refractive_index)
+            } else {
+                (material.refractive_index, 1.0)
+            };
+            let transmitted_color = match refract(direction, facing_normal, n1 / n2) {
+                Some(refracted_direction) => {
+                    // the angle is larger on the side with the lower refractive index
+                    let cos_incident = -direction.dot(facing_normal);
+                    let cos_transmitted = refracted_direction.dot(-facing_normal);
+                    let fresnel = schlick(cos_incident.min(cos_transmitted).max(0.0), n1, n2);
+                    let refracted_ray = Ray::new(first_hit.intersection - SHADOW_BIAS * facing_normal, refracted_direction);
+                    reflected_color * fresnel + self.trace_recursive(&refracted_ray, depth + 1) * (1.0 - fresnel)
+                }
+                // total internal reflection
+                None => reflected_color,
+            };
+            out_color += transmitted_color * transparency;
+        }
+        out_color
+    }
+
+    /// Emitted, ambient and Blinn-Phong shaded light from the lights of the scene at a hit
+    fn direct_light(&self, ray: &Ray, hit: &HitData, material: &Material) -> Vector3<f32> {
+        let normal = hit.normal.get();
+        let to_viewer = -ray.direction().get();
+        let mut out_color = material.emissive + self.ambient_light_color.mul_element_wise(material.albedo);
 
-            let distance_to_light2 = intersection_to_light.magnitude2();
-            let cos_theta = f32::max(intersection_to_light.normalize().dot(first_hit.normal.get()), 0.0);
-            out_color += first_hit.color * cos_theta * 2. / distance_to_light2.sqrt() + Vector3::from_value(0.15);
+        let origin = hit.intersection + SHADOW_BIAS * normal;
+        for light in self.lights.iter().flatten() {
+            let sample_count = light.sample_count();
+            for sample_index in 0..sample_count {
+                let sample = if let Some(sample) = light.sample(hit.intersection, sample_index) {
+                    sample
+                } else {
+                    continue;
+                };
+                let cos_theta = sample.direction.dot(normal);
+                if cos_theta <= 0.0 {
+                    continue;
+                }
+                let shadow_ray = Ray::new(origin, sample.direction);
+                if self.is_occluded(&shadow_ray, sample.distance) {
+                    continue;
+                }
+
+                // Blinn-Phong
+                let halfway = (sample.direction + to_viewer).normalize();
+                let specular_strength = halfway.dot(normal).max(0.0).powf(material.shininess);
+                let reflected = material.albedo * cos_theta + material.specular * specular_strength;
+                out_color += reflected.mul_element_wise(sample.radiance) / sample_count as f32;
+            }
         }
         out_color
     }
     pub fn intersect(&self, ray: &Ray) -> Option<HitData> {
-        self.spheres
-            .iter()
-            .filter_map(|sphere| sphere.intersect(&ray))
-            .min_by(|hit1, hit2| hit1.t
-                .partial_cmp(&hit2.t)
-                .unwrap_or(Ordering::Equal))
+        self.closest_shape_hit(ray).map(|(_, hit)| hit)
+    }
+
+    /// The closest hit of the ray with the material seen there, which has the animated color of the shape
+    pub fn intersect_surface(&self, ray: &Ray) -> Option<(HitData, Material)> {
+        self.closest_shape_hit(ray).map(|(index, hit)| {
+            let material = self.hit_material(index, &hit);
+            (hit, material)
+        })
+    }
+
+    // the material of the hit, with the albedo of the shape if its color is animated
+    fn hit_material(&self, index: usize, hit: &HitData) -> Material {
+        let material = *self.material(hit.material);
+        match self.shape_albedos[index] {
+            Some(albedo) => Material { albedo, ..material },
+            None => material,
+        }
+    }
+
+    /// The albedo the shape is shown with, which is animated by color animations of the shape
+    /// instead of the albedo of its material
+    pub fn shape_albedo(&self, id: ShapeId) -> Option<Vector3<f32>> {
+        let shape = self.shape(id)?;
+        Some(self.shape_albedos[id.0].unwrap_or_else(|| self.material(shape.material()).albedo))
+    }
+
+    /// The shape seen through a point of the screen, with where it is hit
+    pub fn pick(&self, pixel_in_screen_space: Vector2<f32>) -> Option<Pick> {
+        let ray = self.camera.get_ray(pixel_in_screen_space);
+        let (index, hit) = self.closest_shape_hit(&ray)?;
+        Some(Pick { shape: ShapeId(index), distance: hit.t, point: hit.intersection })
+    }
+
+    // the closest hit with the index of the shape that is hit
+    fn closest_shape_hit(&self, ray: &Ray) -> Option<(usize, HitData)> {
+        let shape_hit = |index: usize| self.intersect_shape(index, ray).map(|hit| (index, hit));
+        let acceleration = if let Some(acceleration) = self.current_acceleration() {
+            acceleration
+        } else {
+            return Self::closest_hit((0..self.shapes.len()).filter_map(shape_hit));
+        };
+
+        let bvh_hit = acceleration.bvh
+            .closest_item_hit(ray, |item| self.intersect_shape(acceleration.bounded_shapes[item], ray))
+            .map(|(item, hit)| (acceleration.bounded_shapes[item], hit));
+        let unbounded_hits = acceleration.unbounded_shapes.iter()
+            .filter_map(|&index| shape_hit(index));
+        Self::closest_hit(unbounded_hits.chain(bvh_hit))
+    }
+
+    fn closest_hit(hits: impl Iterator<Item=(usize, HitData)>) -> Option<(usize, HitData)> {
+        hits.min_by(|(_, hit1), (_, hit2)| hit1.t.total_cmp(&hit2.t))
+    }
+
+    /// Whether anything that casts a shadow is closer to the origin of the ray than `max_distance`
+    pub fn is_occluded(&self, ray: &Ray, max_distance: f32) -> bool {
+        let casts_shadow = |index: usize| self.shapes[index].as_ref().is_some_and(Shape::casts_shadow);
+        let shadow_hit = |index: usize| if casts_shadow(index) { self.intersect_shape(index, ray) } else { None };
+        let occludes = |index: usize| shadow_hit(index).is_some_and(|hit| hit.t < max_distance);
+        let acceleration = if let Some(acceleration) = self.current_acceleration() {
+            acceleration
+        } else {
+            return (0..self.shapes.len()).any(occludes);
+        };
+
+        acceleration.unbounded_shapes.iter().any(|&index| occludes(index)) ||
+            acceleration.bvh.any_hit(ray, max_distance, |item| shadow_hit(acceleration.bounded_shapes[item]))
+    }
+
+    // the shapes are defined in the space of their node, rays are brought into that space to intersect them
+    fn intersect_shape(&self, index: usize, ray: &Ray) -> Option<HitData> {
+        let shape = self.shapes[index].as_ref()?;
+        match &self.world_transforms[index] {
+            Some(world_transform) => world_transform.intersect(shape, ray),
+            None => shape.intersect(ray),
+        }
+    }
+
+    // the index and the bounds in world space of each shape that wasn't removed
+    fn world_bounds(&self) -> Vec<(usize, Option<Aabb>)> {
+        self.shapes.iter().zip(&self.world_transforms).enumerate()
+            .filter_map(|(index, (shape, world_transform))| {
+                let bounds = match world_transform {
+                    Some(world_transform) => shape.as_ref()?.bounds().map(|bounds| world_transform.bounds(bounds)),
+                    None => shape.as_ref()?.bounds(),
+                };
+                Some((index, bounds))
+            })
+            .collect()
+    }
+
+    // the BVH if it fits the shapes where they are, after shapes moved it is only used again once it is refit
+    fn current_acceleration(&self) -> Option<&Acceleration> {
+        self.acceleration.as_ref().filter(|_| !self.shapes_moved)
+    }
+
+    /// Brings the BVH of the scene up to date. It is built again after shapes were added,
+    /// and refit if they only moved. Until then rays are intersected with each shape one by one.
+    pub fn update_acceleration(&mut self) {
+        if self.acceleration.is_none() {
+            self.acceleration = Some(Acceleration::build(&self.world_bounds()));
+        } else if self.shapes_moved {
+            let bounds = self.world_bounds();
+            if let Some(acceleration) = &mut self.acceleration {
+                acceleration.refit(&bounds);
+            }
+        }
+        self.shapes_moved = false;
     }
     pub fn new(camera: Camera) -> Scene {
         Scene {
             camera,
-            spheres: Vec::new(),
+            shapes: Vec::new(),
             lights: Vec::new(),
+            shape_names: Vec::new(),
+            light_names: Vec::new(),
+            materials: Vec::new(),
+            material_names: Vec::new(),
+            animations: Vec::new(),
+            shape_albedos: Vec::new(),
+            graph: SceneGraph::new(),
+            shape_nodes: Vec::new(),
+            node_shapes: Vec::new(),
+            world_transforms: Vec::new(),
             ambient_light_color: Vector3::new(0.1, 0.1, 0.1),
+            acceleration: None,
+            shapes_moved: false,
+            max_depth: 5,
+            selected: None,
+            history: History::default(),
+            revision: 0,
+        }
+    }
+    /// Adds a shape with its own node in the scene graph, which isn't transformed and has no parent
+    pub fn add_shape(&mut self, shape: impl Into<Shape>) -> ShapeId {
+        let id = ShapeId(self.shapes.len());
+        let node = self.graph.add_node(Transform::identity());
+        self.shapes.push(Some(shape.into()));
+        self.shape_names.push(None);
+        self.shape_albedos.push(None);
+        self.shape_nodes.push(node);
+        self.node_shapes.push(Some(id));
+        self.world_transforms.push(None);
+        self.acceleration = None;
+        id
+    }
+
+    /// Adds a shape that can be found again by its name with `find_shape`
+    pub fn add_named_shape(&mut self, name: impl Into<String>, shape: impl Into<Shape>) -> ShapeId {
+        let id = self.add_shape(shape);
+        self.shape_names[id.0] = Some(name.into());
+        id
+    }
+
+    /// Removes the shape with its node, the children of the node are moved under the parent of the node.
+    /// The animations of the shape are removed with it.
+    pub fn remove_shape(&mut self, id: ShapeId) -> Option<Shape> {
+        self.take_shape(id).map(|removed| removed.shape)
+    }
+
+    // removes the shape, keeping everything needed to put it back with `restore_shape`
+    pub(crate) fn take_shape(&mut self, id: ShapeId) -> Option<RemovedShape> {
+        let shape = self.shapes.get_mut(id.0)?.take()?;
+        let name = self.shape_names[id.0].take();
+        self.world_transforms[id.0] = None;
+        // the color is animated again once the shape and its animations are restored
+        self.shape_albedos[id.0] = None;
+        let animations = self.take_animations(Target::Shape(id));
+        let node = self.take_node(self.shape_nodes[id.0]);
+        if self.selected == Some(id) {
+            self.selected = None;
+        }
+        self.acceleration = None;
+        Some(RemovedShape { id, shape, name, node, animations })
+    }
+
+    // puts a removed shape back with the same handles, false if its handles are used
+    pub(crate) fn restore_shape(&mut self, removed: RemovedShape) -> bool {
+        let id = removed.id;
+        if !matches!(self.shapes.get(id.0), Some(None)) || self.graph.contains(removed.node.node) {
+            return false;
+        }
+        self.shapes[id.0] = Some(removed.shape);
+        self.shape_names[id.0] = removed.name;
+        self.animations.extend(removed.animations);
+        self.restore_node(removed.node);
+        self.acceleration = None;
+        true
+    }
+
+    /// Adds a copy of the shape with the same parent and transform, so it is in the same place.
+    /// The name, the animations and the children of the node of the shape aren't copied.
+    pub fn duplicate_shape(&mut self, id: ShapeId) -> Option<ShapeId> {
+        let shape = self.shape(id)?.clone();
+        let node = self.shape_nodes[id.0];
+        let (transform, parent) = (self.graph.transform(node), self.graph.parent(node));
+        let copy = self.add_shape(shape);
+        let copy_node = self.shape_nodes[copy.0];
+        self.set_parent(copy_node, parent);
+        self.set_transform(copy_node, transform);
+        self.revision += 1;
+        Some(copy)
+    }
+
+    /// Moves the shape by an offset in world space, whatever the transforms of the ancestors of its node are
+    pub fn translate_shape(&mut self, id: ShapeId, offset: Vector3<f32>) {
+        if let Some((node, transform)) = self.translated(id, offset) {
+            self.set_transform(node, transform);
+        }
+    }
+
+    /// Scales the shape by a factor around the middle of its bounds, or around the origin of its node if it is unbounded
+    pub fn scale_shape(&mut self, id: ShapeId, factor: f32) {
+        if let Some((node, transform)) = self.scaled(id, factor) {
+            self.set_transform(node, transform);
+        }
+    }
+
+    // the node of the shape with the transform that moves the shape by the offset
+    fn translated(&self, id: ShapeId, offset: Vector3<f32>) -> Option<(NodeId, Transform)> {
+        let (node, inverse) = self.shape_placement(id)?;
+        let mut transform = self.graph.transform(node);
+        transform.translation += inverse.transform_vector(offset);
+        Some((node, transform))
+    }
+
+    // the node of the shape with the transform that scales the shape by the factor
+    fn scaled(&self, id: ShapeId, factor: f32) -> Option<(NodeId, Transform)> {
+        let (node, inverse) = self.shape_placement(id)?;
+        let center = inverse.transform_point(Point3::from_vec(self.shape_center(id))).to_vec();
+        let mut transform = self.graph.transform(node);
+        transform.translation = center + (transform.translation - center) * factor;
+        transform.scale *= factor;
+        Some((node, transform))
+    }
+
+    // the node of the shape, with the transform from world space into the space of the parent of the node.
+    // None if the shape was removed or an ancestor squashes it flat
+    fn shape_placement(&self, id: ShapeId) -> Option<(NodeId, Matrix4<f32>)> {
+        let node = self.shape_node(id)?;
+        let inverse = match self.graph.parent(node) {
+            Some(parent) => self.graph.world_matrix(parent).invert()?,
+            None => Matrix4::identity(),
+        };
+        Some((node, inverse))
+    }
+
+    // the middle of the bounds of a shape that wasn't removed, in world space
+    fn shape_center(&self, id: ShapeId) -> Vector3<f32> {
+        let bounds = self.shapes[id.0].as_ref().and_then(Shape::bounds);
+        match (bounds, &self.world_transforms[id.0]) {
+            (Some(bounds), Some(world_transform)) => world_transform.bounds(bounds).centroid(),
+            (Some(bounds), None) => bounds.centroid(),
+            (None, _) => self.graph.world_matrix(self.shape_nodes[id.0]).transform_point(Point3::origin()).to_vec(),
+        }
+    }
+
+    /// Adds a node without a shape to the scene graph, the shapes under it move together
+    pub fn add_group(&mut self, transform: Transform) -> NodeId {
+        let node = self.graph.add_node(transform);
+        self.node_shapes.push(None);
+        node
+    }
+
+    /// Removes a node without a shape, its children are moved under its parent. Returns false if the node
+    /// was already removed or has a shape, which is removed with `remove_shape`.
+    pub fn remove_group(&mut self, node: NodeId) -> bool {
+        self.take_group(node).is_some()
+    }
+
+    pub(crate) fn take_group(&mut self, node: NodeId) -> Option<RemovedNode> {
+        if !self.graph.contains(node) || self.node_shape(node).is_some() {
+            return None;
+        }
+        Some(self.take_node(node))
+    }
+
+    pub(crate) fn restore_group(&mut self, removed: RemovedNode) -> bool {
+        if self.graph.contains(removed.node) || self.node_shape(removed.node).is_some() {
+            return false;
+        }
+        self.restore_node(removed);
+        true
+    }
+
+    fn take_node(&mut self, node: NodeId) -> RemovedNode {
+        let animations = self.take_animations(Target::Node(node));
+        let (transform, parent) = (self.graph.transform(node), self.graph.parent(node));
+        let children = self.graph.children(node).to_vec();
+        let moved = self.graph.remove_node(node);
+        self.update_world_transforms(&moved);
+        RemovedNode { node, transform, parent, children, animations }
+    }
+
+    // puts the node back under its parent, and its children that are still there under it
+    fn restore_node(&mut self, removed: RemovedNode) {
+        let node = removed.node;
+        self.graph.restore_node(node, removed.transform);
+        let parent = removed.parent.filter(|&parent| self.graph.contains(parent));
+        let mut moved = self.graph.set_parent(node, parent).unwrap_or_default();
+        for child in removed.children {
+            if self.graph.contains(child) {
+                moved.extend(self.graph.set_parent(child, Some(node)).unwrap_or_default());
+            }
+        }
+        self.update_world_transforms(&moved);
+        self.animations.extend(removed.animations);
+    }
+
+    // removes the animations of the target and returns them
+    fn take_animations(&mut self, target: Target) -> Vec<Animation> {
+        let (taken, kept) = std::mem::take(&mut self.animations).into_iter()
+            .partition(|animation| animation.refers_to(target));
+        self.animations = kept;
+        taken
+    }
+
+    /// The nodes without a shape, in the order they were added
+    pub fn groups(&self) -> impl Iterator<Item=NodeId> + '_ {
+        self.graph.nodes().filter(|&node| self.node_shape(node).is_none())
+    }
+
+    pub fn graph(&self) -> &SceneGraph {
+        &self.graph
+    }
+
+    /// The node of the scene graph that places the shape, `None` if the shape was removed
+    pub fn shape_node(&self, shape: ShapeId) -> Option<NodeId> {
+        self.shape(shape).map(|_| self.shape_nodes[shape.0])
+    }
+
+    /// The shape placed by the node, `None` for groups
+    pub fn node_shape(&self, node: NodeId) -> Option<ShapeId> {
+        self.node_shapes.get(node.0).copied().flatten()
+    }
+
+    /// The transform of the node relative to its parent, `None` if the node was removed
+    pub fn transform(&self, node: NodeId) -> Option<Transform> {
+        self.graph.contains(node).then(|| self.graph.transform(node))
+    }
+
+    /// Changes the transform of the node relative to its parent, moving the shapes of the node and its descendants
+    pub fn set_transform(&mut self, node: NodeId, transform: Transform) {
+        if self.transform(node).is_some_and(|current| current != transform) {
+            let moved = self.graph.set_transform(node, transform);
+            self.update_world_transforms(&moved);
+        }
+    }
+
+    /// Moves the node under a new parent, or makes it a root with `None`. Returns false without changing anything
+    /// if either node was removed, or the parent is the node itself or one of its descendants.
+    pub fn set_parent(&mut self, node: NodeId, parent: Option<NodeId>) -> bool {
+        if !self.graph.contains(node) || parent.is_some_and(|parent| !self.graph.contains(parent)) {
+            return false;
+        }
+        match self.graph.set_parent(node, parent) {
+            Some(moved) => {
+                self.update_world_transforms(&moved);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn update_world_transforms(&mut self, nodes: &[NodeId]) {
+        for &node in nodes {
+            if let Some(shape) = self.node_shapes[node.0] {
+                let matrix = self.graph.world_matrix(node);
+                self.world_transforms[shape.0] = if matrix.is_identity() { None } else { Some(WorldTransform::new(matrix)) };
+            }
+        }
+        self.shapes_moved = true;
+    }
+
+    /// The maximum number of reflections and refractions followed for a single primary ray
+    pub fn set_max_depth(&mut self, max_depth: u32) {
+        self.max_depth = max_depth;
+    }
+
+    pub fn max_depth(&self) -> u32 {
+        self.max_depth
+    }
+
+    pub fn add_material(&mut self, material: Material) -> MaterialId {
+        self.materials.push(material);
+        self.material_names.push(None);
+        MaterialId(self.materials.len() - 1)
+    }
+
+    /// Adds a material that can be found again by its name with `find_material`
+    pub fn add_named_material(&mut self, name: impl Into<String>, material: Material) -> MaterialId {
+        let id = self.add_material(material);
+        self.material_names[id.0] = Some(name.into());
+        id
+    }
+
+    pub fn material_name(&self, id: MaterialId) -> Option<&str> {
+        self.material_names[id.0].as_deref()
+    }
+
+    pub fn find_material(&self, name: &str) -> Option<MaterialId> {
+        self.material_names.iter()
+            .position(|material_name| material_name.as_deref() == Some(name))
+            .map(MaterialId)
+    }
+
+    pub fn materials(&self) -> impl Iterator<Item=(MaterialId, &Material)> + '_ {
+        self.materials.iter().enumerate().map(|(index, material)| (MaterialId(index), material))
+    }
+
+    /// The shapes in the order they were added
+    pub fn shapes(&self) -> impl Iterator<Item=(ShapeId, &Shape)> + '_ {
+        self.shapes.iter().enumerate().filter_map(|(index, shape)| Some((ShapeId(index), shape.as_ref()?)))
+    }
+
+    /// The shape behind a handle returned by `add_shape` of this scene, `None` if it was removed
+    pub fn shape(&self, id: ShapeId) -> Option<&Shape> {
+        self.shapes.get(id.0)?.as_ref()
+    }
+
+    /// The shape can be changed in any way, even into a different kind of shape
+    pub fn shape_mut(&mut self, id: ShapeId) -> Option<&mut Shape> {
+        let shape = self.shapes.get_mut(id.0)?.as_mut()?;
+        self.acceleration = None;
+        Some(shape)
+    }
+
+    pub fn shape_name(&self, id: ShapeId) -> Option<&str> {
+        self.shape_names.get(id.0)?.as_deref()
+    }
+
+    /// The first shape added with the name that wasn't removed
+    pub fn find_shape(&self, name: &str) -> Option<ShapeId> {
+        self.shape_names.iter()
+            .position(|shape_name| shape_name.as_deref() == Some(name))
+            .map(ShapeId)
+    }
+
+    pub fn add_animation(&mut self, animation: Animation) {
+        self.animations.push(animation);
+    }
+
+    pub fn animations(&self) -> &[Animation] {
+        &self.animations
+    }
+
+    /// The material behind a handle returned by `add_material` of this scene
+    pub fn material(&self, id: MaterialId) -> &Material {
+        &self.materials[id.0]
+    }
+
+    pub fn material_mut(&mut self, id: MaterialId) -> &mut Material {
+        &mut self.materials[id.0]
+    }
+
+    pub fn add_light(&mut self, light: Light) -> LightId {
+        self.lights.push(Some(light));
+        self.light_names.push(None);
+        LightId(self.lights.len() - 1)
+    }
+
+    /// Adds a light that can be found again by its name with `find_light`
+    pub fn add_named_light(&mut self, name: impl Into<String>, light: Light) -> LightId {
+        let id = self.add_light(light);
+        self.light_names[id.0] = Some(name.into());
+        id
+    }
+
+    /// Removes the light and its animations, shapes attached to it stay where they are
+    pub fn remove_light(&mut self, id: LightId) -> Option<Light> {
+        self.take_light(id).map(|removed| removed.light)
+    }
+
+    pub(crate) fn take_light(&mut self, id: LightId) -> Option<RemovedLight> {
+        let light = self.lights.get_mut(id.0)?.take()?;
+        let name = self.light_names[id.0].take();
+        let animations = self.take_animations(Target::Light(id));
+        Some(RemovedLight { id, light, name, animations })
+    }
+
+    pub(crate) fn restore_light(&mut self, removed: RemovedLight) -> bool {
+        if !matches!(self.lights.get(removed.id.0), Some(None)) {
+            return false;
         }
+        self.lights[removed.id.0] = Some(removed.light);
+        self.light_names[removed.id.0] = removed.name;
+        self.animations.extend(removed.animations);
+        true
+    }
+
+    /// The lights in the order they were added
+    pub fn lights(&self) -> impl Iterator<Item=(LightId, &Light)> + '_ {
+        self.lights.iter().enumerate().filter_map(|(index, light)| Some((LightId(index), light.as_ref()?)))
+    }
+
+    /// The light behind a handle returned by `add_light` of this scene, `None` if it was removed
+    pub fn light(&self, id: LightId) -> Option<&Light> {
+        self.lights.get(id.0)?.as_ref()
+    }
+
+    pub fn light_mut(&mut self, id: LightId) -> Option<&mut Light> {
+        self.lights.get_mut(id.0)?.as_mut()
+    }
+
+    pub fn light_name(&self, id: LightId) -> Option<&str> {
+        self.light_names.get(id.0)?.as_deref()
     }
-    pub fn add_sphere(&mut self, sphere: Sphere) {
-        self.spheres.push(sphere)
+
+    /// The first light added with the name that wasn't removed
+    pub fn find_light(&self, name: &str) -> Option<LightId> {
+        self.light_names.iter()
+            .position(|light_name| light_name.as_deref() == Some(name))
+            .map(LightId)
+    }
+
+    /// The color of the light coming from everywhere, also the color of rays that don't hit anything
+    pub fn ambient_light_color(&self) -> Vector3<f32> {
+        self.ambient_light_color
     }
 
-    pub fn add_light(&mut self, light: Light) {
-        self.lights.push(light);
+    pub fn set_ambient_light_color(&mut self, color: Vector3<f32>) {
+        self.ambient_light_color = color;
     }
     pub fn camera(&self) -> &Camera {
         &self.camera
     }
+    pub fn camera_mut(&mut self) -> &mut Camera {
+        &mut self.camera
+    }
+
+    /// The shape selected by clicking on it, it is highlighted on the screen
+    pub fn selected(&self) -> Option<ShapeId> {
+        self.selected
+    }
+
+    pub fn set_selected(&mut self, selected: Option<ShapeId>) {
+        self.selected = selected.filter(|&shape| self.shape(shape).is_some());
+    }
 
     pub fn tick(&mut self, actions: impl Iterator<Item=SceneAction>, time_provider: &impl TimeProvider) {
-        self.camera.tick(actions, time_provider.dt());
+        let actions: Vec<SceneAction> = actions.collect();
+        self.camera.tick(actions.iter().copied(), time_provider.dt());
+        self.animate(time_provider);
+        self.update_acceleration();
+        // the shapes are picked where they are shown after this tick
+        for action in actions {
+            match action {
+                SceneAction::Select { position } => {
+                    self.selected = self.pick(position.map(|x| x as f32) / 1000.).map(|pick| pick.shape);
+                    // a click starts a new drag, which is undone separately
+                    self.history.end_merging();
+                }
+                SceneAction::Undo => {
+                    self.undo();
+                }
+                SceneAction::Redo => {
+                    self.redo();
+                }
+                _ => if let Some(selected) = self.selected {
+                    self.edit_selected(selected, action);
+                },
+            }
+        }
+        self.update_acceleration();
+    }
 
-        let first_light_pos = if let Some(&mut ref mut light) = self.lights.first_mut() {
-            &mut light.position
-        } else {
-            return;
+    fn edit_selected(&mut self, selected: ShapeId, action: SceneAction) {
+        match action {
+            SceneAction::DragSelected { delta } => {
+                if let Some((node, transform)) = self.dragged(selected, delta.map(|x| x as f32) / 1000.) {
+                    self.apply_merged(Edit::SetTransform(node, transform));
+                }
+            }
+            SceneAction::ScaleSelected { steps } => {
+                if let Some((node, transform)) = self.scaled(selected, SCALE_STEP.powi(steps as i32)) {
+                    self.apply_merged(Edit::SetTransform(node, transform));
+                }
+            }
+            SceneAction::CycleMaterial { forward } => {
+                if let Some(material) = self.next_material(selected, forward) {
+                    self.apply(Edit::SetMaterial(selected, material));
+                }
+            }
+            SceneAction::DeleteSelected => {
+                self.apply(Edit::RemoveShape(selected));
+            }
+            SceneAction::DuplicateSelected => {
+                self.selected = self.duplicate_shape(selected);
+                if let Some(copy) = self.selected {
+                    self.history.record(Edit::RemoveShape(copy));
+                }
+            }
+            SceneAction::Move(_) | SceneAction::RotateCamera { .. } | SceneAction::Select { .. }
+            | SceneAction::Undo | SceneAction::Redo => {}
+        }
+    }
+
+    // the node of the shape with the transform that moves the shape in the plane facing the camera
+    // through the middle of the shape, so the point under the cursor follows it
+    fn dragged(&self, id: ShapeId, delta_in_screen_space: Vector2<f32>) -> Option<(NodeId, Transform)> {
+        self.shape(id)?;
+        let forward = self.camera.forward();
+        let depth = (self.shape_center(id) - self.camera.position()).dot(forward);
+        if depth <= 0.0 {
+            return None;
+        }
+        let at_depth = |position: Vector2<f32>| {
+            let direction = self.camera.get_ray(position).direction().get();
+            direction * (depth / direction.dot(forward))
         };
+        self.translated(id, at_depth(delta_in_screen_space) - at_depth(Vector2::zero()))
+    }
+
+    // the material after the one of the shape in the order they were added, or the one before it
+    fn next_material(&self, id: ShapeId, forward: bool) -> Option<MaterialId> {
+        let count = self.materials.len();
+        let current = self.shape(id)?.material().0;
+        Some(MaterialId(if forward { (current + 1) % count } else { (current + count - 1) % count }))
+    }
+
+    /// Makes a change that can be undone with `undo`. Returns false without changing anything
+    /// if the edit refers to removed objects or changes nothing, then nothing is recorded.
+    pub fn apply(&mut self, edit: Edit) -> bool {
+        match edit.apply(self) {
+            Some(undo) => {
+                self.history.record(undo);
+                self.revision += 1;
+                true
+            }
+            None => false,
+        }
+    }
+
+    // like `apply`, but merges a change of a transform into the last one of the same node
+    fn apply_merged(&mut self, edit: Edit) {
+        if let Some(undo) = edit.apply(self) {
+            self.history.record_merged(undo);
+            self.revision += 1;
+            // after a drag back to where it started there is nothing to undo
+            let unchanged = match self.history.last_undo() {
+                Some(&Edit::SetTransform(node, before)) => self.transform(node) == Some(before),
+                _ => false,
+            };
+            if unchanged {
+                self.history.take_undo();
+            }
+        }
+    }
+
+    /// Undoes the last edit that wasn't undone, returns false if there is none. Also returns false if it can't
+    /// be undone because the scene was changed without the history, e.g. its shape was removed, then it is kept.
+    pub fn undo(&mut self) -> bool {
+        let edit = if let Some(edit) = self.history.take_undo() { edit } else { return false; };
+        match edit.clone().apply(self) {
+            Some(redo) => {
+                self.history.push_redo(redo);
+                self.revision += 1;
+                true
+            }
+            None => {
+                self.history.push_undo(edit);
+                false
+            }
+        }
+    }
+
+    /// Makes the last undone edit again, returns false if there is none or another edit was made since.
+    /// Like with `undo`, an edit that can't be made is kept.
+    pub fn redo(&mut self) -> bool {
+        let edit = if let Some(edit) = self.history.take_redo() { edit } else { return false; };
+        match edit.clone().apply(self) {
+            Some(undo) => {
+                self.history.push_undo(undo);
+                self.revision += 1;
+                true
+            }
+            None => {
+                self.history.push_redo(edit);
+                false
+            }
+        }
+    }
 
-        let light_orbit_point = Vector3::new(0.0, 1.0, -6.0);
-        let light_orbit_radius = 3.0;
+    /// Changes with every edit made with `apply`, `undo`, `redo` or `duplicate_shape`, so anything computed
+    /// from the scene, like the samples of the path tracer, can tell when it is outdated
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
 
-        let elapsed = time_provider.total_time().as_secs_f32();
-        let orbit_speed = 1.0;
-        let angle = elapsed * orbit_speed;
+    /// The edits made with `apply` and in the viewer, which can be undone
+    pub fn history(&self) -> &History {
+        &self.history
+    }
 
-        let new_light_x = light_orbit_point.x + light_orbit_radius * angle.cos();
-        let new_light_z = light_orbit_point.z + light_orbit_radius * angle.sin();
+    pub fn history_mut(&mut self) -> &mut History {
+        &mut self.history
+    }
 
-        let new_light_position = Vector3::new(new_light_x, first_light_pos.y, new_light_z);
+    fn animate(&mut self, time_provider: &impl TimeProvider) {
+        let time = time_provider.total_time().as_secs_f32();
+        // the changes of the transforms are collected first, so each node is updated once
+        let mut transforms: BTreeMap<NodeId, Transform> = BTreeMap::new();
+        for animation in &self.animations {
+            let change = if let Some(change) = animation.change_at(time) { change } else { continue; };
+            match change {
+                Change::Position(Target::Light(id), position) => {
+                    if let Some(Some(light)) = self.lights.get_mut(id.0) {
+                        light.set_position(position);
+                    }
+                }
+                Change::Rotation(Target::Light(id), rotation) => {
+                    if let Some(Some(light)) = self.lights.get_mut(id.0) {
+                        light.set_direction(rotation.rotate_vector(-Vector3::unit_z()));
+                    }
+                }
+                Change::Color(Target::Light(id), color) => {
+                    if let Some(Some(light)) = self.lights.get_mut(id.0) {
+                        light.set_color(color);
+                    }
+                }
+                Change::Position(Target::Camera, position) => self.camera.set_position(position),
+                Change::Rotation(Target::Camera, rotation) => self.camera.set_rotation(rotation),
+                // only the shape changes color, not the other shapes with the same material
+                Change::Color(Target::Shape(id), color) => {
+                    if let Some(Some(_)) = self.shapes.get(id.0) {
+                        self.shape_albedos[id.0] = Some(color);
+                    }
+                }
+                Change::Position(target, position) => {
+                    if let Some(node) = self.target_node(target) {
+                        transforms.entry(node).or_insert_with(|| self.graph.transform(node)).translation = position;
+                    }
+                }
+                Change::Rotation(target, rotation) => {
+                    if let Some(node) = self.target_node(target) {
+                        transforms.entry(node).or_insert_with(|| self.graph.transform(node)).rotation = rotation;
+                    }
+                }
+                Change::Scale(target, scale) => {
+                    if let Some(node) = self.target_node(target) {
+                        transforms.entry(node).or_insert_with(|| self.graph.transform(node)).scale = Vector3::new(scale, scale, scale);
+                    }
+                }
+                Change::Color(Target::Node(_) | Target::Camera, _) => {}
+            }
+        }
+        for animation in &self.animations {
+            if let Animation::Attach { shape, light } = *animation {
+                let (node, position) = match (self.shape_node(shape), self.light(light).and_then(Light::position)) {
+                    (Some(node), Some(position)) => (node, position),
+                    _ => continue,
+                };
+                // the light is in world space, the translation relative to the parent of the node
+                let parent_matrix = self.graph.parent(node).map(|parent| self.graph.world_matrix(parent));
+                let translation = match parent_matrix.map(|matrix| matrix.invert()) {
+                    Some(Some(inverse)) => inverse.transform_point(Point3::from_vec(position)).to_vec(),
+                    Some(None) => continue,
+                    None => position,
+                };
+                transforms.entry(node).or_insert_with(|| self.graph.transform(node)).translation = translation;
+            }
+        }
 
-        *first_light_pos = new_light_position;
+        for (node, transform) in transforms {
+            self.set_transform(node, transform);
+        }
+    }
 
-        self.spheres[0].set_center(*first_light_pos)
+    /// The node moved by animations of the target, `None` for lights and the camera
+    fn target_node(&self, target: Target) -> Option<NodeId> {
+        match target {
+            Target::Shape(shape) => self.shape_node(shape),
+            Target::Node(node) => Some(node).filter(|&node| self.graph.contains(node)),
+            Target::Light(_) | Target::Camera => None,
+        }
     }
 }
 
 pub trait TimeProvider {
     fn total_time(&self) -> &Duration;
     fn dt(&self) -> &Duration;
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::shapes::Sphere;
+    use crate::core::light::{AreaLight, AreaLightShape, Attenuation, DirectionalLight, PointLight};
+    use cgmath::Zero;
+
+    const ALBEDO: Vector3<f32> = Vector3::new(1.0, 0.5, 0.25);
+    const AMBIENT: f32 = 0.1;
+    // the material of every shape in the tests, the first material added to the scene
+    const SURFACE: MaterialId = MaterialId(0);
+
+    // a unit sphere at the origin, lit from straight above by a light 4 units away from its top
+    fn create_scene() -> Scene {
+        let camera = Camera::from_position_and_target(Vector3::zero(), -Vector3::unit_z());
+        let mut scene = Scene::new(camera);
+        scene.add_material(Material::diffuse(ALBEDO));
+        scene.add_shape(Sphere::new(Vector3::zero(), 1.0, SURFACE));
+        scene.add_light(PointLight::new(
+            Vector3::new(0.0, 5.0, 0.0),
+            Vector3::new(1.0, 1.0, 1.0),
+            8.0,
+            Attenuation::InverseSquare,
+        ).into());
+        scene
+    }
+
+    // hits the top of the sphere at (0, 1, 0), without passing over it
+    fn ray_to_top_of_sphere() -> Ray {
+        Ray::new(Vector3::new(4.0, 5.0, 0.0), Vector3::new(-1.0, -1.0, 0.0))
+    }
+
+    fn assert_color_eq(actual: Vector3<f32>, expected: Vector3<f32>) {
+        assert!(
+            (actual - expected).magnitude() < 1e-4,
+            "expected {:?}, got {:?}", expected, actual,
+        );
+    }
+
+    #[test]
+    fn lit_surface_gets_ambient_and_attenuated_light() {
+        let scene = create_scene();
+        // cos_theta = 1, distance = 4, radiance = 8 / 4^2 = 0.5
+        let expected = ALBEDO * (AMBIENT + 0.5);
+        assert_color_eq(scene.trace(&ray_to_top_of_sphere()), expected);
+    }
+
+    #[test]
+    fn occluder_between_surface_and_light_casts_shadow() {
+        let mut scene = create_scene();
+        scene.add_shape(Sphere::new(Vector3::new(0.0, 3.0, 0.0), 0.5, SURFACE));
+        assert_color_eq(scene.trace(&ray_to_top_of_sphere()), ALBEDO * AMBIENT);
+    }
+
+    #[test]
+    fn occluder_behind_light_casts_no_shadow() {
+        let mut scene = create_scene();
+        scene.add_shape(Sphere::new(Vector3::new(0.0, 8.0, 0.0), 0.5, SURFACE));
+        assert_color_eq(scene.trace(&ray_to_top_of_sphere()), ALBEDO * (AMBIENT + 0.5));
+    }
+
+    #[test]
+    fn sphere_without_shadow_does_not_occlude() {
+        let mut scene = create_scene();
+        let mut occluder = Sphere::new(Vector3::new(0.0, 3.0, 0.0), 0.5, SURFACE);
+        occluder.set_casts_shadow(false);
+        scene.add_shape(occluder);
+        assert_color_eq(scene.trace(&ray_to_top_of_sphere()), ALBEDO * (AMBIENT + 0.5));
+    }
+
+    #[test]
+    fn directional_light_is_not_attenuated() {
+        let mut scene = create_scene();
+        scene.lights.clear();
+        scene.add_light(DirectionalLight::new(-Vector3::unit_y(), Vector3::new(1.0, 1.0, 1.0), 0.5).into());
+        assert_color_eq(scene.trace(&ray_to_top_of_sphere()), ALBEDO * (AMBIENT + 0.5));
+    }
+
+    #[test]
+    fn area_light_casts_soft_shadow() {
+        let mut scene = create_scene();
+        scene.lights.clear();
+        // a 2x2 grid of samples at x = -1 and x = 1, the occluder only blocks the ones at x = 1
+        scene.add_light(AreaLight::new(
+            AreaLightShape::Rectangle {
+                center: Vector3::new(0.0, 5.0, 0.0),
+                edge1: Vector3::new(2.0, 0.0, 0.0),
+                edge2: Vector3::new(0.0, 0.0, 0.01),
+            },
+            Vector3::new(1.0, 1.0, 1.0),
+            1.0,
+            Attenuation::None,
+            2,
+        ).into());
+        scene.add_shape(Sphere::new(Vector3::new(0.5, 3.0, 0.0), 0.2, SURFACE));
+
+        let lit_color = scene.trace(&ray_to_top_of_sphere()) - ALBEDO * AMBIENT;
+        // the unoccluded samples are at (-1, 5, ±0.005), 4 units above and 1 unit to the side
+        let cos_theta = 4.0 / 17f32.sqrt();
+        assert_color_eq(lit_color, ALBEDO * cos_theta * 0.5);
+    }
+
+    #[test]
+    fn accelerated_scene_gives_the_same_result() {
+        let mut scene = create_scene();
+        scene.add_shape(Sphere::new(Vector3::new(0.0, 3.0, 0.0), 0.5, SURFACE));
+        scene.add_shape(crate::core::shapes::Plane::new(-Vector3::unit_y(), Vector3::unit_y(), SURFACE));
+        let expected = scene.trace(&ray_to_top_of_sphere());
+
+        scene.update_acceleration();
+        assert!(scene.acceleration.is_some());
+        assert_color_eq(scene.trace(&ray_to_top_of_sphere()), expected);
+    }
+
+    #[test]
+    fn glossy_surface_has_blinn_phong_highlight() {
+        let mut scene = create_scene();
+        *scene.material_mut(SURFACE) = Material::glossy(ALBEDO, Vector3::new(1.0, 1.0, 1.0), 10.0);
+        // the viewer is 45 degrees away from the light, so the halfway vector is 22.5 degrees from the normal
+        let specular_strength = 22.5f32.to_radians().cos().powf(10.0);
+        let expected = ALBEDO * (AMBIENT + 0.5) + Vector3::new(1.0, 1.0, 1.0) * specular_strength * 0.5;
+        assert_color_eq(scene.trace(&ray_to_top_of_sphere()), expected);
+    }
+
+    #[test]
+    fn emissive_surface_glows_without_light() {
+        let mut scene = create_scene();
+        scene.lights.clear();
+        *scene.material_mut(SURFACE) = Material::emissive(Vector3::new(0.2, 0.4, 0.6));
+        assert_color_eq(scene.trace(&ray_to_top_of_sphere()), Vector3::new(0.2, 0.4, 0.6));
+    }
+
+    // an emissive sphere with the given center and a material for the other shapes
+    fn create_optics_scene(glow_center: Vector3<f32>, material: Material) -> (Scene, MaterialId) {
+        let camera = Camera::from_position_and_target(Vector3::zero(), -Vector3::unit_z());
+        let mut scene = Scene::new(camera);
+        let glow = scene.add_material(Material::emissive(Vector3::new(0.2, 0.4, 0.6)));
+        scene.add_shape(Sphere::new(glow_center, 1.0, glow));
+        let material = scene.add_material(material);
+        (scene, material)
+    }
+
+    #[test]
+    fn mirror_reflects_the_scene() {
+        let mirror = Material { reflectivity: 1.0, ..Material::diffuse(Vector3::zero()) };
+        let (mut scene, mirror) = create_optics_scene(Vector3::new(4.0, 4.0, 0.0), mirror);
+        scene.add_shape(crate::core::shapes::Plane::new(Vector3::zero(), Vector3::unit_y(), mirror));
+        // bounces off the mirror at the origin, towards the center of the glowing sphere
+        let ray = Ray::new(Vector3::new(-4.0, 4.0, 0.0), Vector3::new(1.0, -1.0, 0.0));
+
+        assert_color_eq(scene.trace(&ray), Vector3::new(0.2, 0.4, 0.6));
+        scene.set_max_depth(0);
+        assert_color_eq(scene.trace(&ray), Vector3::zero());
+    }
+
+    #[test]
+    fn glass_sphere_lets_light_through() {
+        // without a change of refractive index the ray isn't bent, and hitting the surfaces head on, nothing is reflected
+        let glass = Material { transparency: 1.0, refractive_index: 1.0, ..Material::diffuse(Vector3::zero()) };
+        let (mut scene, glass) = create_optics_scene(Vector3::new(0.0, 0.0, -10.0), glass);
+        scene.add_shape(Sphere::new(Vector3::new(0.0, 0.0, -5.0), 1.0, glass));
+
+        assert_color_eq(scene.trace(&Ray::new(Vector3::zero(), -Vector3::unit_z())), Vector3::new(0.2, 0.4, 0.6));
+    }
+
+    #[test]
+    fn attenuation_factors() {
+        assert_eq!(Attenuation::None.factor(4.0), 1.0);
+        assert_eq!(Attenuation::Linear.factor(4.0), 0.25);
+        assert_eq!(Attenuation::InverseSquare.factor(4.0), 0.0625);
+    }
+
+    struct Time(Duration);
+
+    impl TimeProvider for Time {
+        fn total_time(&self) -> &Duration { &self.0 }
+        fn dt(&self) -> &Duration { &Duration::ZERO }
+    }
+
+    #[test]
+    fn attached_shape_follows_its_light() {
+        let mut scene = create_scene();
+        let marker = scene.add_shape(Sphere::new(Vector3::zero(), 0.1, SURFACE));
+        let light = LightId(0);
+        scene.add_animation(Animation::Attach { shape: marker, light });
+        scene.add_animation(Animation::Orbit {
+            target: Target::Light(light),
+            center: Vector3::new(0.0, 5.0, 0.0),
+            radius: 2.0,
+            speed: std::f32::consts::FRAC_PI_2,
+        });
+
+        scene.tick(std::iter::empty(), &Time(Duration::from_secs(1)));
+        let light_position = scene.light(light).unwrap().position().unwrap();
+        assert_color_eq(light_position, Vector3::new(0.0, 5.0, 2.0));
+        assert_color_eq(scene.transform(scene.shape_node(marker).unwrap()).unwrap().translation, light_position);
+        // the marker is drawn around the light
+        let hit = scene.intersect(&Ray::new(Vector3::new(0.0, 10.0, 2.0), -Vector3::unit_y())).unwrap();
+        assert!((hit.intersection.y - 5.1).abs() < 1e-4);
+    }
+
+    #[test]
+    fn animated_shapes_are_transformed() {
+        use crate::core::animation::{Keyframe, Track};
+        use crate::core::shapes::Cylinder;
+        use cgmath::{Deg, Quaternion, Rotation3};
+
+        let mut scene = create_scene();
+        let cylinder = scene.add_shape(Cylinder::new(Vector3::new(0.0, 5.0, 0.0), Vector3::unit_y(), 1.0, 2.0, SURFACE));
+        let target = Target::Shape(cylinder);
+        let rotation = Quaternion::from_angle_x(Deg(90.0));
+        scene.add_animation(Animation::Rotation { target, track: Track::new(vec![Keyframe::new(0.0, rotation)], false) });
+        scene.add_animation(Animation::Scale { target, track: Track::new(vec![Keyframe::new(0.0, 2.0)], false) });
+
+        // the same time gives the same transform, the rotation and the scaling are not applied again
+        for _ in 0..3 {
+            scene.tick(std::iter::empty(), &Time(Duration::from_secs(1)));
+        }
+        let transform = scene.transform(scene.shape_node(cylinder).unwrap()).unwrap();
+        assert_eq!((transform.rotation, transform.scale), (rotation, Vector3::new(2.0, 2.0, 2.0)));
+        // the cylinder is scaled and rotated around the origin, so it now lies along the z axis from 10 to 14 with a radius of 2
+        let hit = scene.intersect(&Ray::new(Vector3::new(0.0, 10.0, 12.0), -Vector3::unit_y())).unwrap();
+        assert!((hit.intersection.y - 2.0).abs() < 1e-4);
+        assert!(scene.intersect(&Ray::new(Vector3::new(0.0, 10.0, 16.0), -Vector3::unit_y())).is_none());
+    }
+
+    #[test]
+    fn animated_colors_only_change_their_shape() {
+        use crate::core::animation::{Keyframe, Track};
+
+        let mut scene = create_scene();
+        let other = scene.add_shape(Sphere::new(Vector3::new(0.0, 0.0, -10.0), 1.0, SURFACE));
+        let red = Vector3::new(1.0, 0.0, 0.0);
+        let track = Track::new(vec![Keyframe::new(0.0, red)], false);
+        scene.add_animation(Animation::Color { target: Target::Shape(ShapeId(0)), track });
+        scene.tick(std::iter::empty(), &Time(Duration::from_secs(1)));
+
+        assert_eq!(scene.shape_albedo(ShapeId(0)), Some(red));
+        assert_eq!(scene.shape_albedo(other), Some(ALBEDO));
+        assert_eq!(scene.material(SURFACE).albedo, ALBEDO);
+        let (hit, material) = scene.intersect_surface(&ray_to_top_of_sphere()).unwrap();
+        assert_eq!((hit.material, material.albedo), (SURFACE, red));
+
+        // a removed shape isn't animated any more, and is shown with its material when it is put back
+        let removed = scene.take_shape(ShapeId(0)).unwrap();
+        assert!(scene.restore_shape(RemovedShape { animations: Vec::new(), ..removed }));
+        assert_eq!(scene.shape_albedo(ShapeId(0)), Some(ALBEDO));
+    }
+
+    #[test]
+    fn children_follow_their_group() {
+        let mut scene = create_scene();
+        let planet = scene.add_group(Transform::from_translation(Vector3::new(0.0, 0.0, -10.0)));
+        let moon = scene.add_shape(Sphere::new(Vector3::new(3.0, 0.0, 0.0), 0.5, SURFACE));
+        assert!(scene.set_parent(scene.shape_node(moon).unwrap(), Some(planet)));
+        assert!(!scene.set_parent(planet, Some(scene.shape_node(moon).unwrap())));
+
+        let down = -Vector3::unit_y();
+        let hit = scene.intersect(&Ray::new(Vector3::new(3.0, 10.0, -10.0), down)).unwrap();
+        assert!((hit.intersection.y - 0.5).abs() < 1e-4);
+
+        scene.set_transform(planet, Transform::from_translation(Vector3::new(0.0, 2.0, -20.0)));
+        assert!(scene.intersect(&Ray::new(Vector3::new(3.0, 10.0, -10.0), down)).is_none());
+        let hit = scene.intersect(&Ray::new(Vector3::new(3.0, 10.0, -20.0), down)).unwrap();
+        assert!((hit.intersection.y - 2.5).abs() < 1e-4);
+    }
+
+    #[test]
+    fn removed_shapes_keep_the_other_handles_valid() {
+        let mut scene = create_scene();
+        let sphere = ShapeId(0);
+        let planet = scene.add_named_shape("planet", Sphere::new(Vector3::new(0.0, 0.0, -10.0), 1.0, SURFACE));
+        let moon = scene.add_shape(Sphere::new(Vector3::new(3.0, 0.0, -10.0), 0.5, SURFACE));
+        scene.set_parent(scene.shape_node(moon).unwrap(), scene.shape_node(planet));
+        scene.add_animation(Animation::Attach { shape: planet, light: LightId(0) });
+        scene.update_acceleration();
+
+        assert!(scene.remove_shape(planet).is_some());
+        assert!(scene.remove_shape(planet).is_none());
+        assert!(scene.shape(planet).is_none() && scene.find_shape("planet").is_none());
+        assert!(scene.animations().is_empty());
+        assert_eq!(scene.shapes().map(|(id, _)| id).collect::<Vec<_>>(), vec![sphere, moon]);
+        assert_eq!(scene.graph().parent(scene.shape_node(moon).unwrap()), None);
+        assert!(scene.intersect(&Ray::new(Vector3::new(0.0, 10.0, -10.0), -Vector3::unit_y())).is_none());
+        assert!(scene.intersect(&Ray::new(Vector3::new(3.0, 10.0, -10.0), -Vector3::unit_y())).is_some());
+
+        // the handle of a removed shape isn't given to a new one
+        assert_ne!(scene.add_shape(Sphere::new(Vector3::zero(), 1.0, SURFACE)), planet);
+        *scene.shape_mut(moon).unwrap() = Sphere::new(Vector3::new(6.0, 0.0, -10.0), 0.5, SURFACE).into();
+        scene.update_acceleration();
+        assert!(scene.intersect(&Ray::new(Vector3::new(6.0, 10.0, -10.0), -Vector3::unit_y())).is_some());
+    }
+
+    #[test]
+    fn removed_lights_stop_lighting_the_scene() {
+        let mut scene = create_scene();
+        let light = scene.add_named_light("sun", DirectionalLight::new(-Vector3::unit_y(), Vector3::new(1.0, 1.0, 1.0), 1.0).into());
+        assert_eq!(scene.find_light("sun"), Some(light));
+        scene.light_mut(LightId(0)).unwrap().set_color(Vector3::zero());
+        let lit = scene.trace(&ray_to_top_of_sphere());
+
+        assert!(scene.remove_light(light).is_some());
+        assert_eq!(scene.lights().count(), 1);
+        assert_color_eq(scene.trace(&ray_to_top_of_sphere()), ALBEDO * AMBIENT);
+        assert!(lit.x > (ALBEDO * AMBIENT).x);
+    }
+
+    #[test]
+    fn shapes_moved_after_the_bvh_was_built_are_hit() {
+        let mut scene = create_scene();
+        scene.remove_shape(ShapeId(0));
+        let sphere = scene.add_shape(Sphere::new(Vector3::new(20.0, 0.0, -10.0), 1.0, SURFACE));
+        scene.add_shape(Sphere::new(Vector3::new(-20.0, 0.0, -10.0), 1.0, SURFACE));
+        scene.camera_mut().set_aspect_ratio(1.0);
+        scene.update_acceleration();
+        assert_eq!(scene.pick(Vector2::new(0.0, 0.0)), None);
+
+        let node = scene.shape_node(sphere).unwrap();
+        scene.set_transform(node, Transform::from_translation(Vector3::new(-20.0, 0.0, 0.0)));
+        assert_eq!(scene.pick(Vector2::new(0.0, 0.0)).map(|pick| pick.shape), Some(sphere));
+        scene.update_acceleration();
+        assert_eq!(scene.pick(Vector2::new(0.0, 0.0)).map(|pick| pick.shape), Some(sphere));
+
+        // the same after an undo, which moves the sphere back out of view
+        assert!(scene.apply(Edit::SetTransform(node, Transform::identity())));
+        scene.update_acceleration();
+        assert!(scene.undo());
+        assert_eq!(scene.pick(Vector2::new(0.0, 0.0)).map(|pick| pick.shape), Some(sphere));
+    }
+
+    #[test]
+    fn clicks_select_the_shape_under_the_cursor() {
+        let mut scene = create_scene();
+        let far_sphere = scene.add_shape(Sphere::new(Vector3::new(0.0, 0.0, -10.0), 1.0, SURFACE));
+        scene.camera_mut().set_aspect_ratio(1.0);
+
+        let pick = scene.pick(Vector2::new(0.0, 0.0)).unwrap();
+        assert_eq!(pick.shape, ShapeId(0));
+        assert!((pick.distance - 1.0).abs() < 1e-4);
+        assert_color_eq(pick.point, Vector3::new(0.0, 0.0, -1.0));
+        // tracing finds the same shape, with the color of `trace`
+        let ray = scene.camera().get_ray(Vector2::new(0.0, 0.0));
+        assert_eq!(scene.trace_with_shape(&ray), (scene.trace(&ray), Some(ShapeId(0))));
+
+        // the camera is inside the first sphere, without it the far one is seen in the middle of the screen
+        scene.remove_shape(ShapeId(0));
+        let select = |x: i16, y: i16| SceneAction::Select { position: Vector2::new(x, y) };
+        scene.tick([select(0, 0)].into_iter(), &Time(Duration::ZERO));
+        assert_eq!(scene.selected(), Some(far_sphere));
+        scene.tick([select(900, 900)].into_iter(), &Time(Duration::ZERO));
+        assert_eq!(scene.selected(), None);
+    }
+
+    #[test]
+    fn selected_shapes_are_edited() {
+        let mut scene = create_scene();
+        scene.remove_shape(ShapeId(0));
+        let sphere = scene.add_shape(Sphere::new(Vector3::new(0.0, 0.0, -10.0), 1.0, SURFACE));
+        let other_material = scene.add_material(Material::default());
+        scene.camera_mut().set_aspect_ratio(1.0);
+        let edit = |scene: &mut Scene, action: SceneAction| scene.tick([action].into_iter(), &Time(Duration::ZERO));
+        edit(&mut scene, SceneAction::Select { position: Vector2::new(0, 0) });
+        // nothing happens to the other shapes without a selection
+        edit(&mut scene, SceneAction::Select { position: Vector2::new(900, 900) });
+        edit(&mut scene, SceneAction::ScaleSelected { steps: 1 });
+        edit(&mut scene, SceneAction::Select { position: Vector2::new(0, 0) });
+
+        // the middle of the sphere stays under the cursor
+        edit(&mut scene, SceneAction::DragSelected { delta: Vector2::new(100, -200) });
+        let center = scene.shape_center(sphere);
+        let ray = scene.camera().get_ray(Vector2::new(0.1, -0.2));
+        assert!(ray.direction().get().cross(center - ray.origin()).magnitude() < 1e-3);
+        assert!((center.z - -10.0).abs() < 1e-4);
+
+        edit(&mut scene, SceneAction::ScaleSelected { steps: 2 });
+        let node = scene.shape_node(sphere).unwrap();
+        assert!((scene.transform(node).unwrap().scale.x - 1.21).abs() < 1e-5);
+        assert_color_eq(scene.shape_center(sphere), center);
+
+        edit(&mut scene, SceneAction::CycleMaterial { forward: true });
+        assert_eq!(scene.shape(sphere).unwrap().material(), other_material);
+        edit(&mut scene, SceneAction::CycleMaterial { forward: true });
+        assert_eq!(scene.shape(sphere).unwrap().material(), SURFACE);
+        edit(&mut scene, SceneAction::CycleMaterial { forward: false });
+        assert_eq!(scene.shape(sphere).unwrap().material(), other_material);
+
+        edit(&mut scene, SceneAction::DuplicateSelected);
+        let copy = scene.selected().unwrap();
+        assert_ne!(copy, sphere);
+        assert_eq!(scene.transform(scene.shape_node(copy).unwrap()), scene.transform(node));
+        assert_color_eq(scene.shape_center(copy), center);
+
+        edit(&mut scene, SceneAction::DeleteSelected);
+        assert_eq!((scene.selected(), scene.shape(copy).is_some()), (None, false));
+        assert_eq!(scene.shapes().count(), 1);
+    }
+}
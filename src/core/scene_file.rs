@@ -0,0 +1,1402 @@
+//! Loading and saving of scenes as TOML files.
+//!
+//! A scene file has a `camera` table, named `materials`, and arrays of `lights`, `shapes` and `animations`,
+//! which choose their kind with a `type` key. Shapes refer to materials by their name, animations refer to
+//! lights and shapes by their index in the file. Angles are in degrees, times in seconds.
+//!
+//! ```toml
+//! ambient = [0.1, 0.1, 0.1]
+//!
+//! [camera]
+//! position = [0, 0, 5]
+//! target = [0, 0, 0]
+//!
+//! [materials.red]
+//! albedo = [0.9, 0.1, 0.1]
+//!
+//! [[lights]]
+//! type = "point"
+//! position = [0, 4, 2]
+//!
+//! [[shapes]]
+//! type = "sphere"
+//! center = [0, 0, 0]
+//! radius = 1
+//! material = "red"
+//!
+//! [[animations]]
+//! type = "position"
+//! target = { shape = 0 }
+//! repeat = true
+//! keyframes = [
+//!     { time = 0, value = [0, 0, 0], easing = "ease_in_out" },
+//!     { time = 1, value = [0, 1, 0], easing = { cubic_bezier = [0.3, 0, 0.2, 1] } },
+//!     { time = 2, value = [0, 0, 0] },
+//! ]
+//! ```
+
+use crate::core::animation::{Animation, Easing, Interpolate, Keyframe, Target, Track};
+use crate::core::camera::Camera;
+use crate::core::light::{AreaLight, AreaLightShape, Attenuation, DirectionalLight, Light, LightId, PointLight, SpotLight};
+use crate::core::material::{Material, MaterialId};
+use crate::core::obj::load_obj;
+use crate::core::scene::Scene;
+use crate::core::scene_graph::NodeId;
+use crate::core::transform::Transform;
+use crate::core::shapes::{AxisAlignedBox, Cone, Cylinder, Disc, Hittable, Plane, Shape, ShapeId, Sphere, Triangle};
+use cgmath::{Deg, Euler, InnerSpace, Quaternion, Vector3};
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize, Serializer};
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use toml::Spanned;
+
+#[derive(Debug)]
+pub enum SceneFileError {
+    Io(std::io::Error),
+    /// The file is not a valid scene, `line` and `column` start at 1
+    Parse { line: usize, column: usize, message: String },
+    /// The scene contains something a scene file can't describe, like a mesh that wasn't loaded from a file
+    Unsupported(String),
+}
+
+impl Display for SceneFileError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SceneFileError::Io(error) => write!(f, "couldn't access scene file: {}", error),
+            SceneFileError::Parse { line, column, message } => {
+                write!(f, "invalid scene file at line {}, column {}: {}", line, column, message)
+            }
+            SceneFileError::Unsupported(message) => write!(f, "can't save scene: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for SceneFileError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SceneFileError::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for SceneFileError {
+    fn from(error: std::io::Error) -> SceneFileError {
+        SceneFileError::Io(error)
+    }
+}
+
+/// Reads a scene file, paths of meshes in it are relative to the directory of the file
+pub fn load_scene(path: impl AsRef<Path>) -> Result<Scene, SceneFileError> {
+    let path = path.as_ref();
+    let source = std::fs::read_to_string(path)?;
+    parse_scene(&source, path.parent().unwrap_or(Path::new("")))
+}
+
+/// Paths of meshes in the source are relative to `base_directory`
+pub fn parse_scene(source: &str, base_directory: &Path) -> Result<Scene, SceneFileError> {
+    let parse_error = |span: Option<Range<usize>>, message: String| {
+        let (line, column) = line_and_column(source, span.map_or(0, |span| span.start));
+        SceneFileError::Parse { line, column, message }
+    };
+    let description: SceneDescription = toml::from_str(source)
+        .map_err(|error| parse_error(error.span(), error.message().to_string()))?;
+
+    let camera = &description.camera;
+    let mut scene = Scene::new(Camera::from_position_and_target(vector(camera.position), vector(camera.target)));
+    scene.camera_mut().set_fov(Deg(camera.fov.0));
+    scene.set_ambient_light_color(vector(description.ambient));
+    scene.set_max_depth(description.max_depth);
+
+    for (name, material) in description.materials {
+        scene.add_named_material(name, material.into());
+    }
+    let mut order = FileOrder::default();
+    for light in description.lights {
+        check_light(&light.get_ref().light).map_err(|message| parse_error(Some(light.span()), message))?;
+        let NamedLightDescription { name, light } = light.into_inner();
+        order.lights.push(match name {
+            Some(name) => scene.add_named_light(name, light.into()),
+            None => scene.add_light(light.into()),
+        });
+    }
+    order.groups = description.groups.iter()
+        .map(|group| scene.add_group(group.get_ref().transform.into()))
+        .collect();
+    for shape in &description.shapes {
+        let ShapeNodeDescription { name, shape: shape_description, transform, .. } = shape.get_ref();
+        let shape_with_geometry = shape_from_description(shape_description, &scene, base_directory)
+            .map_err(|message| parse_error(Some(shape.span()), message))?;
+        let id = match name {
+            Some(name) => scene.add_named_shape(name.clone(), shape_with_geometry),
+            None => scene.add_shape(shape_with_geometry),
+        };
+        let node = scene.shape_node(id).expect("the shape was just added");
+        scene.set_transform(node, (*transform).into());
+        order.shapes.push(id);
+        order.shape_nodes.push(node);
+    }
+    // the parents are set once every node exists, so nodes can refer to nodes later in the file
+    let nodes_with_parents = description.groups.iter().zip(&order.groups)
+        .map(|(group, &node)| (group.span(), node, group.get_ref().parent))
+        .chain(description.shapes.iter().zip(&order.shape_nodes).map(|(shape, &node)| {
+            (shape.span(), node, shape.get_ref().parent)
+        }))
+        .collect::<Vec<_>>();
+    for (span, node, parent) in nodes_with_parents {
+        let parent = if let Some(parent) = parent { parent } else { continue; };
+        let parent = order.node(parent).map_err(|message| parse_error(Some(span.clone()), message))?;
+        if !scene.set_parent(node, Some(parent)) {
+            return Err(parse_error(Some(span), "the parent is a descendant of the node, that would make a cycle".to_string()));
+        }
+    }
+    for animation in &description.animations {
+        let animation = animation_from_description(animation.get_ref(), &order)
+            .map_err(|message| parse_error(Some(animation.span()), message))?;
+        scene.add_animation(animation);
+    }
+
+    Ok(scene)
+}
+
+/// Writes the scene to a file, paths of meshes are written relative to the directory of the file if possible
+pub fn save_scene(scene: &Scene, path: impl AsRef<Path>) -> Result<(), SceneFileError> {
+    let path = path.as_ref();
+    let source = scene_to_string(scene, path.parent().unwrap_or(Path::new("")))?;
+    std::fs::write(path, source)?;
+    Ok(())
+}
+
+/// The scene file describing the scene, paths of meshes are written relative to `base_directory` if possible.
+/// Materials without a name get one made up from their index.
+pub fn scene_to_string(scene: &Scene, base_directory: &Path) -> Result<String, SceneFileError> {
+    let material_names = unique_material_names(scene);
+    let material_name = |id: MaterialId| material_names[&id].clone();
+
+    let order = FileOrder::of_scene(scene);
+    let parent_description = |node: NodeId| scene.graph().parent(node).map(|parent| order.node_description(parent));
+
+    let camera = scene.camera();
+    let description = SceneDescription {
+        ambient: floats(scene.ambient_light_color()),
+        max_depth: scene.max_depth(),
+        camera: CameraDescription {
+            position: floats(camera.position()),
+            // the target and the field of view are computed from angles, they are rounded so that
+            // a target of [0, 0, -1] isn't written as [-0.00000017, 0.00000004, -1]
+            target: floats((camera.position() + camera.forward()).map(round)),
+            fov: Float(round(Deg::from(camera.fov()).0)),
+        },
+        materials: scene.materials()
+            .map(|(id, material)| (material_name(id), MaterialDescription::from(material)))
+            .collect(),
+        lights: scene.lights()
+            .map(|(id, light)| unspanned(NamedLightDescription {
+                name: scene.light_name(id).map(str::to_string),
+                light: LightDescription::from(light),
+            }))
+            .collect(),
+        groups: order.groups.iter()
+            .map(|&node| unspanned(GroupDescription {
+                transform: scene.graph().transform(node).into(),
+                parent: parent_description(node),
+            }))
+            .collect(),
+        shapes: scene.shapes().zip(&order.shape_nodes)
+            .map(|((id, shape), &node)| {
+                Ok(unspanned(ShapeNodeDescription {
+                    name: scene.shape_name(id).map(str::to_string),
+                    shape: shape_to_description(shape, material_name(shape.material()), base_directory)?,
+                    transform: scene.graph().transform(node).into(),
+                    parent: parent_description(node),
+                }))
+            })
+            .collect::<Result<_, SceneFileError>>()?,
+        animations: scene.animations().iter()
+            .map(|animation| unspanned(animation_to_description(animation, &order)))
+            .collect(),
+    };
+    toml::to_string(&description).map_err(|error| SceneFileError::Unsupported(error.to_string()))
+}
+
+fn line_and_column(source: &str, offset: usize) -> (usize, usize) {
+    let before = &source[..offset.min(source.len())];
+    let line = before.matches('\n').count() + 1;
+    let column = before.rsplit('\n').next().map_or(0, |line| line.chars().count()) + 1;
+    (line, column)
+}
+
+fn round(x: f32) -> f32 {
+    const PRECISION: f64 = 1e5;
+    // adding 0 turns -0 into 0
+    ((x as f64 * PRECISION).round() / PRECISION) as f32 + 0.0
+}
+
+fn unspanned<T>(value: T) -> Spanned<T> {
+    Spanned::new(0..0, value)
+}
+
+fn unique_material_names(scene: &Scene) -> BTreeMap<MaterialId, String> {
+    let mut names = BTreeMap::new();
+    for (id, _) in scene.materials() {
+        let name = match scene.material_name(id) {
+            Some(name) => name.to_string(),
+            None => {
+                let mut name = format!("material_{}", id.0);
+                while scene.find_material(&name).is_some() {
+                    name.push('_');
+                }
+                name
+            }
+        };
+        names.insert(id, name);
+    }
+    names
+}
+
+/// An `f32` that is written with the fewest digits that read back as the same number,
+/// e.g. `0.1` instead of `0.10000000149011612`
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(transparent)]
+struct Float(f32);
+
+impl Serialize for Float {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let shortest = self.0.to_string().parse().unwrap_or(self.0 as f64);
+        serializer.serialize_f64(shortest)
+    }
+}
+
+type Vec3 = [Float; 3];
+
+fn vector(v: Vec3) -> Vector3<f32> {
+    Vector3::new(v[0].0, v[1].0, v[2].0)
+}
+
+fn floats(v: Vector3<f32>) -> Vec3 {
+    [Float(v.x), Float(v.y), Float(v.z)]
+}
+
+fn white() -> Vec3 {
+    [Float(1.0); 3]
+}
+
+fn one() -> Float {
+    Float(1.0)
+}
+
+fn default_ambient() -> Vec3 {
+    [Float(0.1); 3]
+}
+
+fn default_max_depth() -> u32 {
+    5
+}
+
+fn default_fov() -> Float {
+    Float(45.0)
+}
+
+fn default_samples_per_axis() -> usize {
+    4
+}
+
+fn yes() -> bool {
+    true
+}
+
+fn is_true(value: &bool) -> bool {
+    *value
+}
+
+fn is_false(value: &bool) -> bool {
+    !*value
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SceneDescription {
+    #[serde(default = "default_ambient")]
+    ambient: Vec3,
+    #[serde(default = "default_max_depth")]
+    max_depth: u32,
+    camera: CameraDescription,
+    // the materials are added in the order of the file, which is the order they are cycled through in the viewer
+    #[serde(default)]
+    materials: IndexMap<String, MaterialDescription>,
+    #[serde(default)]
+    lights: Vec<Spanned<NamedLightDescription>>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    groups: Vec<Spanned<GroupDescription>>,
+    #[serde(default)]
+    shapes: Vec<Spanned<ShapeNodeDescription>>,
+    #[serde(default)]
+    animations: Vec<Spanned<AnimationDescription>>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CameraDescription {
+    position: Vec3,
+    target: Vec3,
+    /// Vertical field of view
+    #[serde(default = "default_fov")]
+    fov: Float,
+}
+
+/// Missing values are taken from `Material::default`
+#[derive(Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct MaterialDescription {
+    albedo: Vec3,
+    specular: Vec3,
+    shininess: Float,
+    emissive: Vec3,
+    reflectivity: Float,
+    transparency: Float,
+    refractive_index: Float,
+}
+
+impl Default for MaterialDescription {
+    fn default() -> MaterialDescription {
+        MaterialDescription::from(&Material::default())
+    }
+}
+
+impl From<&Material> for MaterialDescription {
+    fn from(material: &Material) -> MaterialDescription {
+        MaterialDescription {
+            albedo: floats(material.albedo),
+            specular: floats(material.specular),
+            shininess: Float(material.shininess),
+            emissive: floats(material.emissive),
+            reflectivity: Float(material.reflectivity),
+            transparency: Float(material.transparency),
+            refractive_index: Float(material.refractive_index),
+        }
+    }
+}
+
+impl From<MaterialDescription> for Material {
+    fn from(material: MaterialDescription) -> Material {
+        Material {
+            albedo: vector(material.albedo),
+            specular: vector(material.specular),
+            shininess: material.shininess.0,
+            emissive: vector(material.emissive),
+            reflectivity: material.reflectivity.0,
+            transparency: material.transparency.0,
+            refractive_index: material.refractive_index.0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum AttenuationDescription {
+    #[default]
+    None,
+    Linear,
+    InverseSquare,
+}
+
+impl From<Attenuation> for AttenuationDescription {
+    fn from(attenuation: Attenuation) -> AttenuationDescription {
+        match attenuation {
+            Attenuation::None => AttenuationDescription::None,
+            Attenuation::Linear => AttenuationDescription::Linear,
+            Attenuation::InverseSquare => AttenuationDescription::InverseSquare,
+        }
+    }
+}
+
+impl From<AttenuationDescription> for Attenuation {
+    fn from(attenuation: AttenuationDescription) -> Attenuation {
+        match attenuation {
+            AttenuationDescription::None => Attenuation::None,
+            AttenuationDescription::Linear => Attenuation::Linear,
+            AttenuationDescription::InverseSquare => Attenuation::InverseSquare,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum LightDescription {
+    Point {
+        position: Vec3,
+        #[serde(default = "white")]
+        color: Vec3,
+        #[serde(default = "one")]
+        intensity: Float,
+        #[serde(default)]
+        attenuation: AttenuationDescription,
+    },
+    Directional {
+        /// The direction the light travels in
+        direction: Vec3,
+        #[serde(default = "white")]
+        color: Vec3,
+        #[serde(default = "one")]
+        intensity: Float,
+    },
+    Spot {
+        position: Vec3,
+        direction: Vec3,
+        inner_angle: Float,
+        outer_angle: Float,
+        #[serde(default = "white")]
+        color: Vec3,
+        #[serde(default = "one")]
+        intensity: Float,
+        #[serde(default)]
+        attenuation: AttenuationDescription,
+    },
+    Area {
+        shape: AreaLightShapeDescription,
+        #[serde(default = "white")]
+        color: Vec3,
+        #[serde(default = "one")]
+        intensity: Float,
+        #[serde(default)]
+        attenuation: AttenuationDescription,
+        #[serde(default = "default_samples_per_axis")]
+        samples_per_axis: usize,
+    },
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum AreaLightShapeDescription {
+    Rectangle { center: Vec3, edge1: Vec3, edge2: Vec3 },
+    Sphere { center: Vec3, radius: Float },
+}
+
+impl From<&Light> for LightDescription {
+    fn from(light: &Light) -> LightDescription {
+        match light {
+            Light::Point(light) => LightDescription::Point {
+                position: floats(light.position),
+                color: floats(light.color),
+                intensity: Float(light.intensity),
+                attenuation: light.attenuation.into(),
+            },
+            Light::Directional(light) => LightDescription::Directional {
+                direction: floats(light.direction()),
+                color: floats(light.color),
+                intensity: Float(light.intensity),
+            },
+            Light::Spot(light) => LightDescription::Spot {
+                position: floats(light.position),
+                direction: floats(light.direction()),
+                inner_angle: Float(Deg::from(light.inner_angle()).0),
+                outer_angle: Float(Deg::from(light.outer_angle()).0),
+                color: floats(light.color),
+                intensity: Float(light.intensity),
+                attenuation: light.attenuation.into(),
+            },
+            Light::Area(light) => LightDescription::Area {
+                shape: match light.shape {
+                    AreaLightShape::Rectangle { center, edge1, edge2 } => AreaLightShapeDescription::Rectangle {
+                        center: floats(center),
+                        edge1: floats(edge1),
+                        edge2: floats(edge2),
+                    },
+                    AreaLightShape::Sphere { center, radius } => AreaLightShapeDescription::Sphere {
+                        center: floats(center),
+                        radius: Float(radius),
+                    },
+                },
+                color: floats(light.color),
+                intensity: Float(light.intensity),
+                attenuation: light.attenuation.into(),
+                samples_per_axis: light.samples_per_axis(),
+            },
+        }
+    }
+}
+
+impl From<LightDescription> for Light {
+    fn from(light: LightDescription) -> Light {
+        match light {
+            LightDescription::Point { position, color, intensity, attenuation } => {
+                PointLight::new(vector(position), vector(color), intensity.0, attenuation.into()).into()
+            }
+            LightDescription::Directional { direction, color, intensity } => {
+                DirectionalLight::new(vector(direction), vector(color), intensity.0).into()
+            }
+            LightDescription::Spot { position, direction, inner_angle, outer_angle, color, intensity, attenuation } => {
+                SpotLight::new(
+                    vector(position),
+                    vector(direction),
+                    Deg(inner_angle.0),
+                    Deg(outer_angle.0),
+                    vector(color),
+                    intensity.0,
+                    attenuation.into(),
+                ).into()
+            }
+            LightDescription::Area { shape, color, intensity, attenuation, samples_per_axis } => {
+                let shape = match shape {
+                    AreaLightShapeDescription::Rectangle { center, edge1, edge2 } => AreaLightShape::Rectangle {
+                        center: vector(center),
+                        edge1: vector(edge1),
+                        edge2: vector(edge2),
+                    },
+                    AreaLightShapeDescription::Sphere { center, radius } => AreaLightShape::Sphere {
+                        center: vector(center),
+                        radius: radius.0,
+                    },
+                };
+                AreaLight::new(shape, vector(color), intensity.0, attenuation.into(), samples_per_axis).into()
+            }
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum ShapeDescription {
+    Sphere {
+        center: Vec3,
+        radius: Float,
+        material: String,
+        #[serde(default = "yes", skip_serializing_if = "is_true")]
+        casts_shadow: bool,
+    },
+    Plane { point: Vec3, normal: Vec3, material: String },
+    Box { min: Vec3, max: Vec3, material: String },
+    Triangle { vertices: [Vec3; 3], material: String },
+    Disc { center: Vec3, normal: Vec3, radius: Float, material: String },
+    Cylinder { base_center: Vec3, axis: Vec3, radius: Float, height: Float, material: String },
+    Cone { base_center: Vec3, axis: Vec3, radius: Float, height: Float, material: String },
+    /// A Wavefront OBJ file
+    Mesh { path: PathBuf, material: String },
+}
+
+fn shape_from_description(shape: &ShapeDescription, scene: &Scene, base_directory: &Path) -> Result<Shape, String> {
+    let find_material = |name: &str| scene.find_material(name)
+        .ok_or_else(|| format!("there is no material named \"{}\"", name));
+    let shape: Shape = match shape {
+        ShapeDescription::Sphere { center, radius, material, casts_shadow } => {
+            let mut sphere = Sphere::new(vector(*center), positive("radius", *radius)?, find_material(material)?);
+            sphere.set_casts_shadow(*casts_shadow);
+            sphere.into()
+        }
+        ShapeDescription::Plane { point, normal, material } => {
+            Plane::new(vector(*point), direction("normal", *normal)?, find_material(material)?).into()
+        }
+        ShapeDescription::Box { min, max, material } => {
+            AxisAlignedBox::new(vector(*min), vector(*max), find_material(material)?).into()
+        }
+        ShapeDescription::Triangle { vertices: [a, b, c], material } => {
+            Triangle::new(vector(*a), vector(*b), vector(*c), find_material(material)?).into()
+        }
+        ShapeDescription::Disc { center, normal, radius, material } => {
+            let (normal, radius) = (direction("normal", *normal)?, positive("radius", *radius)?);
+            Disc::new(vector(*center), normal, radius, find_material(material)?).into()
+        }
+        ShapeDescription::Cylinder { base_center, axis, radius, height, material } => {
+            let (axis, radius) = (direction("axis", *axis)?, positive("radius", *radius)?);
+            Cylinder::new(vector(*base_center), axis, radius, height.0, find_material(material)?).into()
+        }
+        ShapeDescription::Cone { base_center, axis, radius, height, material } => {
+            let (axis, radius) = (direction("axis", *axis)?, positive("radius", *radius)?);
+            Cone::new(vector(*base_center), axis, radius, height.0, find_material(material)?).into()
+        }
+        ShapeDescription::Mesh { path, material } => {
+            load_obj(base_directory.join(path), find_material(material)?)
+                .map_err(|error| format!("couldn't load mesh {}: {}", path.display(), error))?
+                .into()
+        }
+    };
+    Ok(shape)
+}
+
+// a vector that is normalized, one of length 0 would make the geometry NaN
+fn direction(name: &str, v: Vec3) -> Result<Vector3<f32>, String> {
+    let v = vector(v);
+    if v.magnitude2() > 0.0 { Ok(v) } else { Err(format!("the {} has a length of 0", name)) }
+}
+
+// a radius, which has to be positive
+fn positive(name: &str, x: Float) -> Result<f32, String> {
+    if x.0 > 0.0 { Ok(x.0) } else { Err(format!("the {} must be greater than 0, found {}", name, x.0)) }
+}
+
+// the directions and radii of a light, like the ones of shapes
+fn check_light(light: &LightDescription) -> Result<(), String> {
+    match light {
+        LightDescription::Directional { direction: light_direction, .. }
+        | LightDescription::Spot { direction: light_direction, .. } => direction("direction", *light_direction).map(|_| ()),
+        LightDescription::Area { shape: AreaLightShapeDescription::Sphere { radius, .. }, .. } => {
+            positive("radius", *radius).map(|_| ())
+        }
+        LightDescription::Point { .. } | LightDescription::Area { .. } => Ok(()),
+    }
+}
+
+fn shape_to_description(shape: &Shape, material: String, base_directory: &Path) -> Result<ShapeDescription, SceneFileError> {
+    let description = match shape {
+        Shape::Sphere(sphere) => ShapeDescription::Sphere {
+            center: floats(sphere.center()),
+            radius: Float(sphere.radius()),
+            material,
+            casts_shadow: sphere.casts_shadow(),
+        },
+        Shape::Plane(plane) => ShapeDescription::Plane {
+            point: floats(plane.point()),
+            normal: floats(plane.normal()),
+            material,
+        },
+        Shape::AxisAlignedBox(aab) => ShapeDescription::Box {
+            min: floats(aab.min()),
+            max: floats(aab.max()),
+            material,
+        },
+        Shape::Triangle(triangle) => ShapeDescription::Triangle {
+            vertices: triangle.vertices().map(floats),
+            material,
+        },
+        Shape::Disc(disc) => ShapeDescription::Disc {
+            center: floats(disc.center()),
+            normal: floats(disc.normal()),
+            radius: Float(disc.radius()),
+            material,
+        },
+        Shape::Cylinder(cylinder) => ShapeDescription::Cylinder {
+            base_center: floats(cylinder.base_center()),
+            axis: floats(cylinder.axis()),
+            radius: Float(cylinder.radius()),
+            height: Float(cylinder.height()),
+            material,
+        },
+        Shape::Cone(cone) => ShapeDescription::Cone {
+            base_center: floats(cone.base_center()),
+            axis: floats(cone.axis()),
+            radius: Float(cone.radius()),
+            height: Float(cone.height()),
+            material,
+        },
+        Shape::Mesh(mesh) => {
+            let source = mesh.source().ok_or_else(|| {
+                SceneFileError::Unsupported("a mesh that wasn't loaded from a file can't be saved".to_string())
+            })?;
+            ShapeDescription::Mesh { path: relative_path(source, base_directory), material }
+        }
+    };
+    Ok(description)
+}
+
+/// A light with the name it can be found by
+#[derive(Serialize, Deserialize)]
+struct NamedLightDescription {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    #[serde(flatten)]
+    light: LightDescription,
+}
+
+/// A shape with its name and its place in the scene graph
+#[derive(Serialize, Deserialize)]
+struct ShapeNodeDescription {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    #[serde(flatten)]
+    shape: ShapeDescription,
+    #[serde(default, skip_serializing_if = "TransformDescription::is_identity")]
+    transform: TransformDescription,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    parent: Option<NodeDescription>,
+}
+
+/// A node without a shape that moves the nodes under it together
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct GroupDescription {
+    #[serde(default, skip_serializing_if = "TransformDescription::is_identity")]
+    transform: TransformDescription,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    parent: Option<NodeDescription>,
+}
+
+/// Nodes refer to their parent by the index of its shape or group in the file
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum NodeDescription {
+    Shape(usize),
+    Group(usize),
+}
+
+/// The lights, groups and shapes of a scene in the order they are written to the file,
+/// parents and animations refer to them by their index in the file
+#[derive(Default)]
+struct FileOrder {
+    lights: Vec<LightId>,
+    groups: Vec<NodeId>,
+    shapes: Vec<ShapeId>,
+    // the node of each shape
+    shape_nodes: Vec<NodeId>,
+}
+
+impl FileOrder {
+    fn of_scene(scene: &Scene) -> FileOrder {
+        let shapes: Vec<ShapeId> = scene.shapes().map(|(id, _)| id).collect();
+        FileOrder {
+            lights: scene.lights().map(|(id, _)| id).collect(),
+            groups: scene.groups().collect(),
+            shape_nodes: shapes.iter().filter_map(|&id| scene.shape_node(id)).collect(),
+            shapes,
+        }
+    }
+
+    fn light(&self, index: usize) -> Result<LightId, String> {
+        self.lights.get(index).copied().ok_or_else(|| format!("there is no light with index {}", index))
+    }
+
+    fn group(&self, index: usize) -> Result<NodeId, String> {
+        self.groups.get(index).copied().ok_or_else(|| format!("there is no group with index {}", index))
+    }
+
+    fn shape(&self, index: usize) -> Result<ShapeId, String> {
+        self.shapes.get(index).copied().ok_or_else(|| format!("there is no shape with index {}", index))
+    }
+
+    fn node(&self, node: NodeDescription) -> Result<NodeId, String> {
+        match node {
+            NodeDescription::Shape(index) => self.shape(index).map(|_| self.shape_nodes[index]),
+            NodeDescription::Group(index) => self.group(index),
+        }
+    }
+
+    fn target(&self, target: TargetDescription) -> Result<Target, String> {
+        Ok(match target {
+            TargetDescription::Shape(index) => Target::Shape(self.shape(index)?),
+            TargetDescription::Group(index) => Target::Node(self.group(index)?),
+            TargetDescription::Light(index) => Target::Light(self.light(index)?),
+            TargetDescription::Camera => Target::Camera,
+        })
+    }
+
+    // the order is made from the scene that is saved, so every object of the scene is found
+    fn light_index(&self, id: LightId) -> usize {
+        self.lights.iter().position(|&light| light == id).unwrap_or_default()
+    }
+
+    fn shape_index(&self, id: ShapeId) -> usize {
+        self.shapes.iter().position(|&shape| shape == id).unwrap_or_default()
+    }
+
+    fn node_description(&self, node: NodeId) -> NodeDescription {
+        match self.shape_nodes.iter().position(|&shape_node| shape_node == node) {
+            Some(index) => NodeDescription::Shape(index),
+            None => NodeDescription::Group(self.groups.iter().position(|&group| group == node).unwrap_or_default()),
+        }
+    }
+
+    fn target_description(&self, target: Target) -> TargetDescription {
+        match target {
+            Target::Shape(id) => TargetDescription::Shape(self.shape_index(id)),
+            Target::Node(node) => match self.node_description(node) {
+                NodeDescription::Shape(index) => TargetDescription::Shape(index),
+                NodeDescription::Group(index) => TargetDescription::Group(index),
+            },
+            Target::Light(id) => TargetDescription::Light(self.light_index(id)),
+            Target::Camera => TargetDescription::Camera,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct TransformDescription {
+    translation: Vec3,
+    /// Euler angles around the x, y and z axes
+    rotation: Vec3,
+    scale: Vec3,
+}
+
+impl Default for TransformDescription {
+    fn default() -> TransformDescription {
+        Transform::identity().into()
+    }
+}
+
+impl TransformDescription {
+    fn is_identity(&self) -> bool {
+        Transform::from(*self).is_identity()
+    }
+}
+
+impl From<Transform> for TransformDescription {
+    fn from(transform: Transform) -> TransformDescription {
+        TransformDescription {
+            translation: floats(transform.translation),
+            rotation: angles_of_rotation(transform.rotation),
+            scale: floats(transform.scale),
+        }
+    }
+}
+
+impl From<TransformDescription> for Transform {
+    fn from(transform: TransformDescription) -> Transform {
+        Transform::from_translation(vector(transform.translation))
+            .with_rotation(rotation_from_angles(transform.rotation))
+            .with_scale(vector(transform.scale))
+    }
+}
+
+fn rotation_from_angles([x, y, z]: Vec3) -> Quaternion<f32> {
+    Quaternion::from(Euler::new(Deg(x.0), Deg(y.0), Deg(z.0)))
+}
+
+fn angles_of_rotation(rotation: Quaternion<f32>) -> Vec3 {
+    // the angles are computed from the quaternion, they are rounded like the camera target
+    let angles = Euler::from(rotation);
+    [angles.x, angles.y, angles.z].map(|angle| Float(round(Deg::from(angle).0)))
+}
+
+/// The path relative to `base_directory` if it is inside it, otherwise the absolute path
+fn relative_path(path: &Path, base_directory: &Path) -> PathBuf {
+    let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    let base_directory = if base_directory.as_os_str().is_empty() { Path::new(".") } else { base_directory };
+    match base_directory.canonicalize() {
+        Ok(base_directory) => path.strip_prefix(&base_directory).map(Path::to_path_buf).unwrap_or(path),
+        Err(_) => path,
+    }
+}
+
+/// Animations refer to shapes, groups and lights by their index in the file
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum TargetDescription {
+    Shape(usize),
+    Group(usize),
+    Light(usize),
+    Camera,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum AnimationDescription {
+    Position {
+        target: TargetDescription,
+        keyframes: Vec<KeyframeDescription<Vec3>>,
+        #[serde(default, skip_serializing_if = "is_false")]
+        repeat: bool,
+    },
+    /// The keyframes are Euler angles around the x, y and z axes
+    Rotation {
+        target: TargetDescription,
+        keyframes: Vec<KeyframeDescription<Vec3>>,
+        #[serde(default, skip_serializing_if = "is_false")]
+        repeat: bool,
+    },
+    Scale {
+        target: TargetDescription,
+        keyframes: Vec<KeyframeDescription<Float>>,
+        #[serde(default, skip_serializing_if = "is_false")]
+        repeat: bool,
+    },
+    Color {
+        target: TargetDescription,
+        keyframes: Vec<KeyframeDescription<Vec3>>,
+        #[serde(default, skip_serializing_if = "is_false")]
+        repeat: bool,
+    },
+    Orbit {
+        target: TargetDescription,
+        center: Vec3,
+        radius: Float,
+        /// In radians per second
+        speed: Float,
+    },
+    Path {
+        target: TargetDescription,
+        points: Vec<Vec3>,
+        duration: Float,
+        #[serde(default, skip_serializing_if = "is_false")]
+        repeat: bool,
+    },
+    Attach { shape: usize, light: usize },
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct KeyframeDescription<T> {
+    time: Float,
+    value: T,
+    #[serde(default, skip_serializing_if = "EasingDescription::is_linear")]
+    easing: EasingDescription,
+}
+
+#[derive(Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum EasingDescription {
+    Step,
+    #[default]
+    Linear,
+    EaseIn,
+    EaseOut,
+    EaseInOut,
+    CubicBezier([Float; 4]),
+}
+
+impl EasingDescription {
+    fn is_linear(&self) -> bool {
+        matches!(self, EasingDescription::Linear)
+    }
+}
+
+impl From<Easing> for EasingDescription {
+    fn from(easing: Easing) -> EasingDescription {
+        match easing {
+            Easing::Step => EasingDescription::Step,
+            Easing::Linear => EasingDescription::Linear,
+            Easing::EaseIn => EasingDescription::EaseIn,
+            Easing::EaseOut => EasingDescription::EaseOut,
+            Easing::EaseInOut => EasingDescription::EaseInOut,
+            Easing::CubicBezier(x1, y1, x2, y2) => EasingDescription::CubicBezier([x1, y1, x2, y2].map(Float)),
+        }
+    }
+}
+
+impl From<EasingDescription> for Easing {
+    fn from(easing: EasingDescription) -> Easing {
+        match easing {
+            EasingDescription::Step => Easing::Step,
+            EasingDescription::Linear => Easing::Linear,
+            EasingDescription::EaseIn => Easing::EaseIn,
+            EasingDescription::EaseOut => Easing::EaseOut,
+            EasingDescription::EaseInOut => Easing::EaseInOut,
+            EasingDescription::CubicBezier([x1, y1, x2, y2]) => Easing::CubicBezier(x1.0, y1.0, x2.0, y2.0),
+        }
+    }
+}
+
+fn animation_from_description(animation: &AnimationDescription, order: &FileOrder) -> Result<Animation, String> {
+    let find_target = |target: TargetDescription| order.target(target);
+
+    let animation = match animation {
+        AnimationDescription::Position { target, keyframes, repeat } => Animation::Position {
+            target: find_target(*target)?,
+            track: track(keyframes, *repeat, |value| vector(*value))?,
+        },
+        AnimationDescription::Rotation { target, keyframes, repeat } => Animation::Rotation {
+            target: find_target(*target)?,
+            track: track(keyframes, *repeat, |&angles| rotation_from_angles(angles))?,
+        },
+        AnimationDescription::Scale { target, keyframes, repeat } => Animation::Scale {
+            target: find_target(*target)?,
+            track: track(keyframes, *repeat, |value| value.0)?,
+        },
+        AnimationDescription::Color { target, keyframes, repeat } => Animation::Color {
+            target: find_target(*target)?,
+            track: track(keyframes, *repeat, |value| vector(*value))?,
+        },
+        AnimationDescription::Orbit { target, center, radius, speed } => Animation::Orbit {
+            target: find_target(*target)?,
+            center: vector(*center),
+            radius: radius.0,
+            speed: speed.0,
+        },
+        AnimationDescription::Path { target, points, duration, repeat } => {
+            if points.is_empty() {
+                return Err("a path needs at least one point".to_string());
+            }
+            Animation::Path {
+                target: find_target(*target)?,
+                points: points.iter().map(|&point| vector(point)).collect(),
+                duration: duration.0,
+                repeat: *repeat,
+            }
+        }
+        AnimationDescription::Attach { shape: attached_shape, light: attached_light } => Animation::Attach {
+            shape: order.shape(*attached_shape)?,
+            light: order.light(*attached_light)?,
+        },
+    };
+    Ok(animation)
+}
+
+fn track<V, T: Interpolate>(
+    keyframes: &[KeyframeDescription<V>],
+    repeat: bool,
+    value: impl Fn(&V) -> T,
+) -> Result<Track<T>, String> {
+    if keyframes.is_empty() {
+        return Err("an animation needs at least one keyframe".to_string());
+    }
+    let keyframes = keyframes.iter()
+        .map(|keyframe| Keyframe::new(keyframe.time.0, value(&keyframe.value)).with_easing(keyframe.easing.into()))
+        .collect();
+    Ok(Track::new(keyframes, repeat))
+}
+
+fn animation_to_description(animation: &Animation, order: &FileOrder) -> AnimationDescription {
+    fn keyframes<T: Interpolate, V>(track: &Track<T>, value: impl Fn(&T) -> V) -> Vec<KeyframeDescription<V>> {
+        track.keyframes().iter()
+            .map(|keyframe| KeyframeDescription {
+                time: Float(keyframe.time),
+                value: value(&keyframe.value),
+                easing: keyframe.easing.into(),
+            })
+            .collect()
+    }
+    let target_description = |target: Target| order.target_description(target);
+
+    match animation {
+        Animation::Position { target, track } => AnimationDescription::Position {
+            target: target_description(*target),
+            keyframes: keyframes(track, |&value| floats(value)),
+            repeat: track.repeats(),
+        },
+        Animation::Rotation { target, track } => AnimationDescription::Rotation {
+            target: target_description(*target),
+            keyframes: keyframes(track, |&rotation| angles_of_rotation(rotation)),
+            repeat: track.repeats(),
+        },
+        Animation::Scale { target, track } => AnimationDescription::Scale {
+            target: target_description(*target),
+            keyframes: keyframes(track, |&value| Float(value)),
+            repeat: track.repeats(),
+        },
+        Animation::Color { target, track } => AnimationDescription::Color {
+            target: target_description(*target),
+            keyframes: keyframes(track, |&value| floats(value)),
+            repeat: track.repeats(),
+        },
+        Animation::Orbit { target, center, radius, speed } => AnimationDescription::Orbit {
+            target: target_description(*target),
+            center: floats(*center),
+            radius: Float(*radius),
+            speed: Float(*speed),
+        },
+        Animation::Path { target, points, duration, repeat } => AnimationDescription::Path {
+            target: target_description(*target),
+            points: points.iter().map(|&point| floats(point)).collect(),
+            duration: Float(*duration),
+            repeat: *repeat,
+        },
+        Animation::Attach { shape, light } => AnimationDescription::Attach {
+            shape: order.shape_index(*shape),
+            light: order.light_index(*light),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCENE: &str = r#"
+ambient = [0.2, 0.2, 0.2]
+
+[camera]
+position = [0, 1, 5]
+target = [0, 0, 0]
+fov = 60
+
+[materials.red]
+albedo = [0.9, 0.1, 0.1]
+
+[materials.mirror]
+reflectivity = 0.8
+
+[[lights]]
+name = "lamp"
+type = "point"
+position = [0, 4, 2]
+intensity = 2
+attenuation = "linear"
+
+[[lights]]
+type = "area"
+shape = { type = "sphere", center = [1, 5, 0], radius = 0.5 }
+samples_per_axis = 2
+
+[[shapes]]
+name = "marker"
+type = "sphere"
+center = [0, 4, 2]
+radius = 0.1
+material = "mirror"
+casts_shadow = false
+
+[[shapes]]
+type = "box"
+min = [-1, -1, -1]
+max = [1, 0, 1]
+material = "red"
+
+[[animations]]
+type = "orbit"
+target = { light = 0 }
+center = [0, 4, 0]
+radius = 2
+speed = 0.5
+
+[[animations]]
+type = "attach"
+shape = 0
+light = 0
+
+[[animations]]
+type = "rotation"
+target = { shape = 1 }
+repeat = true
+keyframes = [
+    { time = 0, value = [0, 0, 0] },
+    { time = 2, value = [0, 45, 0], easing = "ease_in_out" },
+    { time = 4, value = [0, 0, 0], easing = { cubic_bezier = [0.1, 0.7, 1, 0.1] } },
+]
+
+[[animations]]
+type = "path"
+target = "camera"
+points = [[0, 1, 5], [2, 1, 5]]
+duration = 3
+"#;
+
+    fn parse(source: &str) -> Result<Scene, SceneFileError> {
+        parse_scene(source, Path::new(""))
+    }
+
+    fn error_location(source: &str) -> (usize, usize, String) {
+        match parse(source) {
+            Err(SceneFileError::Parse { line, column, message }) => (line, column, message),
+            Err(error) => panic!("unexpected error {}", error),
+            Ok(_) => panic!("the scene was parsed without an error"),
+        }
+    }
+
+    #[test]
+    fn parses_scene() {
+        let scene = parse(SCENE).unwrap();
+        assert_eq!(scene.ambient_light_color(), Vector3::new(0.2, 0.2, 0.2));
+        assert_eq!(scene.camera().position(), Vector3::new(0.0, 1.0, 5.0));
+        assert_eq!(scene.lights().count(), 2);
+        assert_eq!(scene.find_light("lamp"), Some(LightId(0)));
+        assert_eq!(scene.shapes().count(), 2);
+        let marker = scene.find_shape("marker").unwrap();
+        assert!(!scene.shape(marker).unwrap().casts_shadow());
+
+        let red = scene.find_material("red").unwrap();
+        assert_eq!(scene.shape(ShapeId(1)).unwrap().material(), red);
+        assert_eq!(scene.material(red).albedo, Vector3::new(0.9, 0.1, 0.1));
+        let mirror = scene.material(scene.find_material("mirror").unwrap());
+        assert_eq!(mirror.reflectivity, 0.8);
+        assert_eq!(mirror.albedo, Material::default().albedo);
+
+        let animations = scene.animations();
+        assert_eq!(animations.len(), 4);
+        assert_eq!(animations[0], Animation::Orbit {
+            target: Target::Light(LightId(0)),
+            center: Vector3::new(0.0, 4.0, 0.0),
+            radius: 2.0,
+            speed: 0.5,
+        });
+        assert_eq!(animations[1], Animation::Attach { shape: ShapeId(0), light: LightId(0) });
+        match &animations[2] {
+            Animation::Rotation { target: Target::Shape(ShapeId(1)), track } => {
+                assert!(track.repeats());
+                let easings: Vec<_> = track.keyframes().iter().map(|keyframe| keyframe.easing).collect();
+                assert_eq!(easings, [Easing::Linear, Easing::EaseInOut, Easing::CubicBezier(0.1, 0.7, 1.0, 0.1)]);
+            }
+            animation => panic!("unexpected animation {:?}", animation),
+        }
+        assert!(matches!(&animations[3], Animation::Path { target: Target::Camera, points, .. } if points.len() == 2));
+    }
+
+    #[test]
+    fn example_scene_loads() {
+        let scene = load_scene(concat!(env!("CARGO_MANIFEST_DIR"), "/scenes/example.toml")).unwrap();
+        assert!(scene.shapes().next().is_some());
+    }
+
+    #[test]
+    fn saved_scene_loads_the_same() {
+        let scene = parse(SCENE).unwrap();
+        let saved = scene_to_string(&scene, Path::new("")).unwrap();
+        let reloaded = parse(&saved).unwrap();
+
+        assert_eq!(scene_to_string(&reloaded, Path::new("")).unwrap(), saved);
+        assert!(saved.contains("ambient = [0.2, 0.2, 0.2]"), "{}", saved);
+        let (camera, reloaded_camera) = (scene.camera(), reloaded.camera());
+        assert!((camera.forward() - reloaded_camera.forward()).x.abs() < 1e-6);
+        assert!((Deg::from(reloaded_camera.fov()).0 - 60.0).abs() < 1e-4);
+    }
+
+    const GROUPS: &str = r#"
+[camera]
+position = [0, 0, 5]
+target = [0, 0, 0]
+
+[materials.white]
+
+[[groups]]
+transform = { translation = [0, 0, -10], rotation = [0, 90, 0] }
+
+[[groups]]
+transform = { scale = [2, 2, 2] }
+parent = { group = 0 }
+
+[[shapes]]
+type = "sphere"
+center = [0, 0, 0]
+radius = 1
+material = "white"
+parent = { group = 1 }
+
+[[shapes]]
+type = "sphere"
+center = [0, 0, 0]
+radius = 0.5
+material = "white"
+transform = { translation = [3, 0, 0] }
+parent = { shape = 0 }
+
+[[animations]]
+type = "position"
+target = { group = 1 }
+keyframes = [{ time = 0, value = [0, 1, 0] }]
+"#;
+
+    #[test]
+    fn groups_and_transforms_are_loaded_and_saved() {
+        let scene = parse(GROUPS).unwrap();
+        let moon = scene.shape_node(ShapeId(1)).unwrap();
+        let planet = scene.graph().parent(moon).unwrap();
+        assert_eq!(scene.node_shape(planet), Some(ShapeId(0)));
+        let group = scene.graph().parent(planet).unwrap();
+        assert_eq!(scene.transform(group).unwrap().scale, Vector3::new(2.0, 2.0, 2.0));
+        assert!(matches!(scene.animations()[0], Animation::Position { target: Target::Node(node), .. } if node == group));
+        // the moon is 3 units from the planet, scaled by 2 and turned to face the z axis
+        let moon_position = scene.graph().world_matrix(moon).w.truncate();
+        assert!(cgmath::InnerSpace::magnitude(moon_position - Vector3::new(0.0, 0.0, -16.0)) < 1e-4, "{:?}", moon_position);
+
+        let saved = scene_to_string(&scene, Path::new("")).unwrap();
+        assert_eq!(scene_to_string(&parse(&saved).unwrap(), Path::new("")).unwrap(), saved);
+        assert!(saved.contains("[groups.parent]\ngroup = 0"), "{}", saved);
+    }
+
+    #[test]
+    fn parents_must_not_make_cycles() {
+        let source = GROUPS.replace("transform = { translation = [0, 0, -10], rotation = [0, 90, 0] }", "parent = { shape = 1 }");
+        // the parents are set in the order of the file, the moon closes the cycle
+        let (line, _, message) = error_location(&source);
+        assert_eq!(line, 22);
+        assert!(message.contains("cycle"), "{}", message);
+    }
+
+    #[test]
+    fn removed_objects_are_not_saved() {
+        let mut scene = parse(SCENE).unwrap();
+        scene.remove_light(LightId(0));
+        scene.remove_shape(ShapeId(0));
+        // the rotation of the box refers to it by its new index
+        let saved = scene_to_string(&scene, Path::new("")).unwrap();
+        let reloaded = parse(&saved).unwrap();
+        assert_eq!((reloaded.lights().count(), reloaded.shapes().count()), (1, 1));
+        assert_eq!(reloaded.animations().len(), 2);
+        assert!(saved.contains("[animations.target]\nshape = 0"), "{}", saved);
+        assert!(!saved.contains("marker") && !saved.contains("lamp"), "{}", saved);
+    }
+
+    #[test]
+    fn unnamed_materials_get_names_when_saved() {
+        let mut scene = Scene::new(Camera::from_position_and_target(Vector3::unit_z(), Vector3::new(0.0, 0.0, 0.0)));
+        let material = scene.add_material(Material::diffuse(Vector3::new(0.5, 0.5, 0.5)));
+        scene.add_shape(Sphere::new(Vector3::new(0.0, 0.0, 0.0), 1.0, material));
+
+        let reloaded = parse(&scene_to_string(&scene, Path::new("")).unwrap()).unwrap();
+        assert_eq!(reloaded.material_name(reloaded.shape(ShapeId(0)).unwrap().material()), Some("material_0"));
+    }
+
+    #[test]
+    fn syntax_errors_have_locations() {
+        let (line, column, _) = error_location("[camera]\nposition = [0, 0, 0] 1\n");
+        assert_eq!((line, column), (2, 22));
+    }
+
+    #[test]
+    fn unknown_keys_are_errors() {
+        let source = "[camera]\nposition = [0, 0, 0]\ntarget = [0, 0, -1]\nzoom = 2\n";
+        let (line, column, message) = error_location(source);
+        assert_eq!((line, column), (4, 1));
+        assert!(message.contains("zoom"), "{}", message);
+    }
+
+    #[test]
+    fn unknown_materials_are_located_at_their_shape() {
+        let source = SCENE.replace("material = \"red\"", "material = \"blue\"");
+        let (line, _, message) = error_location(&source);
+        // the error points at the [[shapes]] header of the box
+        let shape_line = source.lines().position(|line| line.contains("type = \"box\"")).unwrap();
+        assert_eq!(line, shape_line);
+        assert!(message.contains("blue"), "{}", message);
+    }
+
+    #[test]
+    fn degenerate_geometry_is_located() {
+        let sphere_line = SCENE.lines().position(|line| line.contains("name = \"marker\"")).unwrap();
+        let (line, _, message) = error_location(&SCENE.replace("radius = 0.1", "radius = 0"));
+        assert_eq!(line, sphere_line);
+        assert!(message.contains("radius must be greater than 0"), "{}", message);
+
+        let plane = "\n[[shapes]]\ntype = \"plane\"\npoint = [0, 0, 0]\nnormal = [0, 0, 0]\nmaterial = \"red\"\n";
+        let (_, _, message) = error_location(&format!("{}{}", SCENE, plane));
+        assert!(message.contains("normal has a length of 0"), "{}", message);
+        let light = "\n[[lights]]\ntype = \"directional\"\ndirection = [0, 0, 0]\n";
+        let (_, _, message) = error_location(&format!("{}{}", SCENE, light));
+        assert!(message.contains("direction has a length of 0"), "{}", message);
+    }
+
+    #[test]
+    fn materials_keep_the_order_of_the_file() {
+        let names: Vec<String> = (0..12).map(|i| format!("material_{}", i)).collect();
+        let materials: String = names.iter().map(|name| format!("[materials.{}]\n", name)).collect();
+        let source = format!("[camera]\nposition = [0, 0, 5]\ntarget = [0, 0, 0]\n{}", materials);
+        let scene = parse(&source).unwrap();
+        let order = |scene: &Scene| scene.materials()
+            .map(|(id, _)| scene.material_name(id).unwrap().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(order(&scene), names);
+        assert_eq!(order(&parse(&scene_to_string(&scene, Path::new("")).unwrap()).unwrap()), names);
+    }
+
+    #[test]
+    fn animations_must_refer_to_existing_lights() {
+        let source = SCENE.replace("light = 0", "light = 7");
+        let (_, _, message) = error_location(&source);
+        assert!(message.contains("no light with index 7"), "{}", message);
+    }
+
+    #[test]
+    fn animations_need_keyframes() {
+        let source = format!("{}\n[[animations]]\ntype = \"scale\"\ntarget = {{ shape = 1 }}\nkeyframes = []\n", SCENE);
+        let (_, _, message) = error_location(&source);
+        assert!(message.contains("at least one keyframe"), "{}", message);
+    }
+
+    #[test]
+    fn mesh_paths_are_relative_to_the_scene_file() {
+        let directory = std::env::temp_dir().join(format!("scene_file_test_{}", std::process::id()));
+        std::fs::create_dir_all(directory.join("models")).unwrap();
+        std::fs::write(directory.join("models/triangle.obj"), "v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\n").unwrap();
+        let source = "[camera]\nposition = [0, 0, 5]\ntarget = [0, 0, 0]\n[materials.white]\n\
+                      [[shapes]]\ntype = \"mesh\"\npath = \"models/triangle.obj\"\nmaterial = \"white\"\n";
+        std::fs::write(directory.join("scene.toml"), source).unwrap();
+
+        let scene = load_scene(directory.join("scene.toml")).unwrap();
+        save_scene(&scene, directory.join("saved.toml")).unwrap();
+        let saved = std::fs::read_to_string(directory.join("saved.toml")).unwrap();
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        assert!(matches!(scene.shape(ShapeId(0)), Some(Shape::Mesh(mesh)) if mesh.faces().len() == 1));
+        assert!(saved.contains("path = \"models/triangle.obj\""), "{}", saved);
+    }
+}
@@ -0,0 +1,231 @@
+//! Generation of random scenes of spheres. The same seed and settings always give the same scene,
+//! as long as the version of `rand` doesn't change.
+
+use crate::core::animation::{Animation, Target};
+use crate::core::camera::Camera;
+use crate::core::light::Light;
+use crate::core::material::Material;
+use crate::core::scene::Scene;
+use crate::core::shapes::{Plane, Sphere};
+use cgmath::{ElementWise, InnerSpace, Vector3, Zero};
+use rand_chacha::ChaCha8Rng;
+use rand::{Rng, SeedableRng};
+
+/// The colors the spheres are chosen from
+#[derive(Clone, Debug, PartialEq)]
+pub enum Palette {
+    /// Any color
+    Random,
+    /// Light, desaturated colors
+    Pastel,
+    Grayscale,
+    /// One of the given colors
+    Colors(Vec<Vector3<f32>>),
+}
+
+impl Palette {
+    fn color(&self, rng: &mut impl Rng) -> Vector3<f32> {
+        match self {
+            Palette::Random => random_vector(rng),
+            Palette::Pastel => random_vector(rng) * 0.4 + Vector3::new(0.6, 0.6, 0.6),
+            Palette::Grayscale => {
+                let brightness = rng.random::<f32>();
+                Vector3::new(brightness, brightness, brightness)
+            }
+            Palette::Colors(colors) if !colors.is_empty() => colors[rng.gen_range(0..colors.len())],
+            Palette::Colors(_) => Vector3::new(1.0, 1.0, 1.0),
+        }
+    }
+}
+
+// a vector with components between 0 and 1
+fn random_vector(rng: &mut impl Rng) -> Vector3<f32> {
+    Vector3::new(rng.random(), rng.random(), rng.random())
+}
+
+#[derive(Clone, Debug)]
+pub struct GeneratorSettings {
+    pub sphere_count: usize,
+    /// The corners of the box the centers of the spheres are in
+    pub position_range: (Vector3<f32>, Vector3<f32>),
+    pub radius_range: (f32, f32),
+    pub palette: Palette,
+    /// Spheres are placed so they don't intersect each other or contain the camera.
+    /// If no such place is found for a sphere after a number of tries, it is left out.
+    pub non_overlapping: bool,
+}
+
+impl Default for GeneratorSettings {
+    fn default() -> GeneratorSettings {
+        GeneratorSettings {
+            sphere_count: 10,
+            position_range: (Vector3::new(-5.0, -5.0, -5.0), Vector3::new(5.0, 5.0, 5.0)),
+            radius_range: (0.75, 1.5),
+            palette: Palette::Random,
+            non_overlapping: true,
+        }
+    }
+}
+
+// how often a new place is tried for a sphere that overlaps another one
+const MAX_PLACEMENT_ATTEMPTS: usize = 100;
+// spheres keep at least this distance from the camera
+const CAMERA_CLEARANCE: f32 = 0.5;
+
+/// A scene of randomly placed, sized and colored spheres above a floor, lit by a light orbiting the scene.
+/// The first sphere is a mirror and the second one is made of glass, the others are glossy.
+pub fn generate_scene(seed: u64, settings: &GeneratorSettings) -> Scene {
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    let camera_position = Vector3::zero();
+    let camera = Camera::from_position_and_target(camera_position, -Vector3::unit_z());
+    let mut scene = Scene::new(camera);
+
+    let light_material = scene.add_material(Material::emissive(Vector3::new(1., 1., 1.)));
+    // the sphere is placed around the origin of its node, the attach animation moves the node to the light
+    let mut light_sphere = Sphere::new(Vector3::new(0.0, 0.0, 0.0), 0.1, light_material);
+    light_sphere.set_casts_shadow(false);
+    let light_sphere = scene.add_shape(light_sphere);
+    let light = scene.add_light(Light::from_position(Vector3::new(0.0, 1.0, -5.0)));
+    scene.add_animation(Animation::Orbit {
+        target: Target::Light(light),
+        center: Vector3::new(0.0, 1.0, -6.0),
+        radius: 3.0,
+        speed: 1.0,
+    });
+    scene.add_animation(Animation::Attach { shape: light_sphere, light });
+    let (min, max) = settings.position_range;
+    let floor_material = scene.add_material(Material::diffuse(Vector3::new(0.5, 0.5, 0.5)));
+    let floor_height = min.y.min(max.y) - settings.radius_range.1.max(settings.radius_range.0);
+    scene.add_shape(Plane::new(Vector3::new(0.0, floor_height, 0.0), Vector3::unit_y(), floor_material));
+
+    let mut placed: Vec<(Vector3<f32>, f32)> = Vec::new();
+    for _ in 0..settings.sphere_count {
+        let (center, radius) = if let Some(sphere) = place_sphere(&mut rng, settings, &placed, camera_position) {
+            sphere
+        } else {
+            continue;
+        };
+        placed.push((center, radius));
+
+        let glossy = Material::glossy(settings.palette.color(&mut rng), Vector3::new(0.5, 0.5, 0.5), 32.);
+        // the first placed sphere is a mirror and the second one is glass, the rest are glossy.
+        // They are counted by the placed spheres, as spheres that found no place are left out.
+        let material = match placed.len() {
+            1 => Material { reflectivity: 0.8, ..glossy },
+            2 => Material { transparency: 0.9, refractive_index: 1.5, ..glossy },
+            _ => glossy,
+        };
+        let material = scene.add_material(material);
+        scene.add_shape(Sphere::new(center, radius, material));
+    }
+
+    scene
+}
+
+/// A random center and radius for a sphere, `None` if no place that satisfies the settings was found
+fn place_sphere(
+    rng: &mut impl Rng,
+    settings: &GeneratorSettings,
+    placed: &[(Vector3<f32>, f32)],
+    camera_position: Vector3<f32>,
+) -> Option<(Vector3<f32>, f32)> {
+    let (min, max) = settings.position_range;
+    let (min_radius, max_radius) = settings.radius_range;
+    for _ in 0..MAX_PLACEMENT_ATTEMPTS {
+        let center = min + (max - min).mul_element_wise(random_vector(rng));
+        let radius = min_radius + rng.random::<f32>() * (max_radius - min_radius);
+
+        if !settings.non_overlapping {
+            return Some((center, radius));
+        }
+        let overlaps_sphere = placed.iter()
+            .any(|&(other_center, other_radius)| (center - other_center).magnitude() < radius + other_radius);
+        let contains_camera = (center - camera_position).magnitude() < radius + CAMERA_CLEARANCE;
+        if !overlaps_sphere && !contains_camera {
+            return Some((center, radius));
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::shapes::{Hittable, Shape, ShapeId};
+
+    fn spheres(scene: &Scene) -> Vec<(Vector3<f32>, f32)> {
+        scene.shapes()
+            .skip(2) // the light and the floor
+            .map(|(_, shape)| match shape {
+                Shape::Sphere(sphere) => (sphere.center(), sphere.radius()),
+                _ => panic!("only spheres are generated"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn same_seed_gives_same_scene() {
+        let settings = GeneratorSettings::default();
+        assert_eq!(spheres(&generate_scene(42, &settings)), spheres(&generate_scene(42, &settings)));
+        assert_ne!(spheres(&generate_scene(42, &settings)), spheres(&generate_scene(43, &settings)));
+    }
+
+    #[test]
+    fn seeds_give_the_same_scene_on_every_build() {
+        // the random numbers of ChaCha8 don't change with the version of rand, so printed seeds stay valid
+        let (center, radius) = spheres(&generate_scene(42, &GeneratorSettings::default()))[0];
+        assert!((center - Vector3::new(-2.759_192_5, 1.818_961_6, -3.536_138)).magnitude() < 1e-5, "center {:?}", center);
+        assert!((radius - 1.462_706_6).abs() < 1e-5, "radius {}", radius);
+    }
+
+    #[test]
+    fn spheres_follow_the_settings() {
+        let settings = GeneratorSettings {
+            sphere_count: 30,
+            position_range: (Vector3::new(-8.0, -2.0, -8.0), Vector3::new(8.0, 2.0, 8.0)),
+            radius_range: (0.5, 1.0),
+            palette: Palette::Colors(vec![Vector3::new(1.0, 0.0, 0.0)]),
+            non_overlapping: true,
+        };
+        let scene = generate_scene(7, &settings);
+        let spheres = spheres(&scene);
+
+        assert!(spheres.len() > 20, "only {} spheres were placed", spheres.len());
+        for (i, &(center, radius)) in spheres.iter().enumerate() {
+            assert!((0.5..=1.0).contains(&radius));
+            assert!(center.y.abs() <= 2.0);
+            assert!(center.magnitude() >= radius + CAMERA_CLEARANCE);
+            for &(other_center, other_radius) in &spheres[i + 1..] {
+                assert!((center - other_center).magnitude() >= radius + other_radius);
+            }
+        }
+        let sphere_material = scene.shape(ShapeId(2)).unwrap().material();
+        assert_eq!(scene.material(sphere_material).albedo, Vector3::new(1.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn first_placed_spheres_are_mirror_and_glass() {
+        // spheres that reach around the camera don't fit into most of the box, so many spheres find no place
+        let settings = GeneratorSettings {
+            sphere_count: 4,
+            position_range: (Vector3::new(-1.0, -1.0, -1.0), Vector3::new(1.0, 1.0, 1.0)),
+            radius_range: (1.0, 1.0),
+            non_overlapping: true,
+            ..GeneratorSettings::default()
+        };
+        let mut failed_placements = 0;
+        for seed in 0..40 {
+            let scene = generate_scene(seed, &settings);
+            let materials: Vec<Material> = scene.shapes()
+                .skip(2)
+                .map(|(_, shape)| *scene.material(shape.material()))
+                .collect();
+            failed_placements += settings.sphere_count - materials.len();
+            for (i, material) in materials.iter().enumerate() {
+                assert_eq!(material.reflectivity > 0.0, i == 0, "seed {}", seed);
+                assert_eq!(material.transparency > 0.0, i == 1, "seed {}", seed);
+            }
+        }
+        assert!(failed_placements > 0);
+    }
+}
@@ -0,0 +1,207 @@
+use crate::core::transform::Transform;
+use cgmath::{Matrix4, SquareMatrix};
+
+/// A handle of a node of a scene graph
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct NodeId(pub(crate) usize);
+
+/// A hierarchy of transforms. The transform of a node is relative to its parent,
+/// so moving a node moves all of its descendants with it, e.g. a moon orbiting a planet that moves.
+/// The handles of removed nodes aren't given to new nodes, the methods panic when they get one.
+#[derive(Clone, Default)]
+pub struct SceneGraph {
+    // None where a node was removed
+    nodes: Vec<Option<Node>>,
+}
+
+#[derive(Clone)]
+struct Node {
+    transform: Transform,
+    parent: Option<NodeId>,
+    children: Vec<NodeId>,
+    // the transform into world space, kept up to date whenever a transform or parent changes
+    world_matrix: Matrix4<f32>,
+}
+
+impl SceneGraph {
+    pub fn new() -> SceneGraph {
+        SceneGraph::default()
+    }
+
+    /// Adds a node without a parent
+    pub fn add_node(&mut self, transform: Transform) -> NodeId {
+        self.nodes.push(Some(Node { transform, parent: None, children: Vec::new(), world_matrix: transform.matrix() }));
+        NodeId(self.nodes.len() - 1)
+    }
+
+    /// Removes the node, its children are moved under its parent keeping their transforms relative to the parent.
+    /// Returns the former descendants of the node, whose world matrices changed with it.
+    pub fn remove_node(&mut self, node: NodeId) -> Vec<NodeId> {
+        let parent = self.parent(node);
+        let children = self.children(node).to_vec();
+        let mut moved = Vec::new();
+        for child in children {
+            moved.extend(self.set_parent(child, parent).unwrap_or_default());
+        }
+        if let Some(parent) = parent {
+            self.node_mut(parent).children.retain(|&child| child != node);
+        }
+        self.nodes[node.0] = None;
+        moved
+    }
+
+    /// Puts a removed node back with the same handle, without a parent and children.
+    /// Returns false if the handle is used by a node or wasn't given by this graph.
+    pub fn restore_node(&mut self, node: NodeId, transform: Transform) -> bool {
+        match self.nodes.get_mut(node.0) {
+            Some(slot @ None) => {
+                *slot = Some(Node { transform, parent: None, children: Vec::new(), world_matrix: transform.matrix() });
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub fn contains(&self, node: NodeId) -> bool {
+        self.nodes.get(node.0).is_some_and(Option::is_some)
+    }
+
+    /// The nodes in the order they were added
+    pub fn nodes(&self) -> impl Iterator<Item=NodeId> + '_ {
+        self.nodes.iter().enumerate()
+            .filter(|(_, node)| node.is_some())
+            .map(|(index, _)| NodeId(index))
+    }
+
+    pub fn transform(&self, node: NodeId) -> Transform {
+        self.node(node).transform
+    }
+
+    /// Changes the transform of the node relative to its parent, and returns the node and its descendants,
+    /// whose world matrices changed with it
+    pub fn set_transform(&mut self, node: NodeId, transform: Transform) -> Vec<NodeId> {
+        self.node_mut(node).transform = transform;
+        self.update_world_matrices(node)
+    }
+
+    pub fn parent(&self, node: NodeId) -> Option<NodeId> {
+        self.node(node).parent
+    }
+
+    pub fn children(&self, node: NodeId) -> &[NodeId] {
+        &self.node(node).children
+    }
+
+    /// Moves the node under a new parent, keeping its transform relative to the parent. Returns the node
+    /// and its descendants, whose world matrices changed with it, or `None` without changing anything if
+    /// the parent is the node itself or one of its descendants.
+    pub fn set_parent(&mut self, node: NodeId, parent: Option<NodeId>) -> Option<Vec<NodeId>> {
+        let mut ancestor = parent;
+        while let Some(current) = ancestor {
+            if current == node {
+                return None;
+            }
+            ancestor = self.parent(current);
+        }
+
+        if let Some(old_parent) = self.parent(node) {
+            self.node_mut(old_parent).children.retain(|&child| child != node);
+        }
+        if let Some(parent) = parent {
+            self.node_mut(parent).children.push(node);
+        }
+        self.node_mut(node).parent = parent;
+        Some(self.update_world_matrices(node))
+    }
+
+    /// The transform from the space of the node into world space, including the transforms of its ancestors
+    pub fn world_matrix(&self, node: NodeId) -> Matrix4<f32> {
+        self.node(node).world_matrix
+    }
+
+    fn node(&self, node: NodeId) -> &Node {
+        self.nodes[node.0].as_ref().expect("the node was removed")
+    }
+
+    fn node_mut(&mut self, node: NodeId) -> &mut Node {
+        self.nodes[node.0].as_mut().expect("the node was removed")
+    }
+
+    // updates the node and its descendants and returns them
+    fn update_world_matrices(&mut self, node: NodeId) -> Vec<NodeId> {
+        let mut updated = vec![node];
+        let mut index = 0;
+        while let Some(&current) = updated.get(index) {
+            let parent_matrix = self.parent(current).map_or(Matrix4::identity(), |parent| self.world_matrix(parent));
+            let current_node = self.node_mut(current);
+            current_node.world_matrix = parent_matrix * current_node.transform.matrix();
+            updated.extend_from_slice(&current_node.children);
+            index += 1;
+        }
+        updated
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::{Deg, EuclideanSpace, InnerSpace, Point3, Quaternion, Rotation3, Transform as _, Vector3};
+
+    fn world_position(graph: &SceneGraph, node: NodeId) -> Vector3<f32> {
+        graph.world_matrix(node).transform_point(Point3::origin()).to_vec()
+    }
+
+    fn assert_vector_eq(actual: Vector3<f32>, expected: Vector3<f32>) {
+        assert!((actual - expected).magnitude() < 1e-4, "expected {:?}, got {:?}", expected, actual);
+    }
+
+    #[test]
+    fn children_move_with_their_parents() {
+        let mut graph = SceneGraph::new();
+        let planet = graph.add_node(Transform::from_translation(Vector3::new(10.0, 0.0, 0.0)));
+        let moon = graph.add_node(Transform::from_translation(Vector3::new(2.0, 0.0, 0.0)));
+        assert_eq!(graph.set_parent(moon, Some(planet)), Some(vec![moon]));
+        assert_vector_eq(world_position(&graph, moon), Vector3::new(12.0, 0.0, 0.0));
+
+        // turning the planet carries the moon around it
+        let turned = Transform::from_translation(Vector3::new(10.0, 0.0, 0.0)).with_rotation(Quaternion::from_angle_y(Deg(90.0)));
+        assert_eq!(graph.set_transform(planet, turned), vec![planet, moon]);
+        assert_vector_eq(world_position(&graph, moon), Vector3::new(10.0, 0.0, -2.0));
+
+        graph.set_parent(moon, None);
+        assert!(graph.children(planet).is_empty());
+        assert_vector_eq(world_position(&graph, moon), Vector3::new(2.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn cycles_are_refused() {
+        let mut graph = SceneGraph::new();
+        let group = graph.add_node(Transform::identity());
+        let child = graph.add_node(Transform::identity());
+        let grandchild = graph.add_node(Transform::identity());
+        graph.set_parent(child, Some(group));
+        graph.set_parent(grandchild, Some(child));
+
+        assert_eq!(graph.set_parent(group, Some(grandchild)), None);
+        assert_eq!(graph.set_parent(group, Some(group)), None);
+        assert_eq!(graph.parent(group), None);
+    }
+
+    #[test]
+    fn children_of_removed_nodes_move_to_its_parent() {
+        let mut graph = SceneGraph::new();
+        let group = graph.add_node(Transform::from_translation(Vector3::new(1.0, 0.0, 0.0)));
+        let planet = graph.add_node(Transform::from_translation(Vector3::new(10.0, 0.0, 0.0)));
+        let moon = graph.add_node(Transform::from_translation(Vector3::new(2.0, 0.0, 0.0)));
+        graph.set_parent(planet, Some(group));
+        graph.set_parent(moon, Some(planet));
+
+        assert_eq!(graph.remove_node(planet), vec![moon]);
+        assert!(!graph.contains(planet));
+        assert_eq!(graph.nodes().collect::<Vec<_>>(), vec![group, moon]);
+        assert_eq!((graph.parent(moon), graph.children(group)), (Some(group), &[moon][..]));
+        assert_vector_eq(world_position(&graph, moon), Vector3::new(3.0, 0.0, 0.0));
+        // the handle isn't used again
+        assert_eq!(graph.add_node(Transform::identity()), NodeId(3));
+    }
+}
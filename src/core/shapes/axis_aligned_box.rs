@@ -0,0 +1,87 @@
+use cgmath::{Vector3, Zero};
+use crate::core::bvh::Aabb;
+use crate::core::material::MaterialId;
+use crate::core::common::{HitData, NormalizedVector3, Ray};
+use crate::core::shapes::Hittable;
+
+#[derive(Clone)]
+pub struct AxisAlignedBox {
+    min: Vector3<f32>,
+    max: Vector3<f32>,
+    material: MaterialId,
+}
+
+impl AxisAlignedBox {
+    /// A box spanning between two opposite corners
+    pub fn new(corner1: Vector3<f32>, corner2: Vector3<f32>, material: MaterialId) -> AxisAlignedBox {
+        let min = Vector3::new(corner1.x.min(corner2.x), corner1.y.min(corner2.y), corner1.z.min(corner2.z));
+        let max = Vector3::new(corner1.x.max(corner2.x), corner1.y.max(corner2.y), corner1.z.max(corner2.z));
+        AxisAlignedBox { min, max, material }
+    }
+
+    pub fn min(&self) -> Vector3<f32> {
+        self.min
+    }
+
+    pub fn max(&self) -> Vector3<f32> {
+        self.max
+    }
+
+    /// The outward normal of the face closest to the point
+    fn normal(&self, point: Vector3<f32>) -> NormalizedVector3<f32> {
+        let center = (self.min + self.max) / 2.0;
+        let half_size = (self.max - self.min) / 2.0;
+        let local = point - center;
+        let relative = [local.x / half_size.x, local.y / half_size.y, local.z / half_size.z];
+
+        let axis = (0..3)
+            .max_by(|&a, &b| relative[a].abs().total_cmp(&relative[b].abs()))
+            .unwrap_or(0);
+        let mut normal = Vector3::zero();
+        normal[axis] = relative[axis].signum();
+        NormalizedVector3::from_vector3(normal)
+    }
+
+    pub fn set_material(&mut self, material: MaterialId) {
+        self.material = material;
+    }
+}
+
+impl Hittable for AxisAlignedBox {
+    // slab method: the ray is inside the box where it is inside all three pairs of parallel planes
+    fn intersect(&self, ray: &Ray) -> Option<HitData> {
+        let origin = ray.origin();
+        let direction = ray.direction().get();
+        let mut t_near = f32::NEG_INFINITY;
+        let mut t_far = f32::INFINITY;
+        for axis in 0..3 {
+            let inverse_direction = 1.0 / direction[axis];
+            let t1 = (self.min[axis] - origin[axis]) * inverse_direction;
+            let t2 = (self.max[axis] - origin[axis]) * inverse_direction;
+            t_near = t_near.max(t1.min(t2));
+            t_far = t_far.min(t1.max(t2));
+        }
+
+        if t_near > t_far || t_far <= 0.0 {
+            return None;
+        }
+        // if the origin is inside the box, the ray hits it from the inside when it leaves
+        let t = if t_near > 0.0 { t_near } else { t_far };
+
+        let intersection = ray.position_at(t);
+        Some(HitData {
+            intersection,
+            t,
+            normal: self.normal(intersection),
+            material: self.material,
+        })
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        Some(Aabb::new(self.min, self.max))
+    }
+
+    fn material(&self) -> MaterialId {
+        self.material
+    }
+}
@@ -0,0 +1,105 @@
+use cgmath::{InnerSpace, Vector3};
+use crate::core::bvh::Aabb;
+use crate::core::material::MaterialId;
+use crate::core::common::{HitData, NormalizedVector3, Ray};
+use crate::core::shapes::{closest_hit, disc_bounds, intersect_disc, solve_quadratic, Hittable};
+
+/// A cone standing on the disc around `base_center`, its apex is `height` away along `axis`
+#[derive(Clone)]
+pub struct Cone {
+    base_center: Vector3<f32>,
+    axis: Vector3<f32>,
+    radius: f32,
+    height: f32,
+    material: MaterialId,
+}
+
+impl Cone {
+    pub fn new(
+        base_center: Vector3<f32>,
+        axis: Vector3<f32>,
+        radius: f32,
+        height: f32,
+        material: MaterialId,
+    ) -> Cone {
+        Cone { base_center, axis: axis.normalize(), radius, height, material }
+    }
+
+    pub fn base_center(&self) -> Vector3<f32> {
+        self.base_center
+    }
+
+    pub fn axis(&self) -> Vector3<f32> {
+        self.axis
+    }
+
+    pub fn radius(&self) -> f32 {
+        self.radius
+    }
+
+    pub fn height(&self) -> f32 {
+        self.height
+    }
+
+    pub fn apex(&self) -> Vector3<f32> {
+        self.base_center + self.axis * self.height
+    }
+
+    // The side is where |q|^2 = (1 + k^2) * (q . down)^2 for q = point - apex,
+    // with k being the tangent of the half opening angle and down pointing from the apex towards the base
+    fn intersect_side(&self, ray: &Ray) -> Option<HitData> {
+        let apex = self.apex();
+        let down = -self.axis;
+        let k = self.radius / self.height;
+        let slope = 1.0 + k * k;
+
+        let direction = ray.direction().get();
+        let apex_to_origin = ray.origin() - apex;
+        let direction_down = direction.dot(down);
+        let apex_to_origin_down = apex_to_origin.dot(down);
+
+        let a = direction.dot(direction) - slope * direction_down * direction_down;
+        let b = 2.0 * (apex_to_origin.dot(direction) - slope * apex_to_origin_down * direction_down);
+        let c = apex_to_origin.dot(apex_to_origin) - slope * apex_to_origin_down * apex_to_origin_down;
+        let (t1, t2) = solve_quadratic(a, b, c)?;
+
+        [t1, t2].into_iter()
+            .filter(|&t| t > 0.0)
+            .find_map(|t| {
+                let intersection = ray.position_at(t);
+                let apex_to_intersection = intersection - apex;
+                let depth = apex_to_intersection.dot(down);
+                // the other half of the double cone is above the apex
+                if !(0.0..=self.height).contains(&depth) {
+                    return None;
+                }
+                let normal = apex_to_intersection - down * (slope * depth);
+                Some(HitData {
+                    intersection,
+                    t,
+                    normal: NormalizedVector3::from_vector3(normal),
+                    material: self.material,
+                })
+            })
+    }
+
+    pub fn set_material(&mut self, material: MaterialId) {
+        self.material = material;
+    }
+}
+
+impl Hittable for Cone {
+    fn intersect(&self, ray: &Ray) -> Option<HitData> {
+        let base_cap = intersect_disc(ray, self.base_center, -self.axis, self.radius, self.material);
+        closest_hit(self.intersect_side(ray), base_cap)
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        let base = disc_bounds(self.base_center, self.axis, self.radius);
+        Some(base.union(&Aabb::new(self.apex(), self.apex())))
+    }
+
+    fn material(&self) -> MaterialId {
+        self.material
+    }
+}
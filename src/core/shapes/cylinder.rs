@@ -0,0 +1,96 @@
+use cgmath::{InnerSpace, Vector3};
+use crate::core::bvh::Aabb;
+use crate::core::material::MaterialId;
+use crate::core::common::{HitData, NormalizedVector3, Ray};
+use crate::core::shapes::{closest_hit, disc_bounds, intersect_disc, solve_quadratic, Hittable};
+
+/// A capped cylinder standing on the disc around `base_center`, reaching `height` along `axis`
+#[derive(Clone)]
+pub struct Cylinder {
+    base_center: Vector3<f32>,
+    axis: Vector3<f32>,
+    radius: f32,
+    height: f32,
+    material: MaterialId,
+}
+
+impl Cylinder {
+    pub fn new(
+        base_center: Vector3<f32>,
+        axis: Vector3<f32>,
+        radius: f32,
+        height: f32,
+        material: MaterialId,
+    ) -> Cylinder {
+        Cylinder { base_center, axis: axis.normalize(), radius, height, material }
+    }
+
+    pub fn base_center(&self) -> Vector3<f32> {
+        self.base_center
+    }
+
+    pub fn axis(&self) -> Vector3<f32> {
+        self.axis
+    }
+
+    pub fn radius(&self) -> f32 {
+        self.radius
+    }
+
+    pub fn height(&self) -> f32 {
+        self.height
+    }
+
+    fn intersect_side(&self, ray: &Ray) -> Option<HitData> {
+        let direction = ray.direction().get();
+        let base_to_origin = ray.origin() - self.base_center;
+        // both vectors projected onto the plane perpendicular to the axis
+        let direction_perpendicular = direction - self.axis * direction.dot(self.axis);
+        let base_to_origin_perpendicular = base_to_origin - self.axis * base_to_origin.dot(self.axis);
+
+        let a = direction_perpendicular.magnitude2();
+        let b = 2.0 * direction_perpendicular.dot(base_to_origin_perpendicular);
+        let c = base_to_origin_perpendicular.magnitude2() - self.radius * self.radius;
+        let (t1, t2) = solve_quadratic(a, b, c)?;
+
+        [t1, t2].into_iter()
+            .filter(|&t| t > 0.0)
+            .find_map(|t| {
+                let intersection = ray.position_at(t);
+                let height = (intersection - self.base_center).dot(self.axis);
+                if !(0.0..=self.height).contains(&height) {
+                    return None;
+                }
+                let axis_point = self.base_center + self.axis * height;
+                Some(HitData {
+                    intersection,
+                    t,
+                    normal: NormalizedVector3::from_vector3(intersection - axis_point),
+                    material: self.material,
+                })
+            })
+    }
+
+    pub fn set_material(&mut self, material: MaterialId) {
+        self.material = material;
+    }
+}
+
+impl Hittable for Cylinder {
+    fn intersect(&self, ray: &Ray) -> Option<HitData> {
+        let top_center = self.base_center + self.axis * self.height;
+        let bottom_cap = intersect_disc(ray, self.base_center, -self.axis, self.radius, self.material);
+        let top_cap = intersect_disc(ray, top_center, self.axis, self.radius, self.material);
+        closest_hit(self.intersect_side(ray), closest_hit(bottom_cap, top_cap))
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        let top_center = self.base_center + self.axis * self.height;
+        let bottom = disc_bounds(self.base_center, self.axis, self.radius);
+        Some(bottom.union(&disc_bounds(top_center, self.axis, self.radius)))
+    }
+
+    fn material(&self) -> MaterialId {
+        self.material
+    }
+}
@@ -0,0 +1,49 @@
+use cgmath::{InnerSpace, Vector3};
+use crate::core::bvh::Aabb;
+use crate::core::material::MaterialId;
+use crate::core::common::{HitData, Ray};
+use crate::core::shapes::{disc_bounds, intersect_disc, Hittable};
+
+#[derive(Clone)]
+pub struct Disc {
+    center: Vector3<f32>,
+    normal: Vector3<f32>,
+    radius: f32,
+    material: MaterialId,
+}
+
+impl Disc {
+    pub fn new(center: Vector3<f32>, normal: Vector3<f32>, radius: f32, material: MaterialId) -> Disc {
+        Disc { center, normal: normal.normalize(), radius, material }
+    }
+
+    pub fn center(&self) -> Vector3<f32> {
+        self.center
+    }
+
+    pub fn normal(&self) -> Vector3<f32> {
+        self.normal
+    }
+
+    pub fn radius(&self) -> f32 {
+        self.radius
+    }
+
+    pub fn set_material(&mut self, material: MaterialId) {
+        self.material = material;
+    }
+}
+
+impl Hittable for Disc {
+    fn intersect(&self, ray: &Ray) -> Option<HitData> {
+        intersect_disc(ray, self.center, self.normal, self.radius, self.material)
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        Some(disc_bounds(self.center, self.normal, self.radius))
+    }
+
+    fn material(&self) -> MaterialId {
+        self.material
+    }
+}
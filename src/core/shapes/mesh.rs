@@ -0,0 +1,126 @@
+use cgmath::{Vector2, Vector3, VectorSpace};
+use crate::core::bvh::{Aabb, Bvh};
+use std::path::{Path, PathBuf};
+use crate::core::material::MaterialId;
+use crate::core::common::{HitData, Ray};
+use crate::core::shapes::triangle::intersect_triangle;
+use crate::core::shapes::{facing_normal, Hittable};
+
+/// The vertices of a triangle of a mesh, as indices into the vertex data of the mesh
+#[derive(Clone, Debug, PartialEq)]
+pub struct MeshFace {
+    pub positions: [usize; 3],
+    pub normals: Option<[usize; 3]>,
+    pub texture_coordinates: Option<[usize; 3]>,
+}
+
+#[derive(Clone)]
+pub struct Mesh {
+    positions: Vec<Vector3<f32>>,
+    normals: Vec<Vector3<f32>>,
+    texture_coordinates: Vec<Vector2<f32>>,
+    faces: Vec<MeshFace>,
+    material: MaterialId,
+    bvh: Bvh,
+    // the file the mesh was loaded from, so a scene can refer to it when it is saved
+    source: Option<PathBuf>,
+}
+
+impl Mesh {
+    /// Every index of the faces has to be valid for the corresponding vertex data
+    pub fn new(
+        positions: Vec<Vector3<f32>>,
+        normals: Vec<Vector3<f32>>,
+        texture_coordinates: Vec<Vector2<f32>>,
+        faces: Vec<MeshFace>,
+        material: MaterialId,
+    ) -> Mesh {
+        let face_bounds: Vec<_> = faces.iter()
+            .map(|face| Aabb::from_points(face.positions.map(|index| positions[index])))
+            .collect();
+        let bvh = Bvh::build(&face_bounds);
+        Mesh { positions, normals, texture_coordinates, faces, material, bvh, source: None }
+    }
+
+    pub fn positions(&self) -> &[Vector3<f32>] {
+        &self.positions
+    }
+
+    pub fn normals(&self) -> &[Vector3<f32>] {
+        &self.normals
+    }
+
+    pub fn texture_coordinates(&self) -> &[Vector2<f32>] {
+        &self.texture_coordinates
+    }
+
+    pub fn faces(&self) -> &[MeshFace] {
+        &self.faces
+    }
+
+    pub fn source(&self) -> Option<&Path> {
+        self.source.as_deref()
+    }
+
+    pub fn set_source(&mut self, source: impl Into<PathBuf>) {
+        self.source = Some(source.into());
+    }
+
+    pub fn set_material(&mut self, material: MaterialId) {
+        self.material = material;
+    }
+
+    /// The texture coordinates of a point of a face given by its barycentric coordinates
+    pub fn texture_coordinates_at(&self, face_index: usize, barycentric: Vector2<f32>) -> Option<Vector2<f32>> {
+        let [a, b, c] = self.faces[face_index].texture_coordinates?;
+        Some(interpolate(
+            [self.texture_coordinates[a], self.texture_coordinates[b], self.texture_coordinates[c]],
+            barycentric,
+        ))
+    }
+
+    pub fn face_vertices(&self, face_index: usize) -> [Vector3<f32>; 3] {
+        self.faces[face_index].positions.map(|index| self.positions[index])
+    }
+
+    /// The intersection of the ray with one face of the mesh, the normal is interpolated
+    /// between the vertex normals if the face has them
+    pub fn intersect_face(&self, ray: &Ray, face_index: usize) -> Option<HitData> {
+        let [a, b, c] = self.face_vertices(face_index);
+        let (t, barycentric) = intersect_triangle(ray, a, b, c)?;
+        let normal = match self.faces[face_index].normals {
+            Some(normals) => interpolate(normals.map(|index| self.normals[index]), barycentric),
+            None => (b - a).cross(c - a),
+        };
+        Some(HitData {
+            intersection: ray.position_at(t),
+            t,
+            normal: facing_normal(normal, ray),
+            material: self.material,
+        })
+    }
+}
+
+fn interpolate<V>(values: [V; 3], barycentric: Vector2<f32>) -> V
+where V: VectorSpace<Scalar = f32> {
+    let [a, b, c] = values;
+    a * (1.0 - barycentric.x - barycentric.y) + b * barycentric.x + c * barycentric.y
+}
+
+impl Hittable for Mesh {
+    fn intersect(&self, ray: &Ray) -> Option<HitData> {
+        self.bvh.closest_hit(ray, |face_index| self.intersect_face(ray, face_index))
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        // an empty mesh is never hit, so it doesn't matter that it ends up with the unbounded shapes
+        if self.faces.is_empty() {
+            return None;
+        }
+        Some(self.bvh.bounds())
+    }
+
+    fn material(&self) -> MaterialId {
+        self.material
+    }
+}
@@ -0,0 +1,273 @@
+pub mod axis_aligned_box;
+pub mod cone;
+pub mod cylinder;
+pub mod disc;
+pub mod mesh;
+pub mod plane;
+pub mod sphere;
+pub mod triangle;
+
+pub use axis_aligned_box::AxisAlignedBox;
+pub use cone::Cone;
+pub use cylinder::Cylinder;
+pub use disc::Disc;
+pub use mesh::{Mesh, MeshFace};
+pub use plane::Plane;
+pub use sphere::Sphere;
+pub use triangle::Triangle;
+
+use crate::core::bvh::Aabb;
+use crate::core::material::MaterialId;
+use crate::core::common::{HitData, NormalizedVector3, Ray};
+use cgmath::{InnerSpace, Vector3};
+use std::sync::Arc;
+
+/// A handle of a shape added to a scene
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ShapeId(pub(crate) usize);
+
+pub trait Hittable {
+    /// The closest intersection of the ray with the object in front of the ray's origin
+    fn intersect(&self, ray: &Ray) -> Option<HitData>;
+
+    /// The bounding box of the object, `None` if it is unbounded like an infinite plane
+    fn bounds(&self) -> Option<Aabb>;
+
+    /// The material the object is made of, mesh faces all share the material of the mesh
+    fn material(&self) -> MaterialId;
+
+    /// Objects that don't cast shadows are skipped by shadow rays,
+    /// for example the visual representation of a light source.
+    fn casts_shadow(&self) -> bool { true }
+}
+
+#[derive(Clone)]
+pub enum Shape {
+    Sphere(Sphere),
+    Plane(Plane),
+    AxisAlignedBox(AxisAlignedBox),
+    Triangle(Triangle),
+    Disc(Disc),
+    Cylinder(Cylinder),
+    Cone(Cone),
+    // meshes can be large, so copies of the shape share the same mesh
+    Mesh(Arc<Mesh>),
+}
+
+impl Shape {
+    fn as_hittable(&self) -> &dyn Hittable {
+        match self {
+            Shape::Sphere(shape) => shape,
+            Shape::Plane(shape) => shape,
+            Shape::AxisAlignedBox(shape) => shape,
+            Shape::Triangle(shape) => shape,
+            Shape::Disc(shape) => shape,
+            Shape::Cylinder(shape) => shape,
+            Shape::Cone(shape) => shape,
+            Shape::Mesh(shape) => shape.as_ref(),
+        }
+    }
+
+    /// A mesh shared with copies of the shape is copied first, so the copies keep their material
+    pub fn set_material(&mut self, material: MaterialId) {
+        match self {
+            Shape::Sphere(shape) => shape.set_material(material),
+            Shape::Plane(shape) => shape.set_material(material),
+            Shape::AxisAlignedBox(shape) => shape.set_material(material),
+            Shape::Triangle(shape) => shape.set_material(material),
+            Shape::Disc(shape) => shape.set_material(material),
+            Shape::Cylinder(shape) => shape.set_material(material),
+            Shape::Cone(shape) => shape.set_material(material),
+            Shape::Mesh(shape) => Arc::make_mut(shape).set_material(material),
+        }
+    }
+}
+
+impl Hittable for Shape {
+    fn intersect(&self, ray: &Ray) -> Option<HitData> {
+        self.as_hittable().intersect(ray)
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        self.as_hittable().bounds()
+    }
+
+    fn casts_shadow(&self) -> bool {
+        self.as_hittable().casts_shadow()
+    }
+
+    fn material(&self) -> MaterialId {
+        self.as_hittable().material()
+    }
+}
+
+macro_rules! impl_from_for_shape {
+    ($($variant:ident),*) => {
+        $(
+            impl From<$variant> for Shape {
+                fn from(shape: $variant) -> Shape { Shape::$variant(shape) }
+            }
+        )*
+    };
+}
+impl_from_for_shape!(Sphere, Plane, AxisAlignedBox, Triangle, Disc, Cylinder, Cone);
+
+impl From<Mesh> for Shape {
+    fn from(mesh: Mesh) -> Shape { Shape::Mesh(Arc::new(mesh)) }
+}
+
+/// The real roots of `a * t^2 + b * t + c`, in increasing order
+fn solve_quadratic(a: f32, b: f32, c: f32) -> Option<(f32, f32)> {
+    let discriminant = b * b - 4.0 * a * c;
+    if discriminant < 0.0 || a == 0.0 {
+        return None;
+    }
+
+    let sqrt_discriminant = discriminant.sqrt();
+    let t1 = (-b - sqrt_discriminant) / (2.0 * a);
+    let t2 = (-b + sqrt_discriminant) / (2.0 * a);
+    Some((t1.min(t2), t1.max(t2)))
+}
+
+/// The normal of a flat surface turned towards the side the ray comes from, so flat shapes are lit from both sides
+fn facing_normal(normal: Vector3<f32>, ray: &Ray) -> NormalizedVector3<f32> {
+    if normal.dot(ray.direction().get()) > 0.0 {
+        NormalizedVector3::from_vector3(-normal)
+    } else {
+        NormalizedVector3::from_vector3(normal)
+    }
+}
+
+/// Of two possible hits, the one closer to the origin of the ray
+fn closest_hit(hit1: Option<HitData>, hit2: Option<HitData>) -> Option<HitData> {
+    match (hit1, hit2) {
+        (Some(hit1), Some(hit2)) => Some(if hit1.t <= hit2.t { hit1 } else { hit2 }),
+        (hit1, hit2) => hit1.or(hit2),
+    }
+}
+
+/// The bounds of a disc with the given center, normal and radius
+fn disc_bounds(center: Vector3<f32>, normal: Vector3<f32>, radius: f32) -> Aabb {
+    // how far the disc reaches along each axis
+    let extent = Vector3::new(
+        (1.0 - normal.x * normal.x).max(0.0).sqrt(),
+        (1.0 - normal.y * normal.y).max(0.0).sqrt(),
+        (1.0 - normal.z * normal.z).max(0.0).sqrt(),
+    ) * radius;
+    Aabb::new(center - extent, center + extent)
+}
+
+/// A hit on a disc with the given center, normal and radius
+fn intersect_disc(
+    ray: &Ray,
+    center: Vector3<f32>,
+    normal: Vector3<f32>,
+    radius: f32,
+    material: MaterialId,
+) -> Option<HitData> {
+    let denominator = normal.dot(ray.direction().get());
+    if denominator.abs() < f32::EPSILON {
+        return None;
+    }
+    let t = (center - ray.origin()).dot(normal) / denominator;
+    if t <= 0.0 {
+        return None;
+    }
+
+    let intersection = ray.position_at(t);
+    if (intersection - center).magnitude2() > radius * radius {
+        return None;
+    }
+    Some(HitData {
+        intersection,
+        t,
+        normal: facing_normal(normal, ray),
+        material,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::material::MaterialId;
+
+    const MATERIAL: MaterialId = MaterialId(0);
+
+    fn ray(origin: (f32, f32, f32), direction: (f32, f32, f32)) -> Ray {
+        Ray::new(origin.into(), direction.into())
+    }
+
+    fn assert_hit(shape: impl Into<Shape>, ray: &Ray, expected_t: f32, expected_normal: (f32, f32, f32)) {
+        let hit = shape.into().intersect(ray).expect("the ray should hit the shape");
+        let expected_normal: Vector3<f32> = expected_normal.into();
+        assert!((hit.t - expected_t).abs() < 1e-4, "expected t = {}, got {}", expected_t, hit.t);
+        assert!(
+            (hit.normal.get() - expected_normal.normalize()).magnitude() < 1e-4,
+            "expected normal {:?}, got {:?}", expected_normal, hit.normal.get(),
+        );
+    }
+
+    #[test]
+    fn sphere() {
+        let sphere = Sphere::new(Vector3::new(0.0, 0.0, -5.0), 1.0, MATERIAL);
+        assert_hit(sphere.clone(), &ray((0.0, 0.0, 0.0), (0.0, 0.0, -1.0)), 4.0, (0.0, 0.0, 1.0));
+        assert!(sphere.intersect(&ray((0.0, 0.0, 0.0), (0.0, 0.0, 1.0))).is_none());
+    }
+
+    #[test]
+    fn plane_is_hit_from_both_sides() {
+        let floor = Plane::new(Vector3::new(0.0, -1.0, 0.0), Vector3::unit_y(), MATERIAL);
+        assert_hit(floor.clone(), &ray((0.0, 1.0, 0.0), (0.0, -1.0, 0.0)), 2.0, (0.0, 1.0, 0.0));
+        assert_hit(floor.clone(), &ray((0.0, -3.0, 0.0), (0.0, 1.0, 0.0)), 2.0, (0.0, -1.0, 0.0));
+        assert!(floor.intersect(&ray((0.0, 1.0, 0.0), (1.0, 0.0, 0.0))).is_none());
+    }
+
+    #[test]
+    fn axis_aligned_box() {
+        let aab = AxisAlignedBox::new(Vector3::new(1.0, 1.0, 1.0), Vector3::new(-1.0, -1.0, -1.0), MATERIAL);
+        assert_hit(aab.clone(), &ray((5.0, 0.5, 0.0), (-1.0, 0.0, 0.0)), 4.0, (1.0, 0.0, 0.0));
+        assert_hit(aab.clone(), &ray((0.0, -3.0, 0.2), (0.0, 1.0, 0.0)), 2.0, (0.0, -1.0, 0.0));
+        // from the inside the ray hits the face it leaves through
+        assert_hit(aab.clone(), &ray((0.0, 0.0, 0.0), (0.0, 0.0, 1.0)), 1.0, (0.0, 0.0, 1.0));
+        assert!(aab.intersect(&ray((5.0, 2.0, 0.0), (-1.0, 0.0, 0.0))).is_none());
+    }
+
+    #[test]
+    fn triangle() {
+        let triangle = Triangle::new(
+            Vector3::new(-1.0, -1.0, -2.0),
+            Vector3::new(1.0, -1.0, -2.0),
+            Vector3::new(0.0, 1.0, -2.0),
+            MATERIAL,
+        );
+        assert_hit(triangle.clone(), &ray((0.0, 0.0, 0.0), (0.0, 0.0, -1.0)), 2.0, (0.0, 0.0, 1.0));
+        assert_hit(triangle.clone(), &ray((0.0, 0.0, -4.0), (0.0, 0.0, 1.0)), 2.0, (0.0, 0.0, -1.0));
+        assert!(triangle.intersect(&ray((0.9, 0.9, 0.0), (0.0, 0.0, -1.0))).is_none());
+    }
+
+    #[test]
+    fn disc() {
+        let disc = Disc::new(Vector3::new(0.0, 0.0, 0.0), Vector3::unit_y(), 1.0, MATERIAL);
+        assert_hit(disc.clone(), &ray((0.5, 2.0, 0.0), (0.0, -1.0, 0.0)), 2.0, (0.0, 1.0, 0.0));
+        assert!(disc.intersect(&ray((1.5, 2.0, 0.0), (0.0, -1.0, 0.0))).is_none());
+    }
+
+    #[test]
+    fn cylinder_side_and_caps() {
+        let cylinder = Cylinder::new(Vector3::new(0.0, 0.0, 0.0), Vector3::unit_y(), 1.0, 2.0, MATERIAL);
+        assert_hit(cylinder.clone(), &ray((5.0, 1.0, 0.0), (-1.0, 0.0, 0.0)), 4.0, (1.0, 0.0, 0.0));
+        assert_hit(cylinder.clone(), &ray((0.5, 5.0, 0.0), (0.0, -1.0, 0.0)), 3.0, (0.0, 1.0, 0.0));
+        assert_hit(cylinder.clone(), &ray((0.5, -5.0, 0.0), (0.0, 1.0, 0.0)), 5.0, (0.0, -1.0, 0.0));
+        assert!(cylinder.intersect(&ray((5.0, 3.0, 0.0), (-1.0, 0.0, 0.0))).is_none());
+    }
+
+    #[test]
+    fn cone_side_and_base() {
+        // a cone with a 45 degree half opening angle, its apex is at (0, 1, 0)
+        let cone = Cone::new(Vector3::new(0.0, 0.0, 0.0), Vector3::unit_y(), 1.0, 1.0, MATERIAL);
+        assert_hit(cone.clone(), &ray((5.0, 0.5, 0.0), (-1.0, 0.0, 0.0)), 4.5, (1.0, 1.0, 0.0));
+        assert_hit(cone.clone(), &ray((0.5, -5.0, 0.0), (0.0, 1.0, 0.0)), 5.0, (0.0, -1.0, 0.0));
+        // the mirrored half of the double cone above the apex is not part of the shape
+        assert!(cone.intersect(&ray((5.0, 1.5, 0.0), (-1.0, 0.0, 0.0))).is_none());
+    }
+}
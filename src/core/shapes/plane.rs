@@ -0,0 +1,60 @@
+use cgmath::{InnerSpace, Vector3};
+use crate::core::bvh::Aabb;
+use crate::core::material::MaterialId;
+use crate::core::common::{HitData, Ray};
+use crate::core::shapes::{facing_normal, Hittable};
+
+/// An infinite plane going through `point`, for example a floor
+#[derive(Clone)]
+pub struct Plane {
+    point: Vector3<f32>,
+    normal: Vector3<f32>,
+    material: MaterialId,
+}
+
+impl Plane {
+    pub fn new(point: Vector3<f32>, normal: Vector3<f32>, material: MaterialId) -> Plane {
+        Plane { point, normal: normal.normalize(), material }
+    }
+
+    pub fn point(&self) -> Vector3<f32> {
+        self.point
+    }
+
+    pub fn normal(&self) -> Vector3<f32> {
+        self.normal
+    }
+
+    pub fn set_material(&mut self, material: MaterialId) {
+        self.material = material;
+    }
+}
+
+impl Hittable for Plane {
+    fn intersect(&self, ray: &Ray) -> Option<HitData> {
+        let denominator = self.normal.dot(ray.direction().get());
+        if denominator.abs() < f32::EPSILON {
+            return None; // The ray is parallel to the plane
+        }
+
+        let t = (self.point - ray.origin()).dot(self.normal) / denominator;
+        if t <= 0.0 {
+            return None;
+        }
+
+        Some(HitData {
+            intersection: ray.position_at(t),
+            t,
+            normal: facing_normal(self.normal, ray),
+            material: self.material,
+        })
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        None
+    }
+
+    fn material(&self) -> MaterialId {
+        self.material
+    }
+}
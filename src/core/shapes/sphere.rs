@@ -0,0 +1,84 @@
+use cgmath::{InnerSpace, Vector3};
+use crate::core::bvh::Aabb;
+use crate::core::material::MaterialId;
+use crate::core::common::{HitData, NormalizedVector3, Ray};
+use crate::core::shapes::{solve_quadratic, Hittable};
+
+#[derive(Clone)]
+pub struct Sphere {
+    center: Vector3<f32>,
+    material: MaterialId,
+    radius: f32,
+    casts_shadow: bool,
+}
+
+impl Sphere {
+    pub fn new(center: Vector3<f32>, radius: f32, material: MaterialId) -> Sphere {
+        Sphere { center, radius, material, casts_shadow: true }
+    }
+
+    pub fn normal(&self, point: Vector3<f32>) -> NormalizedVector3<f32> {
+        NormalizedVector3::from_vector3(point - self.center)
+    }
+
+    pub fn set_center(&mut self, point: Vector3<f32>) {
+        self.center = point;
+    }
+
+    pub fn center(&self) -> Vector3<f32> {
+        self.center
+    }
+
+    pub fn radius(&self) -> f32 {
+        self.radius
+    }
+
+    pub fn set_casts_shadow(&mut self, casts_shadow: bool) {
+        self.casts_shadow = casts_shadow;
+    }
+
+    pub fn set_material(&mut self, material: MaterialId) {
+        self.material = material;
+    }
+}
+
+impl Hittable for Sphere {
+    fn intersect(&self, ray: &Ray) -> Option<HitData> {
+        let oc = ray.origin() - self.center;
+        let raydir = ray.direction().get();
+        let a = raydir.dot(raydir);
+        let b = 2.0 * oc.dot(raydir);
+        let c = oc.dot(oc) - self.radius * self.radius;
+        let (t1, t2) = solve_quadratic(a, b, c)?;
+
+        // Choose the smallest positive t
+        let t = if t1 > 0.0 {
+            t1
+        } else if t2 > 0.0 {
+            t2
+        } else {
+            return None; // Both intersections are behind the ray
+        };
+
+        let intersection = ray.position_at(t);
+        Some(HitData{
+            intersection,
+            t,
+            normal: self.normal(intersection),
+            material: self.material,
+        })
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        let extent = Vector3::new(self.radius, self.radius, self.radius);
+        Some(Aabb::new(self.center - extent, self.center + extent))
+    }
+
+    fn casts_shadow(&self) -> bool {
+        self.casts_shadow
+    }
+
+    fn material(&self) -> MaterialId {
+        self.material
+    }
+}
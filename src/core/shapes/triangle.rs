@@ -0,0 +1,90 @@
+use cgmath::{InnerSpace, Vector2, Vector3};
+use crate::core::bvh::Aabb;
+use crate::core::material::MaterialId;
+use crate::core::common::{HitData, Ray};
+use crate::core::shapes::{facing_normal, Hittable};
+
+#[derive(Clone)]
+pub struct Triangle {
+    vertices: [Vector3<f32>; 3],
+    material: MaterialId,
+}
+
+impl Triangle {
+    pub fn new(a: Vector3<f32>, b: Vector3<f32>, c: Vector3<f32>, material: MaterialId) -> Triangle {
+        Triangle { vertices: [a, b, c], material }
+    }
+
+    pub fn vertices(&self) -> &[Vector3<f32>; 3] {
+        &self.vertices
+    }
+
+    /// The normal of the side the vertices are seen counter-clockwise from
+    pub fn normal(&self) -> Vector3<f32> {
+        let [a, b, c] = self.vertices;
+        (b - a).cross(c - a).normalize()
+    }
+
+    pub fn set_material(&mut self, material: MaterialId) {
+        self.material = material;
+    }
+}
+
+impl Hittable for Triangle {
+    fn intersect(&self, ray: &Ray) -> Option<HitData> {
+        let [a, b, c] = self.vertices;
+        let (t, _) = intersect_triangle(ray, a, b, c)?;
+        Some(HitData {
+            intersection: ray.position_at(t),
+            t,
+            normal: facing_normal((b - a).cross(c - a), ray),
+            material: self.material,
+        })
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        Some(Aabb::from_points(self.vertices))
+    }
+
+    fn material(&self) -> MaterialId {
+        self.material
+    }
+}
+
+/// Möller–Trumbore intersection, returns the distance along the ray and the barycentric coordinates
+/// of the intersection, `(u, v)` are the weights of `b` and `c`
+pub(crate) fn intersect_triangle(
+    ray: &Ray,
+    a: Vector3<f32>,
+    b: Vector3<f32>,
+    c: Vector3<f32>,
+) -> Option<(f32, Vector2<f32>)> {
+    let edge1 = b - a;
+    let edge2 = c - a;
+    let direction = ray.direction().get();
+
+    let p = direction.cross(edge2);
+    let determinant = edge1.dot(p);
+    if determinant.abs() < f32::EPSILON {
+        return None; // The ray is parallel to the triangle
+    }
+    let inverse_determinant = 1.0 / determinant;
+
+    let s = ray.origin() - a;
+    let u = s.dot(p) * inverse_determinant;
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+
+    let q = s.cross(edge1);
+    let v = direction.dot(q) * inverse_determinant;
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+
+    let t = edge2.dot(q) * inverse_determinant;
+    if t <= 0.0 {
+        return None;
+    }
+    Some((t, Vector2::new(u, v)))
+}
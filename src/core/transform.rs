@@ -0,0 +1,150 @@
+use crate::core::bvh::Aabb;
+use crate::core::common::{HitData, NormalizedVector3, Ray};
+use crate::core::shapes::Hittable;
+use cgmath::{ElementWise, EuclideanSpace, InnerSpace, Matrix, Matrix4, One, Point3, Quaternion, SquareMatrix, Transform as _, Vector3};
+
+/// The placement of an object relative to its parent: it is scaled, then rotated, then translated
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Transform {
+    pub translation: Vector3<f32>,
+    pub rotation: Quaternion<f32>,
+    /// Scale along each axis of the object, it must not be zero
+    pub scale: Vector3<f32>,
+}
+
+impl Default for Transform {
+    fn default() -> Transform {
+        Transform::identity()
+    }
+}
+
+impl Transform {
+    /// Leaves objects where they are
+    pub fn identity() -> Transform {
+        Transform { translation: Vector3::new(0.0, 0.0, 0.0), rotation: Quaternion::one(), scale: Vector3::new(1.0, 1.0, 1.0) }
+    }
+
+    pub fn from_translation(translation: Vector3<f32>) -> Transform {
+        Transform { translation, ..Transform::identity() }
+    }
+
+    pub fn with_rotation(self, rotation: Quaternion<f32>) -> Transform {
+        Transform { rotation, ..self }
+    }
+
+    pub fn with_scale(self, scale: Vector3<f32>) -> Transform {
+        Transform { scale, ..self }
+    }
+
+    pub fn is_identity(&self) -> bool {
+        *self == Transform::identity()
+    }
+
+    pub fn matrix(&self) -> Matrix4<f32> {
+        Matrix4::from_translation(self.translation)
+            * Matrix4::from(self.rotation)
+            * Matrix4::from_nonuniform_scale(self.scale.x, self.scale.y, self.scale.z)
+    }
+
+    /// Where a point of the object ends up
+    pub fn transform_point(&self, point: Vector3<f32>) -> Vector3<f32> {
+        self.translation + self.rotation * point.mul_element_wise(self.scale)
+    }
+}
+
+/// The transform of a shape from the space it was defined in into the world, with the inverse
+/// to bring rays into the space of the shape
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct WorldTransform {
+    matrix: Matrix4<f32>,
+    // None if the transform squashes the shape flat, then nothing can hit it
+    inverse: Option<Matrix4<f32>>,
+}
+
+impl WorldTransform {
+    pub(crate) fn new(matrix: Matrix4<f32>) -> WorldTransform {
+        WorldTransform { matrix, inverse: matrix.invert() }
+    }
+
+    pub(crate) fn intersect(&self, shape: &impl Hittable, ray: &Ray) -> Option<HitData> {
+        let inverse = self.inverse?;
+        let local_ray = Ray::new(
+            inverse.transform_point(Point3::from_vec(*ray.origin())).to_vec(),
+            inverse.transform_vector(ray.direction().get()),
+        );
+        let hit = shape.intersect(&local_ray)?;
+
+        let intersection = self.matrix.transform_point(Point3::from_vec(hit.intersection)).to_vec();
+        // normals are transformed with the inverse transpose, so they stay perpendicular to non-uniformly scaled surfaces
+        let normal = inverse.transpose().transform_vector(hit.normal.get());
+        Some(HitData {
+            intersection,
+            t: (intersection - ray.origin()).dot(ray.direction().get()),
+            normal: NormalizedVector3::from_vector3(normal),
+            material: hit.material,
+        })
+    }
+
+    /// The bounds of the transformed box
+    pub(crate) fn bounds(&self, bounds: Aabb) -> Aabb {
+        let (min, max) = (bounds.min, bounds.max);
+        Aabb::from_points((0..8).map(|corner| {
+            let point = Point3::new(
+                if corner & 1 == 0 { min.x } else { max.x },
+                if corner & 2 == 0 { min.y } else { max.y },
+                if corner & 4 == 0 { min.z } else { max.z },
+            );
+            self.matrix.transform_point(point).to_vec()
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::material::MaterialId;
+    use crate::core::shapes::Sphere;
+    use cgmath::{Deg, Rotation3};
+
+    fn assert_vector_eq(actual: Vector3<f32>, expected: Vector3<f32>) {
+        assert!((actual - expected).magnitude() < 1e-4, "expected {:?}, got {:?}", expected, actual);
+    }
+
+    #[test]
+    fn transforms_scale_then_rotate_then_translate() {
+        let transform = Transform::from_translation(Vector3::new(0.0, 0.0, 5.0))
+            .with_rotation(Quaternion::from_angle_z(Deg(90.0)))
+            .with_scale(Vector3::new(2.0, 1.0, 1.0));
+        let expected = Vector3::new(0.0, 2.0, 5.0);
+        assert_vector_eq(transform.transform_point(Vector3::unit_x()), expected);
+        assert_vector_eq(transform.matrix().transform_point(Point3::new(1.0, 0.0, 0.0)).to_vec(), expected);
+    }
+
+    #[test]
+    fn rays_hit_transformed_shapes() {
+        // a unit sphere stretched into an ellipsoid 4 units wide, moved 10 units in front of the ray
+        let transform = Transform::from_translation(Vector3::new(0.0, 0.0, -10.0)).with_scale(Vector3::new(4.0, 1.0, 1.0));
+        let world = WorldTransform::new(transform.matrix());
+        let sphere = Sphere::new(Vector3::new(0.0, 0.0, 0.0), 1.0, MaterialId(0));
+
+        let hit = world.intersect(&sphere, &Ray::new(Vector3::new(3.0, 0.0, 0.0), -Vector3::unit_z())).unwrap();
+        let expected_z = -10.0 + (1.0f32 - 0.75 * 0.75).sqrt();
+        assert_vector_eq(hit.intersection, Vector3::new(3.0, 0.0, expected_z));
+        assert!((hit.t - -expected_z).abs() < 1e-4);
+        // the normal of the stretched surface leans outwards much more than the one of the sphere
+        let normal = hit.normal.get();
+        assert!(normal.x > 0.0 && normal.z > 0.0 && (normal.magnitude() - 1.0).abs() < 1e-5);
+        assert!(world.intersect(&sphere, &Ray::new(Vector3::new(4.5, 0.0, 0.0), -Vector3::unit_z())).is_none());
+
+        let bounds = world.bounds(sphere.bounds().unwrap());
+        assert_vector_eq(bounds.min, Vector3::new(-4.0, -1.0, -11.0));
+        assert_vector_eq(bounds.max, Vector3::new(4.0, 1.0, -9.0));
+    }
+
+    #[test]
+    fn flattened_shapes_are_not_hit() {
+        let world = WorldTransform::new(Transform::identity().with_scale(Vector3::new(1.0, 0.0, 1.0)).matrix());
+        let sphere = Sphere::new(Vector3::new(0.0, 0.0, 0.0), 1.0, MaterialId(0));
+        assert!(world.intersect(&sphere, &Ray::new(Vector3::new(0.0, 5.0, 0.0), -Vector3::unit_y())).is_none());
+    }
+}
@@ -8,12 +8,20 @@ pub trait InputHandler {
     fn input_actions(&self) -> impl Iterator<Item=InputAction> + '_;
     fn scene_actions(&self) -> impl Iterator<Item=SceneAction> + '_;
     fn poll_event(&mut self, dt: &Duration) -> std::io::Result<()>;
+    fn set_resolution(&mut self, width: usize, height: usize);
+    /// How many terminal cells, horizontally and vertically, a pixel of the image covers,
+    /// so clicks can be mapped onto the image
+    fn set_cells_per_pixel(&mut self, cells_per_pixel: (f32, f32));
 }
 
 #[derive(Hash, Eq, PartialEq, Copy, Clone)]
 pub enum InputAction {
     Quit,
     ChangeRenderType,
+    TogglePathTracing,
+    Resize { columns: u16, rows: u16 },
+    /// Writes the scene, with the changes made in the terminal, into a scene file
+    SaveScene,
     ActionOnScene(SceneAction),
 }
 
@@ -27,6 +35,23 @@ pub enum SceneAction {
         // the reason it is not stored as a float is that this way it can be hashed
         delta: Vector2<i16>
     },
+    /// Selects the object under a point of the screen, or nothing if there is no object there.
+    /// The position is in normalized screen space times 1000, like the delta of `RotateCamera`.
+    Select { position: Vector2<i16> },
+    /// Moves the selected shape in the plane facing the camera, so it follows the mouse.
+    /// The delta is in normalized screen space times 1000, like the one of `RotateCamera`.
+    DragSelected { delta: Vector2<i16> },
+    /// Scales the selected shape around its center, a step up makes it 10% larger
+    ScaleSelected { steps: i8 },
+    /// Gives the selected shape the next material of the scene, or the previous one
+    CycleMaterial { forward: bool },
+    DeleteSelected,
+    /// Copies the selected shape in the same place and selects the copy, so it can be dragged away
+    DuplicateSelected,
+    /// Undoes the last edit of the scene
+    Undo,
+    /// Makes the last undone edit again
+    Redo,
 }
 #[derive(Hash, Eq, PartialEq, Copy, Clone)]
 pub enum MoveDirection {
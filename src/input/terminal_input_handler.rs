@@ -1,10 +1,9 @@
 use crate::input::InputAction::{ActionOnScene, ChangeRenderType};
 use crate::input::SceneAction::RotateCamera;
 use crate::input::{InputAction, InputHandler, MoveDirection, SceneAction};
-use crate::image::Image;
 use cgmath::Vector2;
 use crossterm::event;
-use crossterm::event::{Event, KeyCode, KeyEvent, KeyEventKind, MouseEvent, MouseEventKind};
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, MouseButton, MouseEvent, MouseEventKind};
 use std::collections::HashSet;
 use std::time::Duration;
 
@@ -12,6 +11,8 @@ pub struct TerminalInputHandler {
     toggled_actions: HashSet<InputAction>,
     single_time_actions: HashSet<InputAction>,
     last_mouse_pos: Option<(u16, u16)>,
+    resolution: (usize, usize),
+    cells_per_pixel: (f32, f32),
 }
 
 impl InputHandler for TerminalInputHandler {
@@ -24,7 +25,7 @@ impl InputHandler for TerminalInputHandler {
         Iterator::chain(
             self.toggled_actions.iter(),
             self.single_time_actions.iter())
-            .map(|action| *action)
+            .copied()
     }
 
     fn scene_actions(&self) -> impl Iterator<Item=SceneAction>  + '_ {
@@ -50,11 +51,28 @@ impl InputHandler for TerminalInputHandler {
                 self.handle_mouse_event(mouse_event, self.last_mouse_pos);
                 self.last_mouse_pos = Some((mouse_event.row, mouse_event.column));
             }
+            Event::Resize(columns, rows) => {
+                self.single_time_actions.insert(InputAction::Resize { columns, rows });
+            }
             _ => {}
         };
 
         Ok(())
     }
+
+    fn set_resolution(&mut self, width: usize, height: usize) {
+        self.resolution = (width, height);
+    }
+
+    fn set_cells_per_pixel(&mut self, cells_per_pixel: (f32, f32)) {
+        self.cells_per_pixel = cells_per_pixel;
+    }
+}
+
+impl Default for TerminalInputHandler {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl TerminalInputHandler {
@@ -63,17 +81,47 @@ impl TerminalInputHandler {
             toggled_actions: HashSet::new(),
             single_time_actions: HashSet::new(),
             last_mouse_pos: None,
+            resolution: (1, 1),
+            cells_per_pixel: (1.0, 1.0),
         }
     }
 
     fn handle_mouse_event(&mut self, mouse_event: MouseEvent, last_mouse_pos: Option<(u16, u16)>) {
+        let (width, height) = self.resolution;
+        let to_screen_space = |column: u16, row: u16| cell_to_screen_space(column, row, self.cells_per_pixel, width, height)
+            .map(|position| position * 1000.);
+        let scene_action = match mouse_event.kind {
+            // clicks next to the image, e.g. on the fps counter, are ignored
+            MouseEventKind::Down(MouseButton::Left) => to_screen_space(mouse_event.column, mouse_event.row)
+                .map(|position| SceneAction::Select { position: position.map(|x| x.round() as i16) }),
+            MouseEventKind::Drag(MouseButton::Left) => {
+                // the last position is stored as the row and the column
+                let last_position = last_mouse_pos.and_then(|(last_row, last_column)| to_screen_space(last_column, last_row));
+                match (last_position, to_screen_space(mouse_event.column, mouse_event.row)) {
+                    (Some(last_position), Some(position)) => {
+                        let delta = (position - last_position).map(|x| x.round() as i16);
+                        Some(SceneAction::DragSelected { delta })
+                    }
+                    _ => None,
+                }
+            }
+            MouseEventKind::ScrollUp => Some(SceneAction::ScaleSelected { steps: 1 }),
+            MouseEventKind::ScrollDown => Some(SceneAction::ScaleSelected { steps: -1 }),
+            _ => None,
+        };
+        if let Some(scene_action) = scene_action {
+            self.single_time_actions.insert(ActionOnScene(scene_action));
+            return;
+        }
         if mouse_event.kind != MouseEventKind::Moved {
             return;
         }
 
         if let Some((last_x, last_y)) = last_mouse_pos {
-            let last_mouse_pos_screen_space = pixel_to_screen_space(last_x, last_y);
-            let current_mouse_pos_screen_space = pixel_to_screen_space(mouse_event.row, mouse_event.column);
+            let (width, height) = self.resolution;
+            let last_mouse_pos_screen_space = pixel_to_screen_space(last_x as usize, last_y as usize, width, height);
+            let current_mouse_pos_screen_space =
+                pixel_to_screen_space(mouse_event.row as usize, mouse_event.column as usize, width, height);
             let delta = (last_mouse_pos_screen_space - current_mouse_pos_screen_space) * 1000.;
             let delta_int = Vector2::new(delta.x.round() as i16, delta.y.round() as i16);
             let action = ActionOnScene(RotateCamera {
@@ -87,6 +135,22 @@ impl TerminalInputHandler {
             self.single_time_actions.insert(ChangeRenderType);
             return;
         }
+        if key_event.code == KeyCode::Char('p') && key_event.kind == KeyEventKind::Press {
+            self.single_time_actions.insert(InputAction::TogglePathTracing);
+            return;
+        }
+        if let Some(action) = control_action_for_key(key_event) {
+            if key_event.kind == KeyEventKind::Press {
+                self.single_time_actions.insert(action);
+            }
+            return;
+        }
+        if let Some(edit) = edit_for_key(key_event.code) {
+            if key_event.kind == KeyEventKind::Press {
+                self.single_time_actions.insert(ActionOnScene(edit));
+            }
+            return;
+        }
 
         match key_event.kind {
             KeyEventKind::Press => {
@@ -127,12 +191,47 @@ impl TerminalInputHandler {
     }
 }
 
-pub fn pixel_to_screen_space(row: u16, col: u16) -> Vector2<f32> {
-    let pixel_x_middle = row as f32 + 0.5;
-    let pixel_y_middle = col as f32 + 0.5;
+/// The actions of keys pressed with Ctrl, they happen once per key press
+fn control_action_for_key(key_event: KeyEvent) -> Option<InputAction> {
+    if !key_event.modifiers.contains(KeyModifiers::CONTROL) {
+        return None;
+    }
+    match key_event.code {
+        KeyCode::Char('s') => Some(InputAction::SaveScene),
+        KeyCode::Char('z') => Some(ActionOnScene(SceneAction::Undo)),
+        KeyCode::Char('y') => Some(ActionOnScene(SceneAction::Redo)),
+        _ => None,
+    }
+}
+
+/// The changes of the selected shape made with keys, they happen once per key press
+fn edit_for_key(key_code: KeyCode) -> Option<SceneAction> {
+    match key_code {
+        KeyCode::Delete | KeyCode::Backspace | KeyCode::Char('x') => Some(SceneAction::DeleteSelected),
+        KeyCode::Char('v') => Some(SceneAction::DuplicateSelected),
+        KeyCode::Char(']') => Some(SceneAction::CycleMaterial { forward: true }),
+        KeyCode::Char('[') => Some(SceneAction::CycleMaterial { forward: false }),
+        _ => None,
+    }
+}
+
+/// The screen space position of the middle of a terminal cell, `None` if the cell isn't on the image
+/// drawn from the top left corner of the terminal
+pub fn cell_to_screen_space(column: u16, row: u16, cells_per_pixel: (f32, f32), width: usize, height: usize) -> Option<Vector2<f32>> {
+    let x_normalized = (column as f32 + 0.5) / cells_per_pixel.0 / width as f32;
+    let y_normalized = (row as f32 + 0.5) / cells_per_pixel.1 / height as f32;
+    if x_normalized > 1.0 || y_normalized > 1.0 {
+        return None;
+    }
+    Some(Vector2::new(2.0 * x_normalized - 1.0, 1.0 - 2.0 * y_normalized))
+}
+
+pub fn pixel_to_screen_space(x: usize, y: usize, width: usize, height: usize) -> Vector2<f32> {
+    let pixel_x_middle = x as f32 + 0.5;
+    let pixel_y_middle = y as f32 + 0.5;
 
-    let x_normalized = pixel_x_middle / Image::width() as f32;
-    let y_normalized = pixel_y_middle / Image::height() as f32;
+    let x_normalized = pixel_x_middle / width as f32;
+    let y_normalized = pixel_y_middle / height as f32;
 
     let x_screen = 2.0 * x_normalized - 1.0;
     let y_screen = 1.0 - 2.0 * y_normalized;
@@ -0,0 +1,4 @@
+pub mod core;
+pub mod render;
+pub mod input;
+pub mod clock;
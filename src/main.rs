@@ -1,93 +1,238 @@
-#![feature(iterator_try_collect)]
-
-use std::time::Duration;
-use crate::core::camera::Camera;
-use crate::core::light::Light;
-use crate::core::scene::{Scene, TimeProvider};
-use crate::core::image::Image;
-use crate::core::shapes::Sphere;
-use cgmath::{Vector3, Zero};
-use rand::random;
-use crate::clock::Clock;
-use crate::core::image;
-use crate::input::{InputAction, InputHandler};
-use crate::input::terminal_input_handler::TerminalInputHandler;
-use crate::render::{Renderer};
-use crate::render::terminal_renderer::{TerminalRenderType, TerminalRenderer};
-
-mod core;
-mod render;
-mod input;
-mod clock;
-
-fn main() -> std::io::Result<()> {
-    let mut renderer = TerminalRenderer::new(TerminalRenderType::Colored);
+mod cli;
+
+use std::error::Error;
+use std::path::Path;
+use clap::Parser;
+use cli::{Cli, Resolution};
+use std::time::{Duration, Instant};
+use ascii_engine::core::material::Material;
+use ascii_engine::core::obj::load_obj;
+use ascii_engine::core::scene::{Scene, TimeProvider};
+use ascii_engine::core::scene_file::{load_scene, save_scene};
+use ascii_engine::core::scene_generator::{generate_scene, GeneratorSettings};
+use ascii_engine::core::image::Image;
+use ascii_engine::core::path_tracer::PathTracer;
+use rand_chacha::ChaCha8Rng;
+use rand::{random, Rng, SeedableRng};
+use ascii_engine::clock::Clock;
+use ascii_engine::input::{InputAction, InputHandler};
+use ascii_engine::input::terminal_input_handler::TerminalInputHandler;
+use ascii_engine::render::Renderer;
+use ascii_engine::render::file_renderer::FileRenderer;
+use ascii_engine::render::terminal_renderer::TerminalRenderer;
+
+const PATH_TRACING_MAX_BOUNCES: u32 = 8;
+
+fn main() {
+    if let Err(error) = run(Cli::parse()) {
+        eprintln!("error: {}", error);
+        std::process::exit(1);
+    }
+}
+
+fn run(cli: Cli) -> Result<(), Box<dyn Error>> {
+    let seed = cli.seed.unwrap_or_else(random);
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+
+    let mut scene = match &cli.scene {
+        Some(scene_path) => load_scene(scene_path)?,
+        None => {
+            let settings = GeneratorSettings {
+                sphere_count: cli.spheres,
+                palette: cli.palette.into(),
+                non_overlapping: !cli.allow_overlap,
+                ..GeneratorSettings::default()
+            };
+            generate_scene(seed, &settings)
+        }
+    };
+    // the seed of a generated scene is printed so the same scene can be shown again with --seed
+    let print_seed = || if cli.scene.is_none() {
+        eprintln!("scene generated with seed {} (show it again with --seed {})", seed, seed);
+    };
+    if let Some(model_path) = &cli.model {
+        let material = scene.add_material(Material::default());
+        scene.add_shape(load_obj(model_path, material)?);
+    }
+    if cli.camera_position.is_some() || cli.camera_target.is_some() {
+        let camera = scene.camera_mut();
+        let position = cli.camera_position.unwrap_or(camera.position());
+        let target = cli.camera_target.unwrap_or(position + camera.forward());
+        camera.set_position_and_target(position, target);
+    }
+    if let Some(save_path) = &cli.save_scene {
+        save_scene(&scene, save_path)?;
+    }
+
+    const DEFAULT_RESOLUTION: Resolution = Resolution { width: 320, height: 180 };
+    if let Some(frames) = cli.benchmark {
+        print_seed();
+        run_benchmark(&mut scene, cli.size.unwrap_or(DEFAULT_RESOLUTION), frames);
+        return Ok(());
+    }
+    if let Some(output) = &cli.output {
+        let Resolution { width, height } = cli.size.unwrap_or(DEFAULT_RESOLUTION);
+        let mut renderer = FileRenderer::new(output, width, height)?
+            .with_tone_mapping(cli.tone_mapping.into())
+            .with_gamma(cli.gamma);
+        print_seed();
+        render_once(&mut scene, &mut renderer, cli.samples, rng.random())?;
+        return Ok(());
+    }
+
+    let mut renderer = TerminalRenderer::new(cli.render_type.into());
+    renderer.set_max_resolution(cli.size.map(|size| (size.width, size.height)));
+    renderer.set_dithering(cli.dithering.into());
+    renderer.set_color_depth(cli.colors.into());
+    renderer.set_color_dithering(cli.color_dithering);
     let mut input_handler = TerminalInputHandler::new();
+    let frame_duration = if cli.fps_cap == 0 {
+        Duration::ZERO
+    } else {
+        Duration::from_secs_f32(1.0 / cli.fps_cap as f32)
+    };
+
+    let save_path = cli.save_path();
+    let result = run_game(&mut scene, &mut input_handler, &mut renderer, frame_duration, &mut rng, &save_path);
+    // the terminal is cleared when the renderer is dropped, so the seed is printed afterwards
+    drop(renderer);
+    print_seed();
+
+    Ok(result?)
+}
 
-    run_game(&mut input_handler, &mut renderer)?;
+/// Renders a single frame of the scene, either ray traced or path traced with the given number of samples per pixel
+fn render_once(scene: &mut Scene, renderer: &mut impl Renderer, samples: Option<u32>, seed: u64) -> std::io::Result<()> {
+    let (width, height) = renderer.resolution();
+    let mut image = Image::new(width, height);
+    scene.camera_mut().set_aspect_ratio(image.aspect_ratio());
+    scene.tick(std::iter::empty(), &Clock::new());
+
+    match samples {
+        Some(samples) => {
+            let mut path_tracer = PathTracer::new(PATH_TRACING_MAX_BOUNCES, seed);
+            for _ in 0..samples.max(1) {
+                path_tracer.render(scene, &mut image);
+            }
+        }
+        None => image.write(scene),
+    }
+    renderer.render(&image)
+}
+
+/// Renders frames of the animated scene as fast as possible without showing them, and prints the frame times
+fn run_benchmark(scene: &mut Scene, resolution: Resolution, frames: u32) {
+    let mut image = Image::new(resolution.width, resolution.height);
+    scene.camera_mut().set_aspect_ratio(image.aspect_ratio());
+    let mut clock = Clock::new();
+    let mut frame_times = Vec::new();
+
+    for _ in 0..frames.max(1) {
+        let frame_start = Instant::now();
+        clock.tick();
+        scene.tick(std::iter::empty(), &clock);
+        image.write(scene);
+        frame_times.push(frame_start.elapsed());
+    }
 
-    Ok(())
+    frame_times.sort();
+    let total: Duration = frame_times.iter().sum();
+    let milliseconds = |duration: Duration| duration.as_secs_f64() * 1000.0;
+    println!("{} frames at {}x{} in {:.2} s", frame_times.len(), resolution.width, resolution.height, total.as_secs_f64());
+    println!("mean:   {:8.2} ms ({:.1} fps)", milliseconds(total) / frame_times.len() as f64,
+             frame_times.len() as f64 / total.as_secs_f64());
+    println!("median: {:8.2} ms", milliseconds(frame_times[frame_times.len() / 2]));
+    println!("min:    {:8.2} ms", milliseconds(frame_times[0]));
+    println!("max:    {:8.2} ms", milliseconds(frame_times[frame_times.len() - 1]));
 }
+
 fn run_game(
+    scene: &mut Scene,
     input_handler: &mut impl InputHandler,
     renderer: &mut impl Renderer,
+    frame_duration: Duration,
+    rng: &mut impl Rng,
+    save_path: &Path,
 ) -> std::io::Result<()> {
 
-    let mut screen_image = Image::new([0.0; image::PIXEL_COUNT]);
-    let mut scene = create_scene();
+    let mut screen_image = Image::new(0, 0);
+    let (width, height) = renderer.resolution();
+    resize_screen(&mut screen_image, scene, input_handler, width, height);
     let mut game_clock: Clock = Clock::new();
     let mut fps_update_clock: Clock = Clock::new();
-    const FPS_CAP: u16 = 144;
-    let frame_duration: Duration = Duration::from_secs_f32(1.0 / FPS_CAP as f32);
+    let mut path_tracer: Option<PathTracer> = None;
+    let mut frame_start = Instant::now();
+    // the outcome of the last save, shown next to the fps
+    let mut status = String::new();
+    // the bytes the renderer wrote since the fps were shown, None for renderers that don't count them
+    let mut frame_bytes: Option<usize> = None;
 
     loop {
-        input_handler.poll_event(&frame_duration)?;
+        // events are waited for until the frame time is over, so the frame rate stays under the cap
+        input_handler.poll_event(&frame_duration.saturating_sub(frame_start.elapsed()))?;
+        std::thread::sleep(frame_duration.saturating_sub(frame_start.elapsed()));
+        frame_start = Instant::now();
         if input_handler.contains_input(InputAction::Quit) { break Ok(()); }
+        if input_handler.contains_input(InputAction::TogglePathTracing) {
+            // the animations are paused while path tracing, so the samples of a still scene can add up
+            path_tracer = match path_tracer {
+                Some(_) => None,
+                None => Some(PathTracer::new(PATH_TRACING_MAX_BOUNCES, rng.random())),
+            };
+            game_clock.set_paused(path_tracer.is_some());
+        }
+        if input_handler.contains_input(InputAction::SaveScene) {
+            status = match save_scene(scene, save_path) {
+                Ok(()) => format!("saved to {}", save_path.display()),
+                Err(error) => format!("saving failed: {}", error),
+            };
+        }
         game_clock.tick();
         fps_update_clock.tick();
 
         scene.tick(input_handler.scene_actions(), &game_clock);
         renderer.tick(input_handler.input_actions());
-        screen_image.write(&scene);
-
-        display_fps(&mut fps_update_clock);
-
-        renderer.render(&screen_image);
+        input_handler.set_cells_per_pixel(renderer.cells_per_pixel());
+        let (width, height) = renderer.resolution();
+        if (width, height) != (screen_image.width(), screen_image.height()) {
+            resize_screen(&mut screen_image, scene, input_handler, width, height);
+        }
+        match &mut path_tracer {
+            Some(path_tracer) => path_tracer.render(scene, &mut screen_image),
+            None => screen_image.write(scene),
+        }
+
+        display_fps(&mut fps_update_clock, &mut frame_bytes, &status);
+
+        renderer.render(&screen_image)?;
+        if let Some(size) = renderer.last_frame_size() {
+            *frame_bytes.get_or_insert(0) += size;
+        }
     }
 }
-fn create_scene() -> Scene {
-    let camera = Camera::from_position_and_target(Vector3::zero(), -Vector3::unit_z());
-    let mut scene = Scene::new(camera);
-    let x_range: (f32, f32) = (-5., 5.);
-    let y_range: (f32, f32) = (-5., 5.);
-    let z_range: (f32, f32) = (-5., 5.);
-    let radius_range: (f32, f32) = (0.75, 1.5);
-
-    scene.add_sphere(Sphere::new(Vector3::new(0.0, 0.0, -6.0), 0.1, Vector3::new(2., 2., 2.)));
-    scene.add_light(Light::from_position(Vector3::new(0.0, 1.0, -5.0)));
-
-    for _ in 0..10 {
-        let x = random::<f32>() * (x_range.0 - x_range.1).abs() + x_range.0;
-        let y = random::<f32>() * (y_range.0 - y_range.1).abs() + y_range.0;
-        let z = random::<f32>() * (z_range.0 - z_range.1).abs() + z_range.0;
-        let radius = random::<f32>() * (radius_range.0 - radius_range.1).abs() + radius_range.0;
-
-        let r = random::<f32>();
-        let g = random::<f32>();
-        let b = random::<f32>();
-
-        scene.add_sphere(Sphere::new(Vector3::new(x, y, z), radius, Vector3::new(r, g, b)));
-    }
-
-    scene
+fn resize_screen(
+    screen_image: &mut Image,
+    scene: &mut Scene,
+    input_handler: &mut impl InputHandler,
+    width: usize,
+    height: usize,
+) {
+    screen_image.resize(width, height);
+    scene.camera_mut().set_aspect_ratio(screen_image.aspect_ratio());
+    input_handler.set_resolution(screen_image.width(), screen_image.height());
 }
-
-fn display_fps(fps_update_clock: &mut Clock) {
+fn display_fps(fps_update_clock: &mut Clock, frame_bytes: &mut Option<usize>, status: &str) {
     const FPS_UPDATE_TIME: Duration = Duration::from_millis(1000);
     let total_time = *fps_update_clock.total_time();
     if total_time > FPS_UPDATE_TIME {
-        let avg_fps = fps_update_clock.tick_count() as f32 / total_time.as_secs_f32();
-        println!("FPS: {}", avg_fps.round() as u16);
+        let tick_count = fps_update_clock.tick_count();
+        let avg_fps = tick_count as f32 / total_time.as_secs_f32();
+        // the average size of the frames written to the terminal, which shows what redrawing only the changes saves
+        let avg_size = frame_bytes.take()
+            .map(|bytes| format!("{:>7.1} KB/frame ", bytes as f32 / tick_count.max(1) as f32 / 1024.0))
+            .unwrap_or_default();
+        // the renderer leaves the cursor on the row below the image
+        print!("FPS: {:<5} {}{}", avg_fps.round() as u16, avg_size, status);
         fps_update_clock.reset();
     }
 }
\ No newline at end of file
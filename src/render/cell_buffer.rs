@@ -0,0 +1,168 @@
+//! The characters of a terminal with their colors, so a frame can be drawn by writing only what changed
+//! since the frame before
+
+use crate::render::color::TerminalColor;
+use std::fmt::Write;
+
+/// The colors and attributes of a character, `None` is the default color of the terminal
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub struct Style {
+    pub foreground: Option<TerminalColor>,
+    pub background: Option<TerminalColor>,
+    /// Swaps the foreground and the background color
+    pub reversed: bool,
+}
+
+/// A character of the terminal, it must be one column wide
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Cell {
+    pub symbol: char,
+    pub style: Style,
+}
+
+impl Default for Cell {
+    fn default() -> Cell {
+        Cell { symbol: ' ', style: Style::default() }
+    }
+}
+
+/// The cells of the terminal from its top left corner, in rows
+#[derive(Clone, Default)]
+pub struct CellBuffer {
+    width: usize,
+    height: usize,
+    cells: Vec<Cell>,
+}
+
+impl CellBuffer {
+    pub fn new(width: usize, height: usize) -> CellBuffer {
+        CellBuffer { width, height, cells: vec![Cell::default(); width * height] }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Changes the size, and makes every cell blank
+    pub fn reset(&mut self, width: usize, height: usize) {
+        self.width = width;
+        self.height = height;
+        self.cells.clear();
+        self.cells.resize(width * height, Cell::default());
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, cell: Cell) {
+        self.cells[y * self.width + x] = cell;
+    }
+
+    /// Appends the escape sequences and characters that turn a terminal showing `front` into one showing
+    /// this buffer. The cursor is only moved over cells that didn't change, and the style only set where it
+    /// differs from the cell before. Everything is written without a front buffer of the same size,
+    /// e.g. after the terminal was cleared. The style is the default one before and after.
+    pub fn write_changes(&self, front: Option<&CellBuffer>, out: &mut String) {
+        let front = front.filter(|front| (front.width, front.height) == (self.width, self.height));
+        let mut style = Style::default();
+        // where the next character is written, None where it isn't known, e.g. at the right edge of the terminal
+        let mut cursor = None;
+        for (index, cell) in self.cells.iter().enumerate() {
+            if front.is_some_and(|front| front.cells[index] == *cell) {
+                continue;
+            }
+            let (x, y) = (index % self.width, index / self.width);
+            match cursor {
+                Some((cursor_x, cursor_y)) if (cursor_x, cursor_y) == (x, y) => {}
+                Some((cursor_x, cursor_y)) if cursor_y == y && cursor_x < x => {
+                    let _ = write!(out, "\x1b[{}C", x - cursor_x);
+                }
+                _ => {
+                    let _ = write!(out, "\x1b[{};{}H", y + 1, x + 1);
+                }
+            }
+            write_style_change(style, cell.style, out);
+            style = cell.style;
+            out.push(cell.symbol);
+            cursor = (x + 1 < self.width).then_some((x + 1, y));
+        }
+        write_style_change(style, Style::default(), out);
+    }
+}
+
+// the SGR escape sequence that changes only the parts of the style that differ
+fn write_style_change(from: Style, to: Style, out: &mut String) {
+    if from == to {
+        return;
+    }
+    // every parameter ends with a separator, the last one is removed
+    let mut parameters = String::new();
+    if from.reversed != to.reversed {
+        parameters.push_str(if to.reversed { "7;" } else { "27;" });
+    }
+    if from.foreground != to.foreground {
+        match to.foreground {
+            Some(color) => color.write_foreground(&mut parameters),
+            None => parameters.push_str("39"),
+        }
+        parameters.push(';');
+    }
+    if from.background != to.background {
+        match to.background {
+            Some(color) => color.write_background(&mut parameters),
+            None => parameters.push_str("49"),
+        }
+        parameters.push(';');
+    }
+    parameters.pop();
+    let _ = write!(out, "\x1b[{}m", parameters);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn colored(symbol: char, color: TerminalColor) -> Cell {
+        Cell { symbol, style: Style { background: Some(color), ..Style::default() } }
+    }
+
+    fn buffer(cells: &[Cell], width: usize) -> CellBuffer {
+        let mut buffer = CellBuffer::new(width, cells.len() / width);
+        for (index, &cell) in cells.iter().enumerate() {
+            buffer.set(index % width, index / width, cell);
+        }
+        buffer
+    }
+
+    fn changes(back: &CellBuffer, front: Option<&CellBuffer>) -> String {
+        let mut out = String::new();
+        back.write_changes(front, &mut out);
+        out
+    }
+
+    #[test]
+    fn the_style_is_only_set_where_it_changes() {
+        let red = colored(' ', TerminalColor::Ansi16(1));
+        let blue = colored(' ', TerminalColor::Ansi16(4));
+        let back = buffer(&[red, red, blue, blue, Cell::default(), red], 3);
+        assert_eq!(changes(&back, None), "\x1b[1;1H\x1b[41m  \x1b[44m \x1b[2;1H \x1b[49m \x1b[41m \x1b[49m");
+    }
+
+    #[test]
+    fn only_changed_cells_are_written() {
+        let red = colored(' ', TerminalColor::Ansi256(196));
+        let front = buffer(&[Cell::default(); 8], 4);
+        assert_eq!(changes(&front, Some(&front)), "");
+
+        let mut back = front.clone();
+        back.set(1, 0, red);
+        back.set(3, 0, Cell { symbol: 'x', style: Style { reversed: true, ..Style::default() } });
+        back.set(0, 1, Cell { symbol: 'y', ..Cell::default() });
+        assert_eq!(changes(&back, Some(&front)), "\x1b[1;2H\x1b[48;5;196m \x1b[1C\x1b[7;49mx\x1b[2;1H\x1b[27my");
+
+        // a buffer of another size is drawn completely
+        let smaller = buffer(&[Cell::default(); 4], 4);
+        assert_eq!(changes(&smaller, Some(&front)), "\x1b[1;1H    ");
+    }
+}
@@ -0,0 +1,240 @@
+//! The colors a terminal can show, and the quantization of colors into the palettes of terminals without true color
+
+use crate::render::dithering::diffuse_errors;
+use cgmath::Vector3;
+use std::fmt::Write;
+
+/// How many colors a terminal can show
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColorDepth {
+    /// Any 24 bit color
+    TrueColor,
+    /// The 256 colors of xterm
+    Ansi256,
+    /// The 16 colors of the Linux console and old terminals, the actual colors depend on the theme of the terminal
+    Ansi16,
+}
+
+impl ColorDepth {
+    /// The colors the terminal supports, according to the COLORTERM and TERM environment variables
+    pub fn detect() -> ColorDepth {
+        let variable = |name: &str| std::env::var(name).ok();
+        ColorDepth::from_environment(variable("COLORTERM").as_deref(), variable("TERM").as_deref())
+    }
+
+    fn from_environment(colorterm: Option<&str>, term: Option<&str>) -> ColorDepth {
+        if matches!(colorterm, Some("truecolor" | "24bit")) {
+            return ColorDepth::TrueColor;
+        }
+        match term {
+            // terminals on Windows don't set TERM, and support true color
+            None => ColorDepth::TrueColor,
+            Some(term) if term.contains("direct") || term.contains("truecolor") => ColorDepth::TrueColor,
+            Some(term) if term.contains("256color") => ColorDepth::Ansi256,
+            Some(_) => ColorDepth::Ansi16,
+        }
+    }
+}
+
+/// A color as it is sent to the terminal
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TerminalColor {
+    Rgb(u8, u8, u8),
+    /// A color of the xterm palette of 256 colors
+    Ansi256(u8),
+    /// One of the 16 colors, the bright ones start at 8
+    Ansi16(u8),
+}
+
+impl TerminalColor {
+    /// Appends the parameters of an SGR escape sequence that make this the color of the text
+    pub fn write_foreground(&self, sgr: &mut String) {
+        let _ = match *self {
+            TerminalColor::Rgb(r, g, b) => write!(sgr, "38;2;{};{};{}", r, g, b),
+            TerminalColor::Ansi256(index) => write!(sgr, "38;5;{}", index),
+            TerminalColor::Ansi16(index) if index < 8 => write!(sgr, "{}", 30 + index),
+            TerminalColor::Ansi16(index) => write!(sgr, "{}", 90 + index - 8),
+        };
+    }
+
+    /// Appends the parameters of an SGR escape sequence that make this the background color
+    pub fn write_background(&self, sgr: &mut String) {
+        let _ = match *self {
+            TerminalColor::Rgb(r, g, b) => write!(sgr, "48;2;{};{};{}", r, g, b),
+            TerminalColor::Ansi256(index) => write!(sgr, "48;5;{}", index),
+            TerminalColor::Ansi16(index) if index < 8 => write!(sgr, "{}", 40 + index),
+            TerminalColor::Ansi16(index) => write!(sgr, "{}", 100 + index - 8),
+        };
+    }
+}
+
+// the 16 colors as xterm shows them by default
+const ANSI_16_COLORS: [(u8, u8, u8); 16] = [
+    (0, 0, 0), (205, 0, 0), (0, 205, 0), (205, 205, 0), (0, 0, 238), (205, 0, 205), (0, 205, 205), (229, 229, 229),
+    (127, 127, 127), (255, 0, 0), (0, 255, 0), (255, 255, 0), (92, 92, 255), (255, 0, 255), (0, 255, 255), (255, 255, 255),
+];
+// the levels of each channel in the 6x6x6 color cube of the 256 colors
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+// the 256 colors are looked up by the top bits of each channel
+const LOOKUP_BITS: u32 = 5;
+
+/// The colors of a terminal without true color, with a table of the nearest palette color for any color
+pub struct TerminalPalette {
+    depth: ColorDepth,
+    // the palette colors with their index in the terminal
+    colors: Vec<(u8, (u8, u8, u8))>,
+    // the index in `colors` of the nearest color, by the top bits of each channel
+    lookup: Vec<u8>,
+}
+
+impl TerminalPalette {
+    /// The palette of the color depth, `None` for true color, which needs none
+    pub fn new(depth: ColorDepth) -> Option<TerminalPalette> {
+        let colors: Vec<(u8, (u8, u8, u8))> = match depth {
+            ColorDepth::TrueColor => return None,
+            ColorDepth::Ansi16 => (0..).zip(ANSI_16_COLORS).collect(),
+            // the first 16 colors are left out, many terminals change them with their theme
+            ColorDepth::Ansi256 => {
+                let cube = (0..216).map(|index: usize| {
+                    let level = |digit: usize| CUBE_LEVELS[digit % 6];
+                    (level(index / 36), level(index / 6), level(index))
+                });
+                let grays = (0..24).map(|index: u8| (8 + index * 10, 8 + index * 10, 8 + index * 10));
+                (16..=255).zip(cube.chain(grays)).collect()
+            }
+        };
+        let colors_in_oklab: Vec<Vector3<f32>> = colors.iter().map(|&(_, color)| oklab(color)).collect();
+        let levels = 1 << LOOKUP_BITS;
+        let lookup = (0..levels * levels * levels)
+            .map(|index| {
+                // the top bits are spread over the whole range, so black and white are looked up exactly
+                let channel = |shift: u32| (((index >> shift) & (levels - 1)) * 255 / (levels - 1)) as u8;
+                let color = oklab((channel(2 * LOOKUP_BITS), channel(LOOKUP_BITS), channel(0)));
+                nearest(&colors_in_oklab, color) as u8
+            })
+            .collect();
+        Some(TerminalPalette { depth, colors, lookup })
+    }
+
+    /// The palette color that looks most like the color
+    pub fn nearest(&self, (r, g, b): (u8, u8, u8)) -> TerminalColor {
+        let top_bits = |channel: u8| (channel >> (8 - LOOKUP_BITS)) as usize;
+        let index = (top_bits(r) << (2 * LOOKUP_BITS)) | (top_bits(g) << LOOKUP_BITS) | top_bits(b);
+        self.color(self.lookup[index] as usize)
+    }
+
+    /// The palette colors that look most like the colors of an image with the given width. With dithering
+    /// the difference to each color is made up by the colors around it, so areas keep their color on average.
+    pub fn quantize(&self, colors: &[(u8, u8, u8)], width: usize, dithering: bool) -> Vec<TerminalColor> {
+        if !dithering || width == 0 {
+            return colors.iter().map(|&color| self.nearest(color)).collect();
+        }
+        let to_u8 = |channel: f32| channel.round().clamp(0.0, 255.0) as u8;
+        let wanted: Vec<Vector3<f32>> = colors.iter().map(|&(r, g, b)| Vector3::new(r as f32, g as f32, b as f32)).collect();
+        diffuse_errors(&wanted, width, |color| {
+            let quantized = self.nearest((to_u8(color.x), to_u8(color.y), to_u8(color.z)));
+            let (r, g, b) = self.rgb(quantized);
+            (quantized, color - Vector3::new(r as f32, g as f32, b as f32))
+        })
+    }
+
+    // the color with the index in `colors`
+    fn color(&self, index: usize) -> TerminalColor {
+        let terminal_index = self.colors[index].0;
+        match self.depth {
+            ColorDepth::Ansi16 => TerminalColor::Ansi16(terminal_index),
+            ColorDepth::Ansi256 | ColorDepth::TrueColor => TerminalColor::Ansi256(terminal_index),
+        }
+    }
+
+    // how the terminal shows a color of this palette
+    fn rgb(&self, color: TerminalColor) -> (u8, u8, u8) {
+        match color {
+            TerminalColor::Rgb(r, g, b) => (r, g, b),
+            TerminalColor::Ansi16(index) => ANSI_16_COLORS[index as usize],
+            TerminalColor::Ansi256(index) => self.colors[index as usize - 16].1,
+        }
+    }
+}
+
+// the index of the color closest to the color, all in Oklab
+fn nearest(colors: &[Vector3<f32>], color: Vector3<f32>) -> usize {
+    let distance = |other: &Vector3<f32>| {
+        let difference = other - color;
+        difference.x * difference.x + difference.y * difference.y + difference.z * difference.z
+    };
+    (0..colors.len()).min_by(|&a, &b| distance(&colors[a]).total_cmp(&distance(&colors[b]))).unwrap_or(0)
+}
+
+/// The color in Oklab, where the distance between colors follows how different they look
+fn oklab((r, g, b): (u8, u8, u8)) -> Vector3<f32> {
+    let linear = |channel: u8| {
+        let channel = channel as f32 / 255.0;
+        if channel <= 0.04045 { channel / 12.92 } else { ((channel + 0.055) / 1.055).powf(2.4) }
+    };
+    let (r, g, b) = (linear(r), linear(g), linear(b));
+    let l = (0.412_221_46 * r + 0.536_332_55 * g + 0.051_445_995 * b).cbrt();
+    let m = (0.211_903_5 * r + 0.680_699_5 * g + 0.107_396_96 * b).cbrt();
+    let s = (0.088_302_46 * r + 0.281_718_85 * g + 0.629_978_7 * b).cbrt();
+    Vector3::new(
+        0.210_454_26 * l + 0.793_617_8 * m - 0.004_072_047 * s,
+        1.977_998_5 * l - 2.428_592_2 * m + 0.450_593_7 * s,
+        0.025_904_037 * l + 0.782_771_77 * m - 0.808_675_77 * s,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn color_depth_is_detected_from_the_environment() {
+        assert_eq!(ColorDepth::from_environment(Some("truecolor"), Some("xterm-256color")), ColorDepth::TrueColor);
+        assert_eq!(ColorDepth::from_environment(None, Some("tmux-256color")), ColorDepth::Ansi256);
+        assert_eq!(ColorDepth::from_environment(None, Some("linux")), ColorDepth::Ansi16);
+        assert_eq!(ColorDepth::from_environment(None, Some("xterm-direct")), ColorDepth::TrueColor);
+        assert_eq!(ColorDepth::from_environment(None, None), ColorDepth::TrueColor);
+    }
+
+    #[test]
+    fn colors_are_written_as_sgr_parameters() {
+        let sgr = |color: TerminalColor| {
+            let mut sgr = String::new();
+            color.write_foreground(&mut sgr);
+            sgr.push(';');
+            color.write_background(&mut sgr);
+            sgr
+        };
+        assert_eq!(sgr(TerminalColor::Rgb(1, 2, 3)), "38;2;1;2;3;48;2;1;2;3");
+        assert_eq!(sgr(TerminalColor::Ansi256(196)), "38;5;196;48;5;196");
+        assert_eq!(sgr(TerminalColor::Ansi16(1)), "31;41");
+        assert_eq!(sgr(TerminalColor::Ansi16(9)), "91;101");
+    }
+
+    #[test]
+    fn nearest_colors_are_picked_by_how_they_look() {
+        let palette = TerminalPalette::new(ColorDepth::Ansi256).unwrap();
+        assert_eq!(palette.nearest((255, 0, 0)), TerminalColor::Ansi256(196));
+        assert_eq!(palette.nearest((0, 0, 0)), TerminalColor::Ansi256(16));
+        // a gray between two levels of the cube is closer to one of the grays
+        assert_eq!(palette.nearest((118, 118, 118)), TerminalColor::Ansi256(243));
+
+        let palette = TerminalPalette::new(ColorDepth::Ansi16).unwrap();
+        assert_eq!(palette.nearest((250, 250, 250)), TerminalColor::Ansi16(15));
+        assert_eq!(palette.nearest((20, 30, 200)), TerminalColor::Ansi16(4));
+        assert!(TerminalPalette::new(ColorDepth::TrueColor).is_none());
+    }
+
+    #[test]
+    fn dithering_keeps_the_average_color() {
+        let palette = TerminalPalette::new(ColorDepth::Ansi16).unwrap();
+        let orange = (230, 120, 20);
+        let width = 16;
+        let dithered = palette.quantize(&vec![orange; width * 16], width, true);
+        let average_red = dithered.iter().map(|&color| palette.rgb(color).0 as f32).sum::<f32>() / dithered.len() as f32;
+        assert!((average_red - orange.0 as f32).abs() < 10.0, "average red {}", average_red);
+        // without dithering every pixel gets the same color
+        let quantized = palette.quantize(&vec![orange; width], width, false);
+        assert!(quantized.iter().all(|&color| color == quantized[0]));
+    }
+}
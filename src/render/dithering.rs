@@ -0,0 +1,99 @@
+//! Turning intensities into dots that are either drawn or not, so that areas keep their brightness
+//! on average, e.g. for the braille patterns of the terminal renderer and for the palettes of terminals without true color.
+
+use std::ops::{Add, Mul};
+
+/// How intensities between black and white are spread over dots
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Dithering {
+    /// Compares each dot with a threshold from a repeating 4x4 Bayer matrix, which gives a regular pattern
+    /// that stays in place while the image changes
+    #[default]
+    Ordered,
+    /// Floyd–Steinberg error diffusion: the error of each dot is passed on to the following dots,
+    /// which keeps more detail but makes the pattern shimmer in animations
+    ErrorDiffusion,
+}
+
+// the thresholds of ordered dithering, in sixteenths
+const BAYER_MATRIX: [[u8; 4]; 4] = [
+    [0, 8, 2, 10],
+    [12, 4, 14, 6],
+    [3, 11, 1, 9],
+    [15, 7, 13, 5],
+];
+
+/// Whether each dot of an image with the given width is drawn, for intensities from 0 (never) to 1 (always).
+/// The intensities are in rows from the top left.
+pub fn dither(intensities: &[f32], width: usize, dithering: Dithering) -> Vec<bool> {
+    if width == 0 {
+        return Vec::new();
+    }
+    match dithering {
+        Dithering::Ordered => intensities.iter().enumerate()
+            .map(|(index, &intensity)| {
+                let threshold = BAYER_MATRIX[(index / width) % 4][(index % width) % 4] as f32 + 0.5;
+                intensity * 16.0 > threshold
+            })
+            .collect(),
+        Dithering::ErrorDiffusion => {
+            let intensities: Vec<f32> = intensities.iter().map(|intensity| intensity.clamp(0.0, 1.0)).collect();
+            diffuse_errors(&intensities, width, |intensity| {
+                let drawn = intensity > 0.5;
+                (drawn, intensity - if drawn { 1.0 } else { 0.0 })
+            })
+        }
+    }
+}
+
+/// Floyd–Steinberg error diffusion over values in rows of the given width. `quantize` picks what is drawn
+/// for a value and returns it with the error, which is passed on to the values to the right and below.
+pub fn diffuse_errors<V, D>(values: &[V], width: usize, mut quantize: impl FnMut(V) -> (D, V)) -> Vec<D>
+where V: Copy + Add<Output=V> + Mul<f32, Output=V> {
+    let mut wanted = values.to_vec();
+    let mut drawn = Vec::with_capacity(wanted.len());
+    for index in 0..wanted.len() {
+        let (value, error) = quantize(wanted[index]);
+        drawn.push(value);
+
+        let x = index % width;
+        let mut pass_on = |target: usize, weight: f32| if let Some(value) = wanted.get_mut(target) {
+            *value = *value + error * weight;
+        };
+        if x + 1 < width {
+            pass_on(index + 1, 7.0 / 16.0);
+            pass_on(index + width + 1, 1.0 / 16.0);
+        }
+        if x > 0 {
+            pass_on(index + width - 1, 3.0 / 16.0);
+        }
+        pass_on(index + width, 5.0 / 16.0);
+    }
+    drawn
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn drawn_share(dots: &[bool]) -> f32 {
+        dots.iter().filter(|&&drawn| drawn).count() as f32 / dots.len() as f32
+    }
+
+    #[test]
+    fn dots_keep_the_brightness() {
+        let width = 16;
+        for dithering in [Dithering::Ordered, Dithering::ErrorDiffusion] {
+            for intensity in [0.0, 0.25, 0.5, 0.8, 1.0] {
+                let dots = dither(&vec![intensity; width * 8], width, dithering);
+                assert!((drawn_share(&dots) - intensity).abs() < 0.05, "{:?} of {}", dithering, intensity);
+            }
+        }
+    }
+
+    #[test]
+    fn ordered_dots_make_a_regular_pattern() {
+        let dots = dither(&[0.5; 8], 4, Dithering::Ordered);
+        assert_eq!(dots, [true, false, true, false, false, true, false, true]);
+    }
+}
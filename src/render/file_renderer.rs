@@ -0,0 +1,193 @@
+use crate::core::image::Image;
+use crate::input::InputAction;
+use crate::render::Renderer;
+use cgmath::Vector3;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ImageFormat {
+    Ppm, Png
+}
+
+impl ImageFormat {
+    /// The format belonging to the extension of the path, `None` if it isn't `.ppm` or `.png`
+    pub fn from_path(path: &Path) -> Option<ImageFormat> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "ppm" => Some(ImageFormat::Ppm),
+            "png" => Some(ImageFormat::Png),
+            _ => None,
+        }
+    }
+}
+
+/// How colors brighter than white are brought into the displayable range
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ToneMapping {
+    /// Every channel is cut off at 1, like in the terminal
+    Clamp,
+    /// `c / (1 + c)`, which never quite reaches white
+    Reinhard,
+    /// The filmic curve of the ACES standard, in the fit of Krzysztof Narkowicz
+    Aces,
+}
+
+impl ToneMapping {
+    pub fn apply(&self, color: Vector3<f32>) -> Vector3<f32> {
+        let map_channel = |c: f32| {
+            let c = c.max(0.0);
+            let mapped = match self {
+                ToneMapping::Clamp => c,
+                ToneMapping::Reinhard => c / (1.0 + c),
+                ToneMapping::Aces => (c * (2.51 * c + 0.03)) / (c * (2.43 * c + 0.59) + 0.14),
+            };
+            mapped.min(1.0)
+        };
+        Vector3::new(map_channel(color.x), map_channel(color.y), map_channel(color.z))
+    }
+}
+
+/// Renders into an image file instead of the terminal, so the engine can be used without a tty.
+/// Every call to `render` overwrites the file with the new frame.
+pub struct FileRenderer {
+    path: PathBuf,
+    format: ImageFormat,
+    resolution: (usize, usize),
+    tone_mapping: ToneMapping,
+    gamma: f32,
+}
+
+impl Renderer for FileRenderer {
+    fn tick(&mut self, _actions: impl Iterator<Item=InputAction>) {}
+
+    fn resolution(&self) -> (usize, usize) {
+        self.resolution
+    }
+
+    fn render(&mut self, image: &Image) -> std::io::Result<()> {
+        let rgb = self.encode(image);
+        let (width, height) = (image.width(), image.height());
+        let mut writer = BufWriter::new(File::create(&self.path)?);
+        match self.format {
+            ImageFormat::Ppm => write_ppm(&mut writer, width, height, &rgb)?,
+            ImageFormat::Png => write_png(&mut writer, width, height, &rgb)?,
+        }
+        writer.flush()
+    }
+}
+
+impl FileRenderer {
+    /// The format of the file is chosen by the extension of the path
+    pub fn new(path: impl Into<PathBuf>, width: usize, height: usize) -> std::io::Result<FileRenderer> {
+        let path = path.into();
+        let format = ImageFormat::from_path(&path).ok_or_else(|| std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("can't tell the image format of {}, use a .ppm or .png extension", path.display()),
+        ))?;
+        Ok(FileRenderer {
+            path,
+            format,
+            resolution: (width.max(1), height.max(1)),
+            tone_mapping: ToneMapping::Clamp,
+            gamma: 1.0,
+        })
+    }
+
+    pub fn with_tone_mapping(mut self, tone_mapping: ToneMapping) -> FileRenderer {
+        self.tone_mapping = tone_mapping;
+        self
+    }
+
+    /// The colors are raised to the power of `1 / gamma` after tone mapping, 1 leaves them unchanged.
+    /// Panics unless the gamma is a finite number greater than 0.
+    pub fn with_gamma(mut self, gamma: f32) -> FileRenderer {
+        assert!(gamma.is_finite() && gamma > 0.0, "the gamma must be greater than 0, not {}", gamma);
+        self.gamma = gamma;
+        self
+    }
+
+    pub fn format(&self) -> ImageFormat {
+        self.format
+    }
+
+    /// The tone mapped and gamma corrected pixels of the image as 8 bit rgb triples, row by row
+    pub fn encode(&self, image: &Image) -> Vec<u8> {
+        let inverse_gamma = 1.0 / self.gamma;
+        image.pixels()
+            .iter()
+            .flat_map(|pixel| {
+                let color = self.tone_mapping.apply(pixel.linear_color());
+                [color.x, color.y, color.z].map(|c| (c.powf(inverse_gamma) * 255.0).round() as u8)
+            })
+            .collect()
+    }
+}
+
+/// Writes a binary (P6) portable pixmap
+pub fn write_ppm(writer: &mut impl Write, width: usize, height: usize, rgb: &[u8]) -> std::io::Result<()> {
+    write!(writer, "P6\n{} {}\n255\n", width, height)?;
+    writer.write_all(rgb)
+}
+
+pub fn write_png(writer: &mut impl Write, width: usize, height: usize, rgb: &[u8]) -> std::io::Result<()> {
+    let mut encoder = png::Encoder::new(writer, width as u32, height as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut png_writer = encoder.write_header()?;
+    png_writer.write_image_data(rgb)?;
+    Ok(png_writer.finish()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_is_chosen_by_extension() {
+        assert_eq!(ImageFormat::from_path(Path::new("out/frame.PNG")), Some(ImageFormat::Png));
+        assert_eq!(ImageFormat::from_path(Path::new("frame.ppm")), Some(ImageFormat::Ppm));
+        assert_eq!(ImageFormat::from_path(Path::new("frame.jpg")), None);
+        assert!(FileRenderer::new("frame", 1, 1).is_err());
+    }
+
+    #[test]
+    fn tone_mapping_keeps_colors_in_range() {
+        let bright = Vector3::new(0.0, 1.0, 50.0);
+        for tone_mapping in [ToneMapping::Clamp, ToneMapping::Reinhard, ToneMapping::Aces] {
+            let mapped = tone_mapping.apply(bright);
+            assert_eq!(mapped.x, 0.0);
+            assert!(mapped.y <= mapped.z && mapped.z <= 1.0);
+        }
+        assert_eq!(ToneMapping::Reinhard.apply(bright).y, 0.5);
+    }
+
+    #[test]
+    fn encodes_with_gamma() {
+        let mut image = Image::new(2, 1);
+        image.fill(|col, _| Vector3::new(0.25, 2.0, col as f32));
+        let renderer = FileRenderer::new("frame.ppm", 2, 1).unwrap().with_gamma(2.0);
+        assert_eq!(renderer.encode(&image), vec![128, 255, 0, 128, 255, 255]);
+    }
+
+    #[test]
+    fn writes_ppm_header_and_pixels() {
+        let mut bytes = Vec::new();
+        write_ppm(&mut bytes, 2, 1, &[1, 2, 3, 4, 5, 6]).unwrap();
+        assert_eq!(bytes, b"P6\n2 1\n255\n\x01\x02\x03\x04\x05\x06");
+    }
+
+    #[test]
+    fn writes_decodable_png() {
+        let rgb = [10, 20, 30, 40, 50, 60];
+        let mut bytes = Vec::new();
+        write_png(&mut bytes, 1, 2, &rgb).unwrap();
+
+        let mut reader = png::Decoder::new(bytes.as_slice()).read_info().unwrap();
+        let mut decoded = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut decoded).unwrap();
+        assert_eq!((info.width, info.height), (1, 2));
+        assert_eq!(&decoded[..info.buffer_size()], &rgb);
+    }
+}
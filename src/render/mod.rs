@@ -1,7 +1,11 @@
+pub mod cell_buffer;
+pub mod color;
+pub mod dithering;
+pub mod file_renderer;
 pub mod terminal_renderer;
 
 use crate::input::InputAction;
-use crate::image::Image;
+use crate::core::image::Image;
 
 const PIXEL_TYPE_COUNT: usize = 21;
 const PIXEL_TYPES: [&str; PIXEL_TYPE_COUNT] = [
@@ -19,5 +23,15 @@ const PIXEL_TYPES: [&str; PIXEL_TYPE_COUNT] = [
 ];
 pub trait Renderer {
     fn tick(&mut self, actions: impl Iterator<Item=InputAction>);
-    fn render(&mut self, image: &Image);
+    /// The resolution of the image this renderer expects to be given in `render`.
+    fn resolution(&self) -> (usize, usize);
+    fn render(&mut self, image: &Image) -> std::io::Result<()>;
+    /// How many terminal cells, horizontally and vertically, a pixel of the rendered image covers
+    fn cells_per_pixel(&self) -> (f32, f32) {
+        (1.0, 1.0)
+    }
+    /// How many bytes were written for the last frame, for renderers writing to a terminal
+    fn last_frame_size(&self) -> Option<usize> {
+        None
+    }
 }
\ No newline at end of file
@@ -1,58 +1,132 @@
-use crate::core::image::Image;
+use crate::core::image::{Image, Pixel};
 use crate::input::InputAction;
+use crate::render::cell_buffer::{Cell, CellBuffer, Style};
+use crate::render::color::{ColorDepth, TerminalColor, TerminalPalette};
+use crate::render::dithering::{dither, Dithering};
 use crate::render::{Renderer, PIXEL_TYPES, PIXEL_TYPE_COUNT};
-use colored::Colorize;
-use crossterm::{cursor, event, execute, terminal, ExecutableCommand};
+use crossterm::{cursor, event, execute, terminal, Command, ExecutableCommand};
 use std::io::{stdout, Stdout, Write};
 use crossterm::cursor::MoveTo;
 use crossterm::terminal::ClearType;
 
+// every pixel is drawn as two characters next to each other, so that it looks roughly square
+const CHARS_PER_PIXEL: u16 = 2;
+// with half blocks every character shows two pixels above each other, which are about as wide as they are high
+const PIXELS_PER_HALF_BLOCK: u16 = 2;
+const UPPER_HALF_BLOCK: char = '\u{2580}';
+// braille patterns have two columns of four dots, each dot is a pixel
+const BRAILLE_DOTS: (u16, u16) = (2, 4);
+// the braille pattern without dots, the other patterns add the bits of their dots to it
+const BRAILLE_BLANK: u32 = 0x2800;
+// the bit of each dot of a braille pattern, by the row and the column of the dot
+const BRAILLE_DOT_BITS: [[u8; 2]; 4] = [[0x01, 0x08], [0x02, 0x10], [0x04, 0x20], [0x40, 0x80]];
+// the last row of the terminal is left free for the fps counter
+const RESERVED_ROWS: u16 = 1;
+
 pub enum TerminalRenderType {
-    Colored, BlackAndWhite
+    Colored,
+    /// Two pixels in each character, the top one in the color of the upper half block glyph
+    /// and the bottom one in the background color
+    HalfBlock,
+    BlackAndWhite,
+    /// Eight pixels in each character as the dots of a braille pattern, without colors.
+    /// The brightness is dithered into dots that are drawn or not.
+    Braille,
 }
 pub struct TerminalRenderer {
+    // the escape sequences and characters of a frame
     chars_buffer: String,
+    // the cells of the frame that is drawn, and the ones of the frame on the terminal, None if it isn't known
+    back: CellBuffer,
+    front: Option<CellBuffer>,
+    // the bytes written for the last frame
+    last_frame_size: usize,
     stdout: Stdout,
     render_type: TerminalRenderType,
+    dithering: Dithering,
+    // None for terminals with true color
+    palette: Option<TerminalPalette>,
+    color_dithering: bool,
+    terminal_size: (u16, u16),
+    max_resolution: Option<(usize, usize)>,
+    needs_clear: bool,
 }
 impl Renderer for TerminalRenderer {
-    fn tick(&mut self, mut actions: impl Iterator<Item=InputAction>) {
-        if actions.any(|a| a == InputAction::ChangeRenderType) {
-            self.render_type = match self.render_type {
-                TerminalRenderType::Colored => TerminalRenderType::BlackAndWhite,
-                TerminalRenderType::BlackAndWhite => TerminalRenderType::Colored,
-            };
+    fn tick(&mut self, actions: impl Iterator<Item=InputAction>) {
+        for action in actions {
+            match action {
+                InputAction::ChangeRenderType => {
+                    self.render_type = match self.render_type {
+                        TerminalRenderType::Colored => TerminalRenderType::HalfBlock,
+                        TerminalRenderType::HalfBlock => TerminalRenderType::BlackAndWhite,
+                        TerminalRenderType::BlackAndWhite => TerminalRenderType::Braille,
+                        TerminalRenderType::Braille => TerminalRenderType::Colored,
+                    };
+                    // the render types fill the terminal with images of different sizes
+                    self.needs_clear = true;
+                }
+                InputAction::Resize { columns, rows } => {
+                    self.terminal_size = (columns, rows);
+                    self.needs_clear = true;
+                }
+                _ => {}
+            }
         }
     }
 
-    fn render(&mut self, image: &Image) {
-        let _ = execute!(self.stdout, MoveTo(0, 0));
+    fn resolution(&self) -> (usize, usize) {
+        let (columns, rows) = self.terminal_size;
+        let rows = rows.saturating_sub(RESERVED_ROWS);
+        let (width, height) = match self.render_type {
+            TerminalRenderType::HalfBlock => (columns, rows * PIXELS_PER_HALF_BLOCK),
+            TerminalRenderType::Braille => (columns * BRAILLE_DOTS.0, rows * BRAILLE_DOTS.1),
+            TerminalRenderType::Colored | TerminalRenderType::BlackAndWhite => (columns / CHARS_PER_PIXEL, rows),
+        };
+        let (width, height) = (width.max(1) as usize, height.max(1) as usize);
+        match self.max_resolution {
+            Some((max_width, max_height)) => (width.min(max_width), height.min(max_height)),
+            None => (width, height),
+        }
+    }
 
-        for (index, pixel) in image.pixels().iter().enumerate() {
-            if index % Image::width() == 0 {
-                self.chars_buffer.push_str("\n");
-            }
-            let c = match self.render_type {
-                TerminalRenderType::Colored => {
-                    let (r, g, b) = pixel.color();
-                    let c = " ".on_truecolor(r, g, b);
-                    format!("{}{}", c, c)
-                }
-                TerminalRenderType::BlackAndWhite => {
-                    let intensity = pixel.intensity();
-                    let mut index = (intensity * PIXEL_TYPE_COUNT as f32).floor() as usize;
-                    if index >= PIXEL_TYPE_COUNT {
-                        index = PIXEL_TYPE_COUNT - 1;
-                    }
-                    format!("{}", PIXEL_TYPES[index])
-                }
-            };
-            self.chars_buffer.push_str(&c);
+    fn cells_per_pixel(&self) -> (f32, f32) {
+        match self.render_type {
+            TerminalRenderType::HalfBlock => (1.0, 1.0 / PIXELS_PER_HALF_BLOCK as f32),
+            TerminalRenderType::Braille => (1.0 / BRAILLE_DOTS.0 as f32, 1.0 / BRAILLE_DOTS.1 as f32),
+            TerminalRenderType::Colored | TerminalRenderType::BlackAndWhite => (CHARS_PER_PIXEL as f32, 1.0),
+        }
+    }
+
+    fn render(&mut self, image: &Image) -> std::io::Result<()> {
+        if self.needs_clear {
+            execute!(self.stdout, terminal::Clear(ClearType::All))?;
+            // everything is drawn again on the cleared terminal
+            self.front = None;
+            self.needs_clear = false;
+        }
+        match self.render_type {
+            TerminalRenderType::Colored => self.fill_colored(image),
+            TerminalRenderType::HalfBlock => self.fill_half_blocks(image),
+            TerminalRenderType::BlackAndWhite => self.fill_ascii(image),
+            TerminalRenderType::Braille => self.fill_braille(image),
         }
 
-        self.stdout.write_all(self.chars_buffer.as_bytes()).unwrap();
-        self.stdout.flush().unwrap();
+        self.back.write_changes(self.front.as_ref(), &mut self.chars_buffer);
+        // the cursor is left at the start of the row after the image, where the fps counter is written
+        let _ = MoveTo(0, self.back.height() as u16).write_ansi(&mut self.chars_buffer);
+        self.last_frame_size = self.chars_buffer.len();
+        self.stdout.write_all(self.chars_buffer.as_bytes())?;
+        self.stdout.flush()?;
         self.chars_buffer.clear();
+
+        // the buffer that was shown is drawn over in the next frame
+        let shown = std::mem::replace(&mut self.back, self.front.take().unwrap_or_default());
+        self.front = Some(shown);
+        Ok(())
+    }
+
+    fn last_frame_size(&self) -> Option<usize> {
+        Some(self.last_frame_size)
     }
 }
 
@@ -67,18 +141,163 @@ impl TerminalRenderer {
             .expect("Terminal doesn't allow cursor hiding capture")
             .execute(terminal::Clear(ClearType::All))
             .expect("Terminal couldn't be cleared");
+        let terminal_size = terminal::size().expect("Terminal size couldn't be queried");
 
         TerminalRenderer {
             chars_buffer: String::new(),
+            back: CellBuffer::default(),
+            front: None,
+            last_frame_size: 0,
             stdout,
-            render_type
+            render_type,
+            dithering: Dithering::default(),
+            palette: None,
+            color_dithering: false,
+            terminal_size,
+            max_resolution: None,
+            needs_clear: false,
+        }
+    }
+
+    /// How the brightness is turned into dots in the braille render type
+    pub fn set_dithering(&mut self, dithering: Dithering) {
+        self.dithering = dithering;
+    }
+
+    /// The colors the terminal can show, the colors of the image are replaced by the nearest ones
+    pub fn set_color_depth(&mut self, color_depth: ColorDepth) {
+        self.palette = TerminalPalette::new(color_depth);
+    }
+
+    /// Whether the colors of terminals without true color are dithered with Floyd–Steinberg
+    pub fn set_color_dithering(&mut self, color_dithering: bool) {
+        self.color_dithering = color_dithering;
+    }
+
+    /// Limits the resolution of the image, which is otherwise as large as the terminal
+    pub fn set_max_resolution(&mut self, max_resolution: Option<(usize, usize)>) {
+        self.max_resolution = max_resolution;
+    }
+
+    // the colors of the pixels as the terminal can show them
+    fn terminal_colors(&self, image: &Image) -> Vec<TerminalColor> {
+        let colors: Vec<(u8, u8, u8)> = image.pixels().iter().map(shown_color).collect();
+        match &self.palette {
+            Some(palette) => palette.quantize(&colors, image.width(), self.color_dithering),
+            None => colors.into_iter().map(|(r, g, b)| TerminalColor::Rgb(r, g, b)).collect(),
+        }
+    }
+
+    // every pixel is two characters with its background color
+    fn fill_colored(&mut self, image: &Image) {
+        let width = image.width();
+        let colors = self.terminal_colors(image);
+        self.back.reset(width * CHARS_PER_PIXEL as usize, image.height());
+        for (index, &color) in colors.iter().enumerate() {
+            let cell = Cell { symbol: ' ', style: Style { background: Some(color), ..Style::default() } };
+            for char_index in 0..CHARS_PER_PIXEL as usize {
+                self.back.set((index % width) * CHARS_PER_PIXEL as usize + char_index, index / width, cell);
+            }
+        }
+    }
+
+    // every row of characters shows two rows of pixels, the last row of an image with an odd height
+    // is shown in the top halves only
+    fn fill_half_blocks(&mut self, image: &Image) {
+        let width = image.width();
+        let colors = self.terminal_colors(image);
+        let pixels_per_block = PIXELS_PER_HALF_BLOCK as usize;
+        self.back.reset(width, image.height().div_ceil(pixels_per_block));
+        for (row, pixel_rows) in colors.chunks((width * pixels_per_block).max(1)).enumerate() {
+            let (top_row, bottom_row) = pixel_rows.split_at(width.min(pixel_rows.len()));
+            for (x, &top) in top_row.iter().enumerate() {
+                let style = Style { foreground: Some(top), background: bottom_row.get(x).copied(), reversed: false };
+                self.back.set(x, row, Cell { symbol: UPPER_HALF_BLOCK, style });
+            }
+        }
+    }
+
+    // every pixel is two characters of the ascii ramp, which is denser for brighter pixels
+    fn fill_ascii(&mut self, image: &Image) {
+        let width = image.width();
+        self.back.reset(width * CHARS_PER_PIXEL as usize, image.height());
+        for (index, pixel) in image.pixels().iter().enumerate() {
+            let intensity = pixel.intensity();
+            let mut type_index = (intensity * PIXEL_TYPE_COUNT as f32).floor() as usize;
+            if type_index >= PIXEL_TYPE_COUNT {
+                type_index = PIXEL_TYPE_COUNT - 1;
+            }
+            let style = Style { reversed: pixel.is_selected(), ..Style::default() };
+            for (char_index, symbol) in PIXEL_TYPES[type_index].chars().enumerate() {
+                self.back.set((index % width) * CHARS_PER_PIXEL as usize + char_index, index / width, Cell { symbol, style });
+            }
+        }
+    }
+
+    // every character shows 2x4 pixels as the dots of a braille pattern, a character with a selected pixel
+    // is drawn with reversed colors
+    fn fill_braille(&mut self, image: &Image) {
+        let width = image.width();
+        let intensities: Vec<f32> = image.pixels().iter().map(Pixel::intensity).collect();
+        let dots = dither(&intensities, width, self.dithering);
+        let (dot_columns, dot_rows) = (BRAILLE_DOTS.0 as usize, BRAILLE_DOTS.1 as usize);
+        self.back.reset(width.div_ceil(dot_columns), image.height().div_ceil(dot_rows));
+        for row in 0..self.back.height() {
+            for column in 0..self.back.width() {
+                let mut pattern = 0;
+                let mut selected = false;
+                for (dot_row, row_bits) in BRAILLE_DOT_BITS.iter().enumerate() {
+                    for (dot_column, &bit) in row_bits.iter().enumerate() {
+                        let (x, y) = (column * dot_columns + dot_column, row * dot_rows + dot_row);
+                        if x >= width || y >= image.height() {
+                            continue;
+                        }
+                        if dots[y * width + x] {
+                            pattern |= bit;
+                        }
+                        selected |= image.pixels()[y * width + x].is_selected();
+                    }
+                }
+                let style = Style { reversed: selected, ..Style::default() };
+                self.back.set(column, row, Cell { symbol: braille_pattern(pattern), style });
+            }
         }
     }
 }
 
+// the selected object is tinted with this color
+const HIGHLIGHT_COLOR: (u8, u8, u8) = (255, 170, 0);
+
+// the braille character with the dots of the bits of the pattern
+fn braille_pattern(pattern: u8) -> char {
+    char::from_u32(BRAILLE_BLANK + pattern as u32).expect("braille patterns are characters")
+}
+
+fn shown_color(pixel: &Pixel) -> (u8, u8, u8) {
+    if pixel.is_selected() { highlighted(pixel.color()) } else { pixel.color() }
+}
+
+fn highlighted((r, g, b): (u8, u8, u8)) -> (u8, u8, u8) {
+    let blend = |channel: u8, highlight: u8| ((channel as u16 + highlight as u16) / 2) as u8;
+    let (highlight_r, highlight_g, highlight_b) = HIGHLIGHT_COLOR;
+    (blend(r, highlight_r), blend(g, highlight_g), blend(b, highlight_b))
+}
+
 impl Drop for TerminalRenderer {
     fn drop(&mut self) {
         let _ = self.stdout.execute(terminal::Clear(ClearType::All));
         terminal::disable_raw_mode().unwrap();
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn braille_dots_are_numbered_by_column() {
+        assert_eq!(braille_pattern(0), '\u{2800}');
+        // the left column and then the bottom row
+        assert_eq!(braille_pattern(BRAILLE_DOT_BITS.iter().map(|row| row[0]).sum()), '⡇');
+        assert_eq!(braille_pattern(BRAILLE_DOT_BITS[3][0] | BRAILLE_DOT_BITS[3][1]), '⣀');
+    }
+}
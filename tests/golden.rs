@@ -0,0 +1,284 @@
+//! Golden image tests: small deterministic scenes are rendered with `Image::write` and compared
+//! with the reference images in `tests/golden`.
+//!
+//! When a change to the renderer is intended, run the tests with `UPDATE_GOLDEN=1` to overwrite the references,
+//! and check the new images before committing them. A failing test writes the rendered image and an
+//! amplified difference image into `target/golden`.
+
+use ascii_engine::core::camera::Camera;
+use ascii_engine::core::image::Image;
+use ascii_engine::core::light::{AreaLight, AreaLightShape, Attenuation, DirectionalLight, Light, PointLight, SpotLight};
+use ascii_engine::core::material::Material;
+use ascii_engine::core::obj::parse_obj;
+use ascii_engine::core::scene::Scene;
+use ascii_engine::core::shapes::{AxisAlignedBox, Cone, Cylinder, Disc, Plane, Sphere, Triangle};
+use ascii_engine::render::file_renderer::write_png;
+use cgmath::{Deg, Vector3};
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+
+const WIDTH: usize = 96;
+const HEIGHT: usize = 64;
+
+/// How far a rendering may be from its reference and still pass
+struct Tolerance {
+    /// The largest difference of a color channel that doesn't count as a difference at all
+    per_channel: u8,
+    /// The fraction of pixels that may differ by more than `per_channel`, e.g. at the edges of shapes,
+    /// where rounding differences between platforms can flip a pixel
+    max_differing_pixels: f64,
+    min_psnr: f64,
+}
+
+const DEFAULT_TOLERANCE: Tolerance = Tolerance {
+    per_channel: 2,
+    max_differing_pixels: 0.002,
+    min_psnr: 40.0,
+};
+
+struct Comparison {
+    differing_pixels: usize,
+    psnr: f64,
+    /// For every pixel, the largest difference of its channels
+    differences: Vec<u8>,
+}
+
+fn compare(actual: &[u8], expected: &[u8], tolerance: &Tolerance) -> Comparison {
+    let mut squared_error_sum = 0.0;
+    let differences: Vec<u8> = actual
+        .chunks_exact(3)
+        .zip(expected.chunks_exact(3))
+        .map(|(a, e)| {
+            a.iter()
+                .zip(e)
+                .map(|(&a, &e)| {
+                    let difference = a.abs_diff(e);
+                    squared_error_sum += (difference as f64).powi(2);
+                    difference
+                })
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+    let differing_pixels = differences.iter().filter(|&&d| d > tolerance.per_channel).count();
+    Comparison {
+        differing_pixels,
+        psnr: psnr(squared_error_sum / actual.len().max(1) as f64),
+        differences,
+    }
+}
+
+/// Peak signal to noise ratio in decibels, infinite for identical images
+fn psnr(mean_squared_error: f64) -> f64 {
+    if mean_squared_error == 0.0 {
+        return f64::INFINITY;
+    }
+    10.0 * (255.0 * 255.0 / mean_squared_error).log10()
+}
+
+fn to_rgb(image: &Image) -> Vec<u8> {
+    image.pixels().iter().flat_map(|pixel| {
+        let (r, g, b) = pixel.color();
+        [r, g, b]
+    }).collect()
+}
+
+fn read_png(path: &Path) -> Option<(usize, usize, Vec<u8>)> {
+    let mut reader = png::Decoder::new(File::open(path).ok()?).read_info().ok()?;
+    let mut rgb = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut rgb).ok()?;
+    if info.color_type != png::ColorType::Rgb || info.bit_depth != png::BitDepth::Eight {
+        return None;
+    }
+    rgb.truncate(info.buffer_size());
+    Some((info.width as usize, info.height as usize, rgb))
+}
+
+fn save_png(path: &Path, width: usize, height: usize, rgb: &[u8]) {
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    let mut writer = BufWriter::new(File::create(path).unwrap());
+    write_png(&mut writer, width, height, rgb).unwrap();
+}
+
+fn render(scene: &mut Scene) -> Image {
+    let mut image = Image::new(WIDTH, HEIGHT);
+    scene.camera_mut().set_aspect_ratio(image.aspect_ratio());
+    scene.update_acceleration();
+    image.write(scene);
+    image
+}
+
+fn assert_matches_golden(name: &str, scene: &mut Scene) {
+    let actual = to_rgb(&render(scene));
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let reference_path = root.join("tests/golden").join(format!("{}.png", name));
+
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        save_png(&reference_path, WIDTH, HEIGHT, &actual);
+        return;
+    }
+
+    let output_dir: PathBuf = root.join("target/golden");
+    let actual_path = output_dir.join(format!("{}.actual.png", name));
+    let (width, height, expected) = if let Some(reference) = read_png(&reference_path) {
+        reference
+    } else {
+        save_png(&actual_path, WIDTH, HEIGHT, &actual);
+        panic!(
+            "no readable reference image at {}, the rendering was written to {}, run with UPDATE_GOLDEN=1 to accept it",
+            reference_path.display(), actual_path.display(),
+        );
+    };
+    assert_eq!((width, height), (WIDTH, HEIGHT), "the reference image of {} has a different size", name);
+
+    let tolerance = DEFAULT_TOLERANCE;
+    let comparison = compare(&actual, &expected, &tolerance);
+    let allowed_pixels = (tolerance.max_differing_pixels * (WIDTH * HEIGHT) as f64) as usize;
+    if comparison.differing_pixels <= allowed_pixels && comparison.psnr >= tolerance.min_psnr {
+        return;
+    }
+
+    // the differences are amplified, so that even small ones are visible
+    let diff_path = output_dir.join(format!("{}.diff.png", name));
+    let diff: Vec<u8> = comparison.differences.iter()
+        .flat_map(|&d| {
+            let amplified = d.saturating_mul(8);
+            if d > tolerance.per_channel { [255, 255 - amplified, 255 - amplified] } else { [amplified; 3] }
+        })
+        .collect();
+    save_png(&actual_path, WIDTH, HEIGHT, &actual);
+    save_png(&diff_path, WIDTH, HEIGHT, &diff);
+    panic!(
+        "{} differs from its reference: {} pixels differ (at most {} may), PSNR {:.1} dB (at least {:.1} needed).\n\
+         rendering: {}\ndifference: {}",
+        name, comparison.differing_pixels, allowed_pixels, comparison.psnr, tolerance.min_psnr,
+        actual_path.display(), diff_path.display(),
+    );
+}
+
+fn white() -> Vector3<f32> {
+    Vector3::new(1.0, 1.0, 1.0)
+}
+
+fn scene_looking_at(position: Vector3<f32>, target: Vector3<f32>) -> Scene {
+    Scene::new(Camera::from_position_and_target(position, target))
+}
+
+fn add_floor(scene: &mut Scene) {
+    let floor = scene.add_material(Material::diffuse(Vector3::new(0.6, 0.6, 0.6)));
+    scene.add_shape(Plane::new(Vector3::new(0.0, -1.0, 0.0), Vector3::unit_y(), floor));
+}
+
+#[test]
+fn spheres_with_point_light() {
+    let mut scene = scene_looking_at(Vector3::new(0.0, 1.0, 4.0), Vector3::new(0.0, 0.0, -1.0));
+    add_floor(&mut scene);
+    let red = scene.add_material(Material::diffuse(Vector3::new(0.9, 0.2, 0.2)));
+    let glossy = scene.add_material(Material::glossy(Vector3::new(0.2, 0.4, 0.9), Vector3::new(0.6, 0.6, 0.6), 32.0));
+    let glowing = scene.add_material(Material::emissive(Vector3::new(1.0, 0.8, 0.3)));
+    scene.add_shape(Sphere::new(Vector3::new(-1.2, 0.0, -1.0), 1.0, red));
+    scene.add_shape(Sphere::new(Vector3::new(1.2, 0.0, -1.0), 1.0, glossy));
+    scene.add_shape(Sphere::new(Vector3::new(0.0, -0.7, 0.5), 0.3, glowing));
+    scene.add_light(Light::from_position(Vector3::new(2.0, 4.0, 2.0)));
+
+    assert_matches_golden("spheres_with_point_light", &mut scene);
+}
+
+#[test]
+fn shapes_with_spot_and_directional_light() {
+    let mut scene = scene_looking_at(Vector3::new(0.0, 2.0, 6.0), Vector3::new(0.0, 0.0, 0.0));
+    add_floor(&mut scene);
+    let orange = scene.add_material(Material::glossy(Vector3::new(0.9, 0.5, 0.1), Vector3::new(0.3, 0.3, 0.3), 16.0));
+    let green = scene.add_material(Material::diffuse(Vector3::new(0.2, 0.8, 0.3)));
+    let purple = scene.add_material(Material::diffuse(Vector3::new(0.6, 0.2, 0.8)));
+    scene.add_shape(AxisAlignedBox::new(Vector3::new(-3.0, -1.0, -1.0), Vector3::new(-1.6, 0.4, 0.4), orange));
+    scene.add_shape(Cylinder::new(Vector3::new(0.0, -1.0, 0.0), Vector3::unit_y(), 0.6, 1.5, green));
+    scene.add_shape(Cone::new(Vector3::new(2.2, -1.0, 0.0), Vector3::unit_y(), 0.7, 1.6, purple));
+    scene.add_shape(Disc::new(Vector3::new(0.0, 1.5, -2.0), Vector3::new(0.0, 0.3, 1.0), 0.8, orange));
+    scene.add_shape(Triangle::new(
+        Vector3::new(-1.0, -1.0, 1.5),
+        Vector3::new(1.0, -1.0, 1.5),
+        Vector3::new(0.0, -0.2, 1.5),
+        purple,
+    ));
+    scene.add_light(SpotLight::new(
+        Vector3::new(0.0, 4.0, 2.0),
+        Vector3::new(0.0, -1.0, -0.5),
+        Deg(25.0),
+        Deg(40.0),
+        white(),
+        1.5,
+        Attenuation::None,
+    ).into());
+    scene.add_light(DirectionalLight::new(Vector3::new(1.0, -1.0, -0.5), white(), 0.4).into());
+
+    assert_matches_golden("shapes_with_spot_and_directional_light", &mut scene);
+}
+
+#[test]
+fn mirror_and_glass() {
+    let mut scene = scene_looking_at(Vector3::new(0.0, 0.5, 4.0), Vector3::new(0.0, 0.0, -1.0));
+    add_floor(&mut scene);
+    let mirror = scene.add_material(Material { reflectivity: 0.9, ..Material::diffuse(Vector3::new(0.9, 0.9, 0.9)) });
+    let glass = scene.add_material(Material {
+        transparency: 0.9,
+        refractive_index: 1.5,
+        ..Material::glossy(Vector3::new(0.9, 0.9, 0.9), Vector3::new(0.5, 0.5, 0.5), 64.0)
+    });
+    let checker_red = scene.add_material(Material::diffuse(Vector3::new(0.9, 0.1, 0.1)));
+    let checker_blue = scene.add_material(Material::diffuse(Vector3::new(0.1, 0.1, 0.9)));
+    scene.add_shape(Sphere::new(Vector3::new(-1.1, 0.0, -1.5), 1.0, mirror));
+    scene.add_shape(Sphere::new(Vector3::new(0.9, -0.3, 0.0), 0.7, glass));
+    scene.add_shape(AxisAlignedBox::new(Vector3::new(0.2, -1.0, -4.0), Vector3::new(2.5, 1.5, -3.5), checker_red));
+    scene.add_shape(AxisAlignedBox::new(Vector3::new(-3.0, -1.0, 1.0), Vector3::new(-2.0, 0.5, 2.0), checker_blue));
+    scene.add_light(PointLight::new(Vector3::new(0.0, 4.0, 3.0), white(), 5.0, Attenuation::Linear).into());
+
+    assert_matches_golden("mirror_and_glass", &mut scene);
+}
+
+#[test]
+fn mesh_with_area_light() {
+    let mut scene = scene_looking_at(Vector3::new(2.5, 2.0, 3.5), Vector3::new(0.0, -0.3, 0.0));
+    add_floor(&mut scene);
+    let material = scene.add_material(Material::glossy(Vector3::new(0.8, 0.7, 0.5), Vector3::new(0.4, 0.4, 0.4), 24.0));
+    // a unit cube with smooth vertex normals on its top face only
+    let cube = "\
+        v -0.5 -1 -0.5\nv 0.5 -1 -0.5\nv 0.5 0 -0.5\nv -0.5 0 -0.5\n\
+        v -0.5 -1 0.5\nv 0.5 -1 0.5\nv 0.5 0 0.5\nv -0.5 0 0.5\n\
+        vn 0 1 0\nvn -0.5 1 -0.5\n\
+        f 1 2 3 4\nf 5 8 7 6\nf 1 5 6 2\nf 4//2 3//1 7//1 8//2\nf 2 6 7 3\nf 1 4 8 5\n";
+    scene.add_shape(parse_obj(cube.as_bytes(), material).unwrap());
+    scene.add_light(AreaLight::new(
+        AreaLightShape::Rectangle {
+            center: Vector3::new(-1.0, 3.0, 1.0),
+            edge1: Vector3::new(0.6, 0.0, 0.0),
+            edge2: Vector3::new(0.0, 0.0, 0.6),
+        },
+        white(),
+        1.2,
+        Attenuation::None,
+        4,
+    ).into());
+
+    assert_matches_golden("mesh_with_area_light", &mut scene);
+}
+
+#[test]
+fn psnr_of_identical_images_is_infinite() {
+    let rgb = [1, 2, 3, 4, 5, 6];
+    let comparison = compare(&rgb, &rgb, &DEFAULT_TOLERANCE);
+    assert_eq!(comparison.differing_pixels, 0);
+    assert_eq!(comparison.psnr, f64::INFINITY);
+}
+
+#[test]
+fn differences_beyond_tolerance_are_counted() {
+    let expected = [100, 100, 100, 100, 100, 100];
+    let actual = [102, 99, 100, 100, 110, 100];
+    let comparison = compare(&actual, &expected, &DEFAULT_TOLERANCE);
+    assert_eq!(comparison.differences, vec![2, 10]);
+    assert_eq!(comparison.differing_pixels, 1);
+    // mean squared error of (4 + 1 + 100) / 6
+    assert!((comparison.psnr - psnr(17.5)).abs() < 1e-9);
+}